@@ -88,7 +88,9 @@
             contents = [ static ];
 
             config = {
-              Cmd = [ "${self'.packages.default}/bin/${cargoToml.package.name}" ];
+              # An entrypoint so that Fly's release_command can pass a subcommand.
+              Entrypoint = [ "${self'.packages.default}/bin/${cargoToml.package.name}" ];
+              Cmd = [ "serve" ];
             };
           };
 
@@ -0,0 +1,5 @@
+alter table todos
+    add column search tsvector
+    generated always as (to_tsvector('english', content)) stored;
+
+create index on todos using gin(search);
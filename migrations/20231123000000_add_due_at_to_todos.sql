@@ -0,0 +1,5 @@
+alter table todos add column due_at timestamptz;
+
+-- Keyset pagination walks these in order, tie-broken by id.
+create index on todos(user_id, created_at, todo_id);
+create index on todos(user_id, due_at, todo_id);
@@ -0,0 +1,15 @@
+-- Manual ordering. Positions are spaced out so that a todo can usually be moved
+-- between two others by updating just its own row.
+alter table todos add column position bigint;
+
+update todos
+set position = ranked.rank * 65536
+from (
+    select todo_id, row_number() over (partition by user_id order by created_at, todo_id) as rank
+    from todos
+) ranked
+where todos.todo_id = ranked.todo_id;
+
+alter table todos alter column position set not null;
+
+create index on todos(user_id, position, todo_id);
@@ -0,0 +1,6 @@
+-- Subtasks. Deleting a todo deletes everything nested under it.
+alter table todos
+    add column parent_id uuid references todos(todo_id) on delete cascade,
+    add constraint todos_parent_id_check check (parent_id <> todo_id);
+
+create index on todos(parent_id);
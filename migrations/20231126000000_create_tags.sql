@@ -0,0 +1,34 @@
+create table tags (
+    tag_id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users(user_id) on delete cascade,
+    -- Lowercase, as written after the `#`.
+    name text not null,
+    -- `#rrggbb`.
+    color text not null,
+    created_at timestamptz not null default now(),
+    unique (user_id, name)
+);
+
+create table todo_tags (
+    todo_id uuid not null references todos(todo_id) on delete cascade,
+    tag_id uuid not null references tags(tag_id) on delete cascade,
+    primary key (todo_id, tag_id)
+);
+
+create index on todo_tags(tag_id);
+
+-- A todo's tags as they're loaded along with it, by name.
+create function tags_of_todo(todo_id uuid) returns jsonb
+language sql stable
+as $$
+    select coalesce(
+        jsonb_agg(
+            jsonb_build_object('tagId', tags.tag_id, 'name', tags.name, 'color', tags.color)
+            order by tags.name
+        ),
+        '[]'
+    )
+    from todo_tags
+    join tags on tags.tag_id = todo_tags.tag_id
+    where todo_tags.todo_id = $1
+$$;
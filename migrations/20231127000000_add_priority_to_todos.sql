@@ -0,0 +1,6 @@
+-- 0 (none) through 4 (urgent); see `data::priority`.
+alter table todos
+    add column priority smallint not null default 0,
+    add constraint todos_priority_check check (priority between 0 and 4);
+
+create index on todos(user_id, priority, todo_id);
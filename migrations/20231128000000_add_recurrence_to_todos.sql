@@ -0,0 +1,2 @@
+-- An RRULE subset; see `data::recurrence`. Null for todos that don't repeat.
+alter table todos add column recurrence text;
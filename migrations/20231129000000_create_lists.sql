@@ -0,0 +1,20 @@
+create table lists (
+    list_id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users(user_id) on delete cascade,
+    -- Lowercase, as written after the `@`.
+    name text not null,
+    created_at timestamptz not null default now(),
+    unique (user_id, name)
+);
+
+-- Deleting a list leaves its todos without one.
+alter table todos add column list_id uuid references lists(list_id) on delete set null;
+
+create index on todos(list_id);
+
+-- The name of a todo's list, loaded along with it.
+create function list_name(list_id uuid) returns text
+language sql stable
+as $$
+    select name from lists where list_id = $1
+$$;
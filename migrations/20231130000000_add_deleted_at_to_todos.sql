@@ -0,0 +1,4 @@
+-- Deleted todos go to the trash first; see `data::trash`.
+alter table todos add column deleted_at timestamptz;
+
+create index on todos(user_id, deleted_at) where deleted_at is not null;
@@ -0,0 +1,10 @@
+-- When todos were done, and when they were put in the archive; see
+-- `data::archive`. Todos done before this was tracked count as done now.
+alter table todos add column completed_at timestamptz;
+alter table todos add column archived_at timestamptz;
+
+update todos set completed_at = now() where done;
+
+alter table todos add constraint todos_completed_at_check check (done = (completed_at is not null));
+
+create index on todos(user_id, archived_at) where archived_at is not null;
@@ -0,0 +1,38 @@
+-- What happened to each todo, and who did it; see `data::history`.
+create table todo_events (
+    event_id uuid primary key default gen_random_uuid(),
+    todo_id uuid not null references todos(todo_id) on delete cascade,
+    actor_id uuid not null references users(user_id) on delete cascade,
+    change jsonb not null,
+    created_at timestamptz not null default now()
+);
+
+create index on todo_events(todo_id, created_at);
+
+-- Logins and other security-relevant account events; see `data::audit`.
+create type audit_action as enum ('login', 'login_failed', 'password_changed');
+
+create table audit_log (
+    event_id uuid primary key default gen_random_uuid(),
+    -- Null for failed logins with an email nobody has.
+    user_id uuid references users(user_id) on delete cascade,
+    action audit_action not null,
+    email text not null,
+    ip text,
+    created_at timestamptz not null default now()
+);
+
+create index on audit_log(user_id, created_at);
+
+-- Both are append-only.
+create function forbid_update() returns trigger language plpgsql as $$
+begin
+    raise exception '% is append-only', tg_table_name;
+end;
+$$;
+
+create trigger todo_events_append_only before update on todo_events
+    for each row execute function forbid_update();
+
+create trigger audit_log_append_only before update on audit_log
+    for each row execute function forbid_update();
@@ -0,0 +1,3 @@
+-- Bumped by every change to a todo's own fields, so that a client can tell
+-- whether the todo it's editing is still the latest.
+alter table todos add column version bigint not null default 1;
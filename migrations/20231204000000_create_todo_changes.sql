@@ -0,0 +1,103 @@
+-- Every change to a user's todos, numbered per user in the order they were
+-- committed, for clients that sync; see `data::changes`.
+create table todo_changes (
+    user_id uuid not null references users(user_id) on delete cascade,
+    seq bigint not null,
+    -- Not a foreign key: todos deleted for good have to be synced too.
+    todo_id uuid not null,
+    changed_at timestamptz not null default now(),
+    primary key (user_id, seq)
+);
+
+-- The last `seq` handed out to each user.
+create table todo_change_seqs (
+    user_id uuid primary key references users(user_id) on delete cascade,
+    seq bigint not null
+);
+
+create function log_todo_change(changed_user_id uuid, changed_todo_id uuid) returns void
+language plpgsql as $$
+declare
+    next_seq bigint;
+begin
+    -- Deleted along with the user.
+    if not exists(select from users where user_id = changed_user_id) then
+        return;
+    end if;
+
+    insert into todo_change_seqs as seqs(user_id, seq) values (changed_user_id, 1)
+    on conflict (user_id) do update set seq = seqs.seq + 1
+    returning seqs.seq into next_seq;
+
+    insert into todo_changes(user_id, seq, todo_id)
+    values (changed_user_id, next_seq, changed_todo_id);
+end;
+$$;
+
+create function log_todo_row_change() returns trigger language plpgsql as $$
+begin
+    if tg_op = 'DELETE' then
+        perform log_todo_change(old.user_id, old.todo_id);
+    else
+        perform log_todo_change(new.user_id, new.todo_id);
+    end if;
+
+    return null;
+end;
+$$;
+
+create function log_todo_tag_change() returns trigger language plpgsql as $$
+declare
+    changed_todo_id uuid;
+begin
+    if tg_op = 'DELETE' then
+        changed_todo_id := old.todo_id;
+    else
+        changed_todo_id := new.todo_id;
+    end if;
+
+    -- Nothing to do for todos deleted for good, which are logged themselves.
+    perform log_todo_change(user_id, todo_id) from todos where todo_id = changed_todo_id;
+
+    return null;
+end;
+$$;
+
+create function log_tag_change() returns trigger language plpgsql as $$
+begin
+    perform log_todo_change(todos.user_id, todos.todo_id)
+    from todo_tags
+    join todos on todos.todo_id = todo_tags.todo_id
+    where todo_tags.tag_id = new.tag_id;
+
+    return null;
+end;
+$$;
+
+-- Deferred until commit, so that numbers are handed out in the order changes
+-- are committed and each user's counter is only locked while committing.
+create constraint trigger todos_inserted_or_deleted after insert or delete on todos
+    deferrable initially deferred
+    for each row execute function log_todo_row_change();
+
+create constraint trigger todos_changed after update on todos
+    deferrable initially deferred
+    for each row when (old.* is distinct from new.*)
+    execute function log_todo_row_change();
+
+create constraint trigger todo_tags_changed after insert or delete on todo_tags
+    deferrable initially deferred
+    for each row execute function log_todo_tag_change();
+
+create constraint trigger tags_changed after update on tags
+    deferrable initially deferred
+    for each row when (old.* is distinct from new.*)
+    execute function log_tag_change();
+
+-- Todos from before the log started, so that syncing from scratch gets them.
+insert into todo_changes(user_id, seq, todo_id)
+select user_id, row_number() over (partition by user_id order by created_at, todo_id), todo_id
+from todos;
+
+insert into todo_change_seqs(user_id, seq)
+select user_id, count(*) from todos group by user_id;
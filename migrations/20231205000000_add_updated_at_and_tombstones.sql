@@ -0,0 +1,76 @@
+-- When each todo last changed, for clients that poll; see `data::changes`.
+alter table todos add column updated_at timestamptz not null default now();
+
+-- Backfilling isn't a change clients need to hear about.
+alter table todos disable trigger todos_changed;
+update todos set updated_at = greatest(created_at, completed_at, archived_at, deleted_at);
+alter table todos enable trigger todos_changed;
+
+-- Compared as JSON since `when` can't see generated columns in `before`
+-- triggers, leaving out the search column for the same reason.
+create function touch_todo() returns trigger language plpgsql as $$
+begin
+    if to_jsonb(new) - 'search' - 'updated_at' is distinct from to_jsonb(old) - 'search' - 'updated_at' then
+        new.updated_at := now();
+    end if;
+
+    return new;
+end;
+$$;
+
+create trigger todos_touched before update on todos
+    for each row execute function touch_todo();
+
+-- Tags are part of the todo as clients see it.
+create function touch_tagged_todo() returns trigger language plpgsql as $$
+begin
+    if tg_op = 'DELETE' then
+        update todos set updated_at = now() where todo_id = old.todo_id;
+    else
+        update todos set updated_at = now() where todo_id = new.todo_id;
+    end if;
+
+    return null;
+end;
+$$;
+
+create trigger todo_tags_touched after insert or delete on todo_tags
+    for each row execute function touch_tagged_todo();
+
+create function touch_todos_with_tag() returns trigger language plpgsql as $$
+begin
+    update todos set updated_at = now()
+    where todo_id in (select todo_id from todo_tags where tag_id = new.tag_id);
+
+    return null;
+end;
+$$;
+
+create trigger tags_touched after update on tags
+    for each row when (old.* is distinct from new.*)
+    execute function touch_todos_with_tag();
+
+-- Todos deleted for good, so that clients can be told when.
+create table todo_tombstones (
+    todo_id uuid primary key,
+    user_id uuid not null references users(user_id) on delete cascade,
+    deleted_at timestamptz not null default now()
+);
+
+create function bury_todo() returns trigger language plpgsql as $$
+begin
+    if tg_op = 'INSERT' then
+        -- Brought back with the same id, which clients can pick.
+        delete from todo_tombstones where todo_id = new.todo_id;
+    -- Deleted along with the user.
+    elsif exists(select from users where user_id = old.user_id) then
+        insert into todo_tombstones(todo_id, user_id) values (old.todo_id, old.user_id)
+        on conflict (todo_id) do update set user_id = excluded.user_id, deleted_at = now();
+    end if;
+
+    return null;
+end;
+$$;
+
+create trigger todos_buried after insert or delete on todos
+    for each row execute function bury_todo();
@@ -0,0 +1,43 @@
+-- Polling clients sync by `(updated_at, todo_id)`; see `data::changes`. For a
+-- cursor never to skip a change committed late, each user's todos are stamped
+-- in the order they're committed: by the clock, once a lock on the user's
+-- todos that's held until commit has been taken.
+create function lock_todos_for_sync(locked_user_id uuid) returns void language sql as $$
+    select pg_advisory_xact_lock(hashtextextended('todo-sync:' || locked_user_id, 0));
+$$;
+
+-- Setting `updated_at` by hand, as the triggers on tags do, counts as a change.
+create or replace function touch_todo() returns trigger language plpgsql as $$
+begin
+    if tg_op = 'INSERT' or to_jsonb(new) - 'search' is distinct from to_jsonb(old) - 'search' then
+        perform lock_todos_for_sync(new.user_id);
+        new.updated_at := clock_timestamp();
+    end if;
+
+    return new;
+end;
+$$;
+
+create trigger todos_stamped before insert on todos
+    for each row execute function touch_todo();
+
+create or replace function bury_todo() returns trigger language plpgsql as $$
+begin
+    if tg_op = 'INSERT' then
+        -- Brought back with the same id, which clients can pick.
+        delete from todo_tombstones where todo_id = new.todo_id;
+    -- Deleted along with the user.
+    elsif exists(select from users where user_id = old.user_id) then
+        perform lock_todos_for_sync(old.user_id);
+        insert into todo_tombstones(todo_id, user_id, deleted_at)
+        values (old.todo_id, old.user_id, clock_timestamp())
+        on conflict (todo_id) do update
+        set user_id = excluded.user_id, deleted_at = excluded.deleted_at;
+    end if;
+
+    return null;
+end;
+$$;
+
+create index on todos(user_id, updated_at, todo_id);
+create index on todo_tombstones(user_id, deleted_at, todo_id);
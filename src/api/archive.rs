@@ -0,0 +1,108 @@
+//! The archive: done todos put away, by the day they were done; see
+//! [`crate::data::archive`].
+
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderValue, StatusCode},
+    response::Html,
+    routing::*,
+    Form, Json,
+};
+use uuid::Uuid;
+
+use crate::api::{listing::ListParams, todos::render_list};
+use crate::data::{archive, user::AuthSession};
+use crate::{error::Error, live::Change, templates::*, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/archive", get(handle_list_archive_json))
+        .route(
+            "/archive",
+            get(handle_get_archive).post(handle_archive_completed_htmx),
+        )
+        .route(
+            "/archive/:todo_id/restore",
+            post(handle_unarchive_todo_htmx),
+        )
+}
+
+#[axum::debug_handler]
+pub async fn handle_get_archive(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let days = archive::by_day(state.archive.get_archive(user.user_id).await?);
+
+    let tmpl = ArchiveTemplate {
+        user: &Some(user),
+        days: &days,
+        auto_archive_days: state.auto_archive_days,
+    };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+}
+
+/// What's in the archive, most recently done first.
+#[axum::debug_handler]
+pub async fn handle_list_archive_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    Ok(Json(state.archive.get_archive(user.user_id).await?))
+}
+
+/// Archives everything done, then re-renders the list for the filters it's
+/// showing.
+#[axum::debug_handler]
+pub async fn handle_archive_completed_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Form(params): Form<ListParams>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let archived = state.archive.archive_completed(user.user_id).await?;
+    metrics::counter!("todos_archived_total", archived);
+    if archived > 0 {
+        state.live.publish(user.user_id, Change::Many).await;
+    }
+
+    render_list(&state, user.user_id, &params).await
+}
+
+/// Takes a todo back out of the archive. The row in the archive is left empty,
+/// and the list of todos is told to reload.
+#[axum::debug_handler]
+pub async fn handle_unarchive_todo_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    match state.archive.unarchive_todo(user.user_id, todo_id).await {
+        Ok(()) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(Error::Conflict("that todo isn't in the archive".to_owned()))
+        }
+        Err(e) => return Err(e.into()),
+    }
+    state
+        .live
+        .publish(user.user_id, Change::Created(todo_id))
+        .await;
+
+    let mut response = (StatusCode::OK, Html("")).into_response();
+    response
+        .headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("todos-changed"));
+
+    Ok(response)
+}
@@ -1,21 +1,24 @@
 use askama::Template;
 use askama_axum::IntoResponse;
 use axum::{
-    http::StatusCode,
+    extract::State,
+    http::{HeaderMap, StatusCode},
     response::{Html, Redirect},
     routing::*,
-    Extension, Form,
+    Form,
 };
 use serde::Deserialize;
-use sqlx::PgPool;
 use tracing::warn;
 use validator::Validate;
 
-use crate::data::user::AuthSession;
-use crate::{data, error::Error, templates::*};
+use crate::data::{
+    audit::{AuditAction, NewAuditEvent},
+    user::AuthSession,
+};
 use crate::validators::*;
+use crate::{data, error::Error, templates::*, AppState};
 
-pub fn router() -> Router {
+pub fn router() -> Router<AppState> {
     Router::new()
         .route("/login", get(handle_login).post(handle_login_post))
         .route("/signup", get(handle_signup).post(handle_signup_post))
@@ -63,20 +66,64 @@ pub struct LoginForm {
     #[validate(email)]
     pub email: String,
     #[validate(
-        custom(function= "validate_password", message = "Password must be at least 8 characters long and include a digit, an uppercase letter, and a lowercase letter."),
-        regex(path = "RE_SPECIAL_CHAR", message = "Password must contain a special character.")
+        custom(
+            function = "validate_password",
+            message = "Password must be at least 8 characters long and include a digit, an uppercase letter, and a lowercase letter."
+        ),
+        regex(
+            path = "RE_SPECIAL_CHAR",
+            message = "Password must contain a special character."
+        )
     )]
     pub password: String,
 }
 
+/// The client's address, as Fly's proxy saw it.
+fn client_ip(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("fly-client-ip")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+/// Adds to the audit log. Not being able to doesn't stop the user.
+async fn audit(state: &AppState, event: NewAuditEvent) {
+    if let Err(e) = state.audit.record_audit_event(event).await {
+        warn!("Error recording audit event: {:?}", e);
+    }
+}
+
 #[axum::debug_handler]
 pub async fn handle_login_post(
     mut auth_session: AuthSession,
+    State(state): State<AppState>,
+    headers: HeaderMap,
     Form(creds): Form<data::user::Credentials>,
 ) -> impl IntoResponse {
+    let email = creds.email.clone();
+    let ip = client_ip(&headers);
+
     let user = match auth_session.authenticate(creds).await {
         Ok(Some(user)) => user,
         Ok(None) => {
+            metrics::increment_counter!("logins_total", "outcome" => "failure");
+
+            // Goes in the log of whoever has that email, if anyone does.
+            let user_id = match state.users.get_user_by_email(&email).await {
+                Ok(user) => user.map(|user| user.user_id),
+                Err(e) => {
+                    warn!("Error looking up user: {:?}", e);
+                    None
+                }
+            };
+            let event = NewAuditEvent {
+                user_id,
+                action: AuditAction::LoginFailed,
+                email,
+                ip,
+            };
+            audit(&state, event).await;
+
             return LoginTemplate { user: &None }.into_response();
         }
         Err(e) => {
@@ -89,17 +136,30 @@ pub async fn handle_login_post(
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
+    metrics::increment_counter!("logins_total", "outcome" => "success");
+
+    let event = NewAuditEvent {
+        user_id: Some(user.user_id),
+        action: AuditAction::Login,
+        email,
+        ip,
+    };
+    audit(&state, event).await;
+
     Redirect::to("/").into_response()
 }
 
 #[axum::debug_handler]
 pub async fn handle_signup_post(
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
     Form(signup_form): Form<SignupForm>,
 ) -> Result<impl IntoResponse, Error> {
     signup_form.validate()?;
 
-    data::user::create_user(&db, &signup_form.email, &signup_form.password).await?;
+    state
+        .users
+        .create_user(&signup_form.email, &signup_form.password)
+        .await?;
 
     Ok(Redirect::to("/login").into_response())
 }
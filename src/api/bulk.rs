@@ -0,0 +1,180 @@
+//! Changing many todos at once; see [`crate::data::bulk`].
+
+use askama_axum::IntoResponse;
+use axum::{
+    extract::{RawForm, State},
+    routing::*,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::{
+    listing::{parse_datetime_local, ListParams},
+    todos::render_list,
+};
+use crate::data::{
+    bulk::{self, BulkAction, BulkResult, BulkStatus},
+    lists, tags,
+    user::AuthSession,
+};
+use crate::{error::Error, live::Change, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/todos/bulk", post(handle_bulk_update_json))
+        .route("/todos/bulk", post(handle_bulk_update_htmx))
+}
+
+/// What to do to the todos picked.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkRequest {
+    /// `done`, `undone`, `delete`, `move_to_list`, `add_tag`, `remove_tag` or
+    /// `set_due`.
+    pub action: String,
+    /// For `move_to_list`, with or without the `@`. Empty takes the todos off
+    /// their lists.
+    pub list_name: Option<String>,
+    /// For `add_tag` and `remove_tag`, with or without the `#`.
+    pub tag_name: Option<String>,
+    /// For `set_due`, from a `datetime-local` input; see
+    /// [`parse_datetime_local`]. Empty clears the due date.
+    pub due: Option<String>,
+}
+
+impl BulkRequest {
+    fn action(&self) -> Result<BulkAction, Error> {
+        let value = |value: &Option<String>| value.clone().unwrap_or_default();
+
+        Ok(match self.action.as_str() {
+            "done" => BulkAction::Done,
+            "undone" => BulkAction::Undone,
+            "delete" => BulkAction::Delete,
+            "move_to_list" => match value(&self.list_name) {
+                name if name.is_empty() => BulkAction::MoveToList(None),
+                name => BulkAction::MoveToList(Some(lists::normalize(&name).ok_or_else(|| {
+                    Error::UnprocessableEntity(format!("invalid list name: {}", name))
+                })?)),
+            },
+            "add_tag" | "remove_tag" => {
+                let name = value(&self.tag_name);
+                let name = tags::normalize(&name).ok_or_else(|| {
+                    Error::UnprocessableEntity(format!("invalid tag name: {}", name))
+                })?;
+                match self.action.as_str() {
+                    "add_tag" => BulkAction::AddTag(name),
+                    _ => BulkAction::RemoveTag(name),
+                }
+            }
+            "set_due" => BulkAction::SetDue(parse_datetime_local("due", &self.due)?),
+            action => {
+                return Err(Error::UnprocessableEntity(format!(
+                    "invalid action: {}",
+                    action
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkJsonRequest {
+    pub todo_ids: Vec<Uuid>,
+    #[serde(flatten)]
+    pub change: BulkRequest,
+}
+
+/// The bulk form, with a `todo_id` for each todo picked.
+#[derive(Deserialize)]
+pub struct BulkForm {
+    #[serde(flatten)]
+    pub change: BulkRequest,
+    /// The filters the list is showing, so the re-rendered list matches them.
+    #[serde(flatten)]
+    pub listing: ListParams,
+}
+
+#[derive(Serialize)]
+pub struct BulkResponse {
+    /// One for each todo picked, in the order they were given.
+    pub results: Vec<BulkResult>,
+}
+
+async fn update_todos(
+    state: &AppState,
+    user_id: Uuid,
+    todo_ids: &[Uuid],
+    change: &BulkRequest,
+) -> Result<Vec<BulkResult>, Error> {
+    if todo_ids.is_empty() {
+        return Err(Error::UnprocessableEntity(
+            "no todos were picked".to_owned(),
+        ));
+    }
+    if todo_ids.len() > bulk::MAX_TODOS {
+        return Err(Error::UnprocessableEntity(format!(
+            "at most {} todos can be changed at once",
+            bulk::MAX_TODOS
+        )));
+    }
+    let action = change.action()?;
+
+    let results = state.todos.update_todos(user_id, todo_ids, &action).await?;
+    let updated = results
+        .iter()
+        .filter(|result| result.status == BulkStatus::Updated)
+        .count();
+    if action == BulkAction::Done {
+        metrics::counter!("todos_completed_total", updated as u64);
+    }
+    if updated > 0 {
+        state.live.publish(user_id, Change::Many).await;
+    }
+
+    Ok(results)
+}
+
+/// Changes many todos at once, reporting what happened to each.
+#[axum::debug_handler]
+pub async fn handle_bulk_update_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Json(req): Json<BulkJsonRequest>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let results = update_todos(&state, user.user_id, &req.todo_ids, &req.change).await?;
+
+    Ok(Json(BulkResponse { results }))
+}
+
+/// Changes the todos picked in the list, then re-renders it for the filters
+/// it's showing.
+#[axum::debug_handler]
+pub async fn handle_bulk_update_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    RawForm(body): RawForm,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    // Each checkbox picked sends its own `todo_id`, which `Form` can't collect.
+    let invalid = |e: serde_urlencoded::de::Error| Error::UnprocessableEntity(e.to_string());
+    let form: BulkForm = serde_urlencoded::from_bytes(&body).map_err(invalid)?;
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(&body).map_err(invalid)?;
+    let todo_ids = pairs
+        .iter()
+        .filter(|(name, _)| name == "todo_id")
+        .map(|(_, value)| {
+            value
+                .parse()
+                .map_err(|_| Error::UnprocessableEntity(format!("invalid todo_id: {}", value)))
+        })
+        .collect::<Result<Vec<Uuid>, _>>()?;
+
+    update_todos(&state, user.user_id, &todo_ids, &form.change).await?;
+
+    render_list(&state, user.user_id, &form.listing).await
+}
@@ -0,0 +1,588 @@
+//! Syncing over a WebSocket, for clients that work offline and catch up
+//! later; see [`crate::data::changes`].
+//!
+//! Each side sends JSON text messages tagged with their `type`:
+//!
+//! - The client sends `{"type": "pull", "since": 41}` with the last `seq` it
+//!   has seen, or 0 to start from scratch. The server replies with `changes`
+//!   until the client is caught up, always at least once, then keeps sending
+//!   them as more changes are made, from this client or any other.
+//! - The client sends `{"type": "push", "mutations": [...]}` with what it
+//!   changed locally, oldest first. Each mutation has a `mutationId`, and new
+//!   todos have a `todoId`, both made up by the client. The server applies
+//!   them in order and replies with `pushed`, saying what became of each.
+//!   Edits and deletions made against an older `baseVersion` of a todo than
+//!   the server's are left out as conflicts, along with the todo as it is now
+//!   for the client to reconcile. If the server fails part way, it replies
+//!   with `pushed` for whatever it applied before then, if anything, and then
+//!   `error`; the client pushes the rest again.
+
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::IntoResponse,
+    routing::*,
+};
+use futures::{stream::SplitSink, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_with::{de::DeserializeAs, serde_as};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::data::{
+    changes::{self, LoggedChange},
+    lists,
+    priority::Priority,
+    tags,
+    todo::{NewTodo, Todo, TodoChanges},
+    user::AuthSession,
+};
+use crate::{error::Error, live::Change, AppState};
+
+/// The most mutations pushed at once.
+pub const MAX_MUTATIONS: usize = 500;
+
+/// How often the log is checked for changes nobody announced, such as those
+/// made by housekeeping.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// As for todos created or edited through the forms.
+const MAX_CONTENT_LENGTH: usize = 1000;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/v1/changes", get(handle_changes_ws))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Pull { since: i64 },
+    Push { mutations: Vec<Mutation> },
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Changes after the last `seq` the client saw, and the `seq` it's up to
+    /// now.
+    Changes {
+        changes: Vec<LoggedChange>,
+        seq: i64,
+    },
+    /// What became of each mutation pushed, in the order they were pushed.
+    Pushed { results: Vec<MutationResult> },
+    /// The client's last message couldn't be handled.
+    Error { message: String },
+}
+
+/// A change the client made locally.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Mutation {
+    pub mutation_id: Uuid,
+    #[serde(flatten)]
+    pub op: Op,
+}
+
+#[serde_as]
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum Op {
+    Create {
+        todo_id: Uuid,
+        content: String,
+        #[serde_as(as = "Option<Rfc3339>")]
+        due_at: Option<OffsetDateTime>,
+        #[serde(default)]
+        priority: Priority,
+        parent_id: Option<Uuid>,
+        /// Tag names, created as needed.
+        #[serde(default)]
+        tags: Vec<String>,
+        /// A list name, created as needed.
+        list: Option<String>,
+    },
+    /// Changes only the fields given.
+    Update {
+        todo_id: Uuid,
+        base_version: i64,
+        content: Option<String>,
+        done: Option<bool>,
+        /// `null` clears the due date.
+        #[serde(default, deserialize_with = "given_due_at")]
+        due_at: Option<Option<OffsetDateTime>>,
+        priority: Option<Priority>,
+        /// Replaces the todo's tags.
+        tags: Option<Vec<String>>,
+        /// Empty takes the todo off its list.
+        list: Option<String>,
+    },
+    /// Moves the todo to the trash, along with its subtasks.
+    Delete {
+        todo_id: Uuid,
+        base_version: Option<i64>,
+    },
+}
+
+/// A due date that's there, even if it's `null`, so that clearing it can be
+/// told apart from leaving it be.
+fn given_due_at<'de, D>(deserializer: D) -> Result<Option<Option<OffsetDateTime>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    <Option<Rfc3339>>::deserialize_as(deserializer).map(Some)
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationStatus {
+    Applied,
+    /// The todo has changed since the client's `baseVersion`, or there's one
+    /// with the `todoId` already; `todo` is how it is now.
+    Conflict,
+    /// The todo isn't the user's, or has been deleted.
+    NotFound,
+    /// The mutation isn't valid, as `message` says.
+    Rejected,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MutationResult {
+    pub mutation_id: Uuid,
+    pub status: MutationStatus,
+    /// The todo as it is afterwards, unless it's gone.
+    pub todo: Option<Todo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Upgrades to a WebSocket speaking the protocol above.
+#[axum::debug_handler]
+pub async fn handle_changes_ws(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let user = auth_session.user.unwrap();
+
+    ws.on_upgrade(move |socket| feed(socket, state, user.user_id))
+}
+
+async fn feed(socket: WebSocket, state: AppState, user_id: Uuid) {
+    let (mut sender, mut receiver) = socket.split();
+    // Changes are read from the log, so these only say when to look. They
+    // stop when the server starts shutting down.
+    let mut updates = Box::pin(state.live.subscribe(user_id));
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    // Nothing is sent until the client pulls.
+    let mut seen = None;
+
+    loop {
+        let sent = tokio::select! {
+            message = receiver.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    handle_message(&state, user_id, &text, &mut seen, &mut sender).await
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by axum.
+                Some(Ok(_)) => Ok(()),
+            },
+            update = updates.next() => match update {
+                Some(_) => catch_up(&state, user_id, &mut seen, false, &mut sender).await,
+                None => break,
+            },
+            _ = poll.tick() => catch_up(&state, user_id, &mut seen, false, &mut sender).await,
+        };
+
+        // The client has gone.
+        if sent.is_err() {
+            return;
+        }
+    }
+
+    let _ = sender.send(Message::Close(None)).await;
+}
+
+async fn send(
+    sender: &mut SplitSink<WebSocket, Message>,
+    message: &ServerMessage,
+) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap();
+    sender.send(Message::Text(text)).await
+}
+
+async fn handle_message(
+    state: &AppState,
+    user_id: Uuid,
+    text: &str,
+    seen: &mut Option<i64>,
+    sender: &mut SplitSink<WebSocket, Message>,
+) -> Result<(), axum::Error> {
+    let message = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => {
+            let message = format!("invalid message: {}", e);
+            return send(sender, &ServerMessage::Error { message }).await;
+        }
+    };
+
+    match message {
+        ClientMessage::Pull { since } => {
+            *seen = Some(since);
+            catch_up(state, user_id, seen, true, sender).await
+        }
+        ClientMessage::Push { mutations } => {
+            let (results, pushed) = push(state, user_id, &mutations).await;
+            // What was applied is reported even if the rest failed, so that
+            // the client doesn't push it again.
+            if !results.is_empty() || pushed.is_ok() {
+                send(sender, &ServerMessage::Pushed { results }).await?;
+            }
+            if let Err(e) = pushed {
+                warn!("failed to apply pushed mutations: {:?}", e);
+                let message = e.to_string();
+                send(sender, &ServerMessage::Error { message }).await?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Sends the changes the client hasn't seen, a page at a time, if it has
+/// pulled. With `always`, something is sent even if there's nothing new, so
+/// that the client can tell it's caught up.
+async fn catch_up(
+    state: &AppState,
+    user_id: Uuid,
+    seen: &mut Option<i64>,
+    always: bool,
+    sender: &mut SplitSink<WebSocket, Message>,
+) -> Result<(), axum::Error> {
+    let Some(mut seq) = *seen else {
+        return Ok(());
+    };
+    let mut sent = false;
+
+    loop {
+        // One more than fits on a page says whether there are more pages.
+        let mut changes = match state
+            .changes
+            .changes_since(user_id, seq, changes::PAGE_SIZE + 1)
+            .await
+        {
+            Ok(changes) => changes,
+            Err(e) => {
+                warn!("failed to read the change log: {}", e);
+                let message = Error::from(e).to_string();
+                return send(sender, &ServerMessage::Error { message }).await;
+            }
+        };
+
+        let more = changes.len() as i64 > changes::PAGE_SIZE;
+        changes.truncate(changes::PAGE_SIZE as usize);
+        if let Some(last) = changes.last() {
+            seq = last.seq;
+        }
+
+        if !changes.is_empty() || (always && !sent) {
+            send(sender, &ServerMessage::Changes { changes, seq }).await?;
+            sent = true;
+            *seen = Some(seq);
+        }
+
+        if !more {
+            return Ok(());
+        }
+    }
+}
+
+/// Applies the mutations in order, returning what became of each. If one of
+/// them fails, the results up to it come back along with the error.
+async fn push(
+    state: &AppState,
+    user_id: Uuid,
+    mutations: &[Mutation],
+) -> (Vec<MutationResult>, Result<(), Error>) {
+    let mut results = vec![];
+
+    if mutations.len() > MAX_MUTATIONS {
+        let message = format!("at most {} mutations can be pushed at once", MAX_MUTATIONS);
+        return (results, Err(Error::UnprocessableEntity(message)));
+    }
+
+    for mutation in mutations {
+        let result = match apply(state, user_id, &mutation.op).await {
+            Ok((status, todo)) => MutationResult {
+                mutation_id: mutation.mutation_id,
+                status,
+                todo,
+                message: None,
+            },
+            Err(Error::UnprocessableEntity(message)) => MutationResult {
+                mutation_id: mutation.mutation_id,
+                status: MutationStatus::Rejected,
+                todo: None,
+                message: Some(message),
+            },
+            Err(e) => return (results, Err(e)),
+        };
+        results.push(result);
+    }
+
+    (results, Ok(()))
+}
+
+async fn apply(
+    state: &AppState,
+    user_id: Uuid,
+    op: &Op,
+) -> Result<(MutationStatus, Option<Todo>), Error> {
+    match op {
+        Op::Create {
+            todo_id,
+            content,
+            due_at,
+            priority,
+            parent_id,
+            tags,
+            list,
+        } => {
+            check_content(content)?;
+
+            // Say, pushed again after the reply to the first push was lost.
+            if let Some(existing) = current(state, user_id, *todo_id).await? {
+                return Ok((MutationStatus::Conflict, Some(existing)));
+            }
+
+            let new = NewTodo {
+                todo_id: Some(*todo_id),
+                content: content.clone(),
+                due_at: *due_at,
+                priority: *priority,
+                recurrence: None,
+                parent_id: *parent_id,
+                tags: tag_names(tags)?,
+                list: list_name(list.as_deref())?,
+            };
+            let todo = match state.todos.create_todo(user_id, new).await {
+                Ok(todo) => todo,
+                Err(sqlx::Error::RowNotFound) => {
+                    return Err(Error::UnprocessableEntity(
+                        "unknown parent, or the todoId is taken".to_owned(),
+                    ))
+                }
+                Err(e) => return Err(e.into()),
+            };
+            metrics::increment_counter!("todos_created_total");
+            state.live.publish(user_id, Change::Created(*todo_id)).await;
+
+            Ok((MutationStatus::Applied, Some(todo)))
+        }
+        Op::Update {
+            todo_id,
+            base_version,
+            content,
+            done,
+            due_at,
+            priority,
+            tags,
+            list,
+        } => {
+            let Some(todo) = current(state, user_id, *todo_id).await? else {
+                return Ok((MutationStatus::NotFound, None));
+            };
+            if todo.version != *base_version {
+                return Ok((MutationStatus::Conflict, Some(todo)));
+            }
+
+            let edited = content.is_some()
+                || due_at.is_some()
+                || priority.is_some()
+                || tags.is_some()
+                || list.is_some();
+            let toggled = done.is_some_and(|done| done != todo.done);
+            if let Some(content) = content {
+                check_content(content)?;
+            }
+
+            // Either way, all of it is applied at once or not at all.
+            let applied = if edited {
+                let changes = TodoChanges {
+                    content: content.clone().unwrap_or_else(|| todo.content.clone()),
+                    due_at: due_at.unwrap_or(todo.due_at),
+                    priority: priority.unwrap_or(todo.priority),
+                    recurrence: todo.recurrence.clone(),
+                    tags: match tags {
+                        Some(names) => tag_names(names)?,
+                        None => todo.tags.iter().map(|tag| tag.name.clone()).collect(),
+                    },
+                    list: match list {
+                        Some(name) => list_name(Some(name))?,
+                        None => todo.list.clone(),
+                    },
+                    done: *done,
+                    version: Some(*base_version),
+                };
+                state
+                    .todos
+                    .update_todo_by_id(user_id, *todo_id, changes)
+                    .await
+            } else if toggled {
+                state
+                    .todos
+                    .toggle_todo_by_id(user_id, *todo_id, Some(*base_version))
+                    .await
+            } else {
+                Ok(None)
+            };
+            let next = match applied {
+                Ok(next) => next,
+                // Changed in the meantime.
+                Err(sqlx::Error::RowNotFound) => return changed(state, user_id, *todo_id).await,
+                Err(e) => return Err(e.into()),
+            };
+
+            if toggled && !todo.done {
+                metrics::increment_counter!("todos_completed_total");
+            }
+            if let Some(next) = next {
+                state.live.publish(user_id, Change::Created(next)).await;
+            }
+            state.live.publish(user_id, Change::Updated(*todo_id)).await;
+
+            let todo = current(state, user_id, *todo_id).await?;
+            Ok((MutationStatus::Applied, todo))
+        }
+        Op::Delete {
+            todo_id,
+            base_version,
+        } => {
+            // Deleting what's gone already is fine.
+            let Some(todo) = current(state, user_id, *todo_id).await? else {
+                return Ok((MutationStatus::Applied, None));
+            };
+            if base_version.is_some_and(|version| version != todo.version) {
+                return Ok((MutationStatus::Conflict, Some(todo)));
+            }
+
+            state.todos.delete_todo_by_id(user_id, *todo_id).await?;
+            state.live.publish(user_id, Change::Deleted(*todo_id)).await;
+
+            Ok((MutationStatus::Applied, None))
+        }
+    }
+}
+
+/// What became of a mutation to a todo that changed while it was applied.
+async fn changed(
+    state: &AppState,
+    user_id: Uuid,
+    todo_id: Uuid,
+) -> Result<(MutationStatus, Option<Todo>), Error> {
+    let latest = current(state, user_id, todo_id).await?;
+    let status = match latest {
+        Some(_) => MutationStatus::Conflict,
+        None => MutationStatus::NotFound,
+    };
+
+    Ok((status, latest))
+}
+
+/// The todo as it is now, unless it isn't the user's or has been deleted.
+async fn current(state: &AppState, user_id: Uuid, todo_id: Uuid) -> Result<Option<Todo>, Error> {
+    match state.todos.get_todo_by_id(user_id, todo_id).await {
+        Ok(todo) => Ok(Some(todo)),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn check_content(content: &str) -> Result<(), Error> {
+    if content.trim().is_empty() || content.chars().count() > MAX_CONTENT_LENGTH {
+        return Err(Error::UnprocessableEntity(format!(
+            "content must be between 1 and {} characters",
+            MAX_CONTENT_LENGTH
+        )));
+    }
+
+    Ok(())
+}
+
+fn tag_names(names: &[String]) -> Result<Vec<String>, Error> {
+    names
+        .iter()
+        .map(|name| {
+            tags::normalize(name)
+                .ok_or_else(|| Error::UnprocessableEntity(format!("invalid tag name: {}", name)))
+        })
+        .collect()
+}
+
+/// Empty for none.
+fn list_name(name: Option<&str>) -> Result<Option<String>, Error> {
+    name.filter(|name| !name.is_empty())
+        .map(|name| {
+            lists::normalize(name)
+                .ok_or_else(|| Error::UnprocessableEntity(format!("invalid list name: {}", name)))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_tell_clearing_the_due_date_from_leaving_it() {
+        let parse = |json: &str| match serde_json::from_str::<Mutation>(json).unwrap().op {
+            Op::Update { due_at, .. } => due_at,
+            op => panic!("expected an update, got {:?}", op),
+        };
+        let todo_id = Uuid::new_v4();
+        let mutation = |due_at: &str| {
+            format!(
+                r#"{{"mutationId": "{}", "op": "update", "todoId": "{}", "baseVersion": 1{}}}"#,
+                Uuid::new_v4(),
+                todo_id,
+                due_at
+            )
+        };
+
+        assert_eq!(parse(&mutation("")), None);
+        assert_eq!(parse(&mutation(r#", "dueAt": null"#)), Some(None));
+        assert!(matches!(
+            parse(&mutation(r#", "dueAt": "2023-12-24T18:00:00Z""#)),
+            Some(Some(_))
+        ));
+    }
+
+    #[test]
+    fn creates_need_little_more_than_content() {
+        let json = format!(
+            r#"{{"mutationId": "{}", "op": "create", "todoId": "{}", "content": "Call mum"}}"#,
+            Uuid::new_v4(),
+            Uuid::new_v4()
+        );
+
+        match serde_json::from_str::<Mutation>(&json).unwrap().op {
+            Op::Create {
+                priority,
+                tags,
+                due_at,
+                ..
+            } => {
+                assert_eq!(priority, Priority::None);
+                assert!(tags.is_empty());
+                assert!(due_at.is_none());
+            }
+            op => panic!("expected a create, got {:?}", op),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::*, Json};
+use serde::Serialize;
+use tokio::time::{timeout, Duration};
+
+use crate::AppState;
+
+/// How long a single dependency gets to answer before it's reported as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(handle_healthz))
+        .route("/readyz", get(handle_readyz))
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub status: Status,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthResponse {
+    pub status: Status,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+/// Liveness: the process is up and able to answer HTTP.
+#[axum::debug_handler]
+pub async fn handle_healthz() -> impl IntoResponse {
+    Json(HealthResponse {
+        status: Status::Ok,
+        checks: BTreeMap::new(),
+    })
+}
+
+/// Readiness: every dependency registered on [`AppState`] answered in time.
+#[axum::debug_handler]
+pub async fn handle_readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let mut checks = BTreeMap::new();
+
+    for check in &state.health_checks {
+        let started = Instant::now();
+        let outcome = timeout(CHECK_TIMEOUT, check.check()).await;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let error = match outcome {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some("timed out".to_owned()),
+        };
+
+        checks.insert(
+            check.name(),
+            CheckResult {
+                status: if error.is_none() {
+                    Status::Ok
+                } else {
+                    Status::Unavailable
+                },
+                latency_ms,
+                error,
+            },
+        );
+    }
+
+    let (code, status) = if checks.values().all(|c| c.status == Status::Ok) {
+        (StatusCode::OK, Status::Ok)
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, Status::Unavailable)
+    };
+
+    (code, Json(HealthResponse { status, checks }))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+    use axum::{body::Body, http::Request};
+    use axum_login::tower_sessions::MemoryStore;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{app, health::HealthCheck, shutdown::Shutdown};
+
+    struct Failing;
+
+    #[async_trait]
+    impl HealthCheck for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn check(&self) -> anyhow::Result<()> {
+            Err(anyhow!("connection refused"))
+        }
+    }
+
+    async fn get(state: AppState, uri: &str) -> (StatusCode, String) {
+        let response = app(state, MemoryStore::default())
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn readyz_reports_failing_checks() {
+        let state = AppState::memory().with_health_check(Failing);
+
+        let (status, body) = get(state, "/readyz").await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.contains(r#""status":"unavailable""#));
+        assert!(body.contains(r#""error":"connection refused""#));
+    }
+
+    #[tokio::test]
+    async fn healthz_ignores_dependencies() {
+        let state = AppState::memory().with_health_check(Failing);
+
+        let (status, body) = get(state, "/healthz").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"status":"ok"}"#);
+    }
+
+    #[tokio::test]
+    async fn readyz_is_unavailable_while_draining() {
+        let shutdown = Shutdown::new();
+        let state = AppState::memory().with_health_check(shutdown.clone());
+
+        let (status, _) = get(state.clone(), "/readyz").await;
+        assert_eq!(status, StatusCode::OK);
+
+        shutdown.begin();
+
+        let (status, body) = get(state, "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.contains(r#""shutdown":{"status":"unavailable""#));
+    }
+}
@@ -0,0 +1,85 @@
+//! What happened to each todo, and the security audit log of the account; see
+//! [`crate::data::history`] and [`crate::data::audit`].
+
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Html,
+    routing::*,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::data::user::AuthSession;
+use crate::{error::Error, templates::*, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/v1/todos/:todo_id/history",
+            get(handle_get_history_json),
+        )
+        .route("/todos/:todo_id/history", get(handle_get_history_htmx))
+        .route("/api/v1/account/audit-log", get(handle_get_audit_log_json))
+        .route("/account/security", get(handle_get_security))
+}
+
+/// A todo's history, oldest first.
+#[axum::debug_handler]
+pub async fn handle_get_history_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    Ok(Json(
+        state.history.get_history(user.user_id, todo_id).await?,
+    ))
+}
+
+/// A todo's history, shown under it.
+#[axum::debug_handler]
+pub async fn handle_get_history_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let events = state.history.get_history(user.user_id, todo_id).await?;
+
+    let tmpl = TodoHistoryTemplate { events: &events };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+}
+
+/// The latest logins, failed logins and password changes, newest first.
+#[axum::debug_handler]
+pub async fn handle_get_audit_log_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    Ok(Json(state.audit.get_audit_log(user.user_id).await?))
+}
+
+#[axum::debug_handler]
+pub async fn handle_get_security(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let events = state.audit.get_audit_log(user.user_id).await?;
+
+    let tmpl = SecurityTemplate {
+        user: &Some(user),
+        events: &events,
+    };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+}
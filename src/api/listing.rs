@@ -0,0 +1,214 @@
+//! The query string shared by every todo listing, HTML or JSON.
+
+use serde::{Deserialize, Serialize};
+use time::{macros::format_description, Date, OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    data::{
+        listing::{Cursor, Page, TimeRange, TodoQuery, TodoSort, MAX_LIMIT},
+        lists, tags,
+        todo::DATETIME_LOCAL_FORMAT,
+    },
+    error::Error,
+};
+
+/// Filters, sorting and paging as sent by the filter form or an API client.
+///
+/// Everything is a string so that the empty values a form submits for unset
+/// inputs read as "no filter" rather than failing to deserialize.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct ListParams {
+    /// Full-text search; see [`crate::data::search`]. Results are ranked
+    /// rather than sorted, and come as a single page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// `open`, `done` or `all`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// A tag name, with or without the `#`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// A list name, with or without the `@`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list: Option<String>,
+    /// A priority name; only todos at least that urgent are listed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_priority: Option<String>,
+    /// Dates as `YYYY-MM-DD`, in UTC. Both ends are inclusive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_to: Option<String>,
+    /// `created`, `due`, `manual` or `priority`, prefixed with `-` for descending.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<String>,
+}
+
+impl ListParams {
+    /// The search, if there is one.
+    pub fn search(&self) -> Option<&str> {
+        non_empty(&self.q).filter(|q| !q.trim().is_empty())
+    }
+
+    pub fn status(&self) -> &str {
+        non_empty(&self.status).unwrap_or("all")
+    }
+
+    pub fn sort(&self) -> &str {
+        non_empty(&self.sort).unwrap_or("created")
+    }
+
+    /// The tag filtered by, if any, by name.
+    pub fn tag(&self) -> String {
+        non_empty(&self.tag)
+            .and_then(tags::normalize)
+            .unwrap_or_default()
+    }
+
+    /// The list filtered by, if any, by name.
+    pub fn list(&self) -> String {
+        non_empty(&self.list)
+            .and_then(lists::normalize)
+            .unwrap_or_default()
+    }
+
+    /// The name of the priority filtered by, if any.
+    pub fn min_priority(&self) -> &str {
+        non_empty(&self.min_priority).unwrap_or_default()
+    }
+
+    /// For prefilling date inputs.
+    pub fn value<'a>(&self, field: &'a Option<String>) -> &'a str {
+        field.as_deref().unwrap_or_default()
+    }
+
+    pub fn to_query(&self) -> Result<TodoQuery, Error> {
+        let done = match self.status() {
+            "all" => None,
+            "open" => Some(false),
+            "done" => Some(true),
+            other => return Err(invalid("status", other)),
+        };
+
+        let (sort, descending) = match self.sort() {
+            "created" => (TodoSort::Created, false),
+            "-created" => (TodoSort::Created, true),
+            "due" => (TodoSort::Due, false),
+            "-due" => (TodoSort::Due, true),
+            "manual" => (TodoSort::Manual, false),
+            "-manual" => (TodoSort::Manual, true),
+            "priority" => (TodoSort::Priority, false),
+            "-priority" => (TodoSort::Priority, true),
+            other => return Err(invalid("sort", other)),
+        };
+
+        let after = non_empty(&self.cursor)
+            .map(|cursor| Cursor::decode(cursor).ok_or_else(|| invalid("cursor", cursor)))
+            .transpose()?;
+        if after.as_ref().is_some_and(|after| !sort.fits(after.key)) {
+            return Err(Error::UnprocessableEntity(
+                "cursor doesn't match sort".to_owned(),
+            ));
+        }
+
+        let limit = match non_empty(&self.limit) {
+            None => TodoQuery::default().limit,
+            Some(limit) => limit
+                .parse::<i64>()
+                .ok()
+                .filter(|limit| (1..=MAX_LIMIT).contains(limit))
+                .ok_or_else(|| invalid("limit", limit))?,
+        };
+
+        let tag = match non_empty(&self.tag) {
+            None => None,
+            Some(tag) => Some(tags::normalize(tag).ok_or_else(|| invalid("tag", tag))?),
+        };
+
+        let list = match non_empty(&self.list) {
+            None => None,
+            Some(list) => Some(lists::normalize(list).ok_or_else(|| invalid("list", list))?),
+        };
+
+        let min_priority = non_empty(&self.min_priority)
+            .map(|name| name.parse().map_err(|_| invalid("min_priority", name)))
+            .transpose()?;
+
+        Ok(TodoQuery {
+            top_level: false,
+            done,
+            tag,
+            list,
+            min_priority,
+            created: date_range("created", &self.created_from, &self.created_to)?,
+            due: date_range("due", &self.due_from, &self.due_to)?,
+            sort,
+            descending,
+            after,
+            limit,
+        })
+    }
+
+    /// Where to fetch the page after `page` from, if there is one.
+    pub fn next_page(&self, page: &Page) -> Option<String> {
+        let cursor = page.next?;
+        let params = Self {
+            cursor: Some(cursor.encode()),
+            ..self.clone()
+        };
+
+        Some(format!(
+            "/todos/page?{}",
+            serde_urlencoded::to_string(params).unwrap()
+        ))
+    }
+}
+
+/// Parses the value of a `datetime-local` input, taken to be in UTC.
+pub fn parse_datetime_local(
+    name: &str,
+    value: &Option<String>,
+) -> Result<Option<OffsetDateTime>, Error> {
+    non_empty(value)
+        .map(|value| {
+            PrimitiveDateTime::parse(value, DATETIME_LOCAL_FORMAT)
+                .map(PrimitiveDateTime::assume_utc)
+                .map_err(|_| invalid(name, value))
+        })
+        .transpose()
+}
+
+fn date_range(name: &str, from: &Option<String>, to: &Option<String>) -> Result<TimeRange, Error> {
+    let date = |value: &str| {
+        Date::parse(value, format_description!("[year]-[month]-[day]"))
+            .map_err(|_| invalid(name, value))
+    };
+
+    Ok(TimeRange {
+        from: non_empty(from)
+            .map(date)
+            .transpose()?
+            .map(|from| from.midnight().assume_utc()),
+        until: non_empty(to)
+            .map(date)
+            .transpose()?
+            .and_then(Date::next_day)
+            .map(|until| until.midnight().assume_utc()),
+    })
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.is_empty())
+}
+
+fn invalid(name: &str, value: &str) -> Error {
+    Error::UnprocessableEntity(format!("invalid {}: {}", name, value))
+}
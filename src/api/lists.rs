@@ -0,0 +1,23 @@
+//! The lists todos are filed under. They're created by naming them in a todo's
+//! content, so there's nothing to manage yet.
+
+use askama_axum::IntoResponse;
+use axum::{extract::State, routing::*, Json};
+
+use crate::data::user::AuthSession;
+use crate::{error::Error, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/v1/lists", get(handle_list_lists_json))
+}
+
+/// The user's lists with how many todos are on each.
+#[axum::debug_handler]
+pub async fn handle_list_lists_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    Ok(Json(state.lists.get_lists(user.user_id).await?))
+}
@@ -0,0 +1,34 @@
+//! Live updates to the user's todos; see [`crate::live`].
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::*,
+};
+use futures::{Stream, StreamExt};
+
+use crate::data::user::AuthSession;
+use crate::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/todos/events", get(handle_todo_events))
+}
+
+/// Streams changes to the user's todos, from any tab or instance, as
+/// server-sent events named by [`crate::live::TodoUpdate::event_name`] with
+/// the update as JSON.
+#[axum::debug_handler]
+pub async fn handle_todo_events(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    let user = auth_session.user.unwrap();
+
+    let events = state.live.subscribe(user.user_id).map(|update| {
+        Event::default()
+            .event(update.event_name())
+            .json_data(update)
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
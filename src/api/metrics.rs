@@ -0,0 +1,126 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::*,
+};
+
+use crate::{error::Error, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/metrics", get(handle_metrics))
+}
+
+/// Prometheus scrape endpoint.
+///
+/// Only served when an exporter has been configured on the state; when it has a
+/// token, scrapers must present it as a bearer token.
+#[axum::debug_handler]
+pub async fn handle_metrics(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(exporter) = &state.metrics else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if let Some(token) = exporter.token() {
+        let presented = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        if !presented.is_some_and(|presented| constant_time_eq(presented, token)) {
+            return Error::Unauthorized.into_response();
+        }
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        exporter.render(),
+    )
+        .into_response()
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use axum::{body::Body, http::Request};
+    use axum_login::tower_sessions::MemoryStore;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{app, prometheus::Exporter};
+
+    // The recorder is process-global, so every test shares one exporter.
+    fn exporter() -> Exporter {
+        static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+        EXPORTER
+            .get_or_init(|| Exporter::install().unwrap())
+            .clone()
+    }
+
+    async fn scrape(state: AppState, token: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::builder().uri("/metrics");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+
+        let response = app(state, MemoryStore::default())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn metrics_are_disabled_without_an_exporter() {
+        let (status, _) = scrape(AppState::memory(), None).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn metrics_require_the_configured_token() {
+        let state = AppState::memory().with_metrics(exporter().with_token("s3cret".into()));
+
+        let (status, _) = scrape(state.clone(), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = scrape(state.clone(), Some("guess")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = scrape(state, Some("s3cret")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn requests_are_counted_by_matched_route() {
+        let state = AppState::memory().with_metrics(exporter());
+
+        let request = Request::builder()
+            .uri("/login")
+            .body(Body::empty())
+            .unwrap();
+        app(state.clone(), MemoryStore::default())
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        let (status, body) = scrape(state, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#"http_requests_total{method="GET",route="/login",status="200"}"#));
+        assert!(body.contains("http_request_duration_seconds_bucket"));
+    }
+}
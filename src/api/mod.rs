@@ -9,8 +9,20 @@ use axum::{
 
 use crate::templates::*;
 
+pub mod archive;
 pub mod auth;
+pub mod bulk;
+pub mod changes;
+pub mod health;
+pub mod history;
+pub mod listing;
+pub mod lists;
+pub mod live;
+pub mod metrics;
+pub mod sync;
+pub mod tags;
 pub mod todos;
+pub mod trash;
 
 pub async fn handle_index(auth_session: AuthSession) -> impl IntoResponse {
     match auth_session.user {
@@ -0,0 +1,91 @@
+//! Syncing by polling: only what's changed since the client last asked, going
+//! by when each todo was last updated; see [`crate::data::changes`]. Clients
+//! that stay connected can use the change feed in [`super::changes`] instead.
+
+use askama_axum::IntoResponse;
+use axum::{
+    extract::{Query, State},
+    routing::*,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::data::{
+    changes::{self, SyncCursor, SyncedChange, Tombstone},
+    todo::Todo,
+    user::AuthSession,
+};
+use crate::{error::Error, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/v1/sync", get(handle_sync_json))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SyncParams {
+    /// The `cursor` from the last sync, or nothing to start from scratch.
+    pub since: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncResponse {
+    /// Todos created or changed since the cursor, as they are now.
+    pub changed: Vec<Todo>,
+    /// Todos deleted since the cursor, into the trash or for good.
+    pub deleted: Vec<Tombstone>,
+    /// Opaque; pass it back as `since` next time. Empty until there's been
+    /// anything to sync.
+    pub cursor: String,
+    /// Set when there's more to fetch right away from `cursor`.
+    pub has_more: bool,
+}
+
+/// What's changed since `since`, a page at a time.
+#[axum::debug_handler]
+pub async fn handle_sync_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(params): Query<SyncParams>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let since = params
+        .since
+        .as_deref()
+        .filter(|since| !since.is_empty())
+        .map(|since| {
+            SyncCursor::decode(since)
+                .ok_or_else(|| Error::UnprocessableEntity(format!("invalid since: {}", since)))
+        })
+        .transpose()?;
+
+    // One more than fits on a page says whether there's another page.
+    let mut synced = state
+        .changes
+        .changed_since(user.user_id, since, changes::PAGE_SIZE + 1)
+        .await?;
+    let has_more = synced.len() as i64 > changes::PAGE_SIZE;
+    synced.truncate(changes::PAGE_SIZE as usize);
+    let cursor = synced
+        .last()
+        .map(SyncedChange::cursor)
+        .or(since)
+        .map_or_else(String::new, |cursor| cursor.encode());
+
+    let mut changed = vec![];
+    let mut deleted = vec![];
+    for change in synced {
+        match change {
+            SyncedChange::Changed(todo) => changed.push(*todo),
+            SyncedChange::Deleted { tombstone, .. } => deleted.push(tombstone),
+        }
+    }
+
+    Ok(Json(SyncResponse {
+        changed,
+        deleted,
+        cursor,
+        has_more,
+    }))
+}
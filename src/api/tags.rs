@@ -0,0 +1,154 @@
+//! Managing tags: recoloring, renaming, merging and deleting them.
+//!
+//! Every change re-renders the whole list of tags, as merging or renaming one
+//! changes the others' counts and order.
+
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Html,
+    routing::*,
+    Form, Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::data::{tags, user::AuthSession};
+use crate::validators::RE_COLOR;
+use crate::{error::Error, templates::*, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/tags", get(handle_list_tags_json))
+        .route("/tags", get(handle_get_tags))
+        .route(
+            "/tags/:tag_id",
+            put(handle_update_tag_htmx).delete(handle_delete_tag_htmx),
+        )
+        .route("/tags/:tag_id/merge", post(handle_merge_tag_htmx))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct UpdateTagRequest {
+    pub name: String,
+    #[validate(regex = "RE_COLOR")]
+    pub color: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeTagRequest {
+    /// The tag to keep.
+    pub into: Uuid,
+}
+
+#[axum::debug_handler]
+pub async fn handle_get_tags(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let tags = state.tags.list_tags(user.user_id).await?;
+
+    let tmpl = TagsTemplate {
+        user: &Some(user),
+        tags: &tags,
+    };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+}
+
+/// The user's tags with how many todos each is on.
+#[axum::debug_handler]
+pub async fn handle_list_tags_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    Ok(Json(state.tags.list_tags(user.user_id).await?))
+}
+
+/// Renames and recolors a tag. Renaming one to the name of another is refused,
+/// as that's what merging them is for.
+#[axum::debug_handler]
+pub async fn handle_update_tag_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(tag_id): Path<Uuid>,
+    Form(req): Form<UpdateTagRequest>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    req.validate()?;
+    let name = tags::normalize(&req.name)
+        .ok_or_else(|| Error::UnprocessableEntity(format!("invalid tag name: {}", req.name)))?;
+
+    let existing = state.tags.list_tags(user.user_id).await?;
+    if existing
+        .iter()
+        .any(|summary| summary.tag.name == name && summary.tag.tag_id != tag_id)
+    {
+        return Err(Error::Conflict(format!(
+            "there's already a tag called #{}, merge them instead",
+            name
+        )));
+    }
+
+    match state
+        .tags
+        .update_tag(user.user_id, tag_id, &name, &req.color)
+        .await
+    {
+        Ok(_) => render_tags(&state, user.user_id).await,
+        Err(sqlx::Error::RowNotFound) => Err(Error::UnprocessableEntity("unknown tag".to_owned())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Merges a tag into another, which every todo tagged with it gets instead.
+#[axum::debug_handler]
+pub async fn handle_merge_tag_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(tag_id): Path<Uuid>,
+    Form(req): Form<MergeTagRequest>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    if req.into == tag_id {
+        return Err(Error::UnprocessableEntity(
+            "can't merge a tag into itself".to_owned(),
+        ));
+    }
+
+    match state.tags.merge_tags(user.user_id, tag_id, req.into).await {
+        Ok(()) => render_tags(&state, user.user_id).await,
+        Err(sqlx::Error::RowNotFound) => Err(Error::UnprocessableEntity("unknown tag".to_owned())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn handle_delete_tag_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(tag_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    state.tags.delete_tag(user.user_id, tag_id).await?;
+
+    render_tags(&state, user.user_id).await
+}
+
+async fn render_tags(state: &AppState, user_id: Uuid) -> Result<impl IntoResponse, Error> {
+    let tags = state.tags.list_tags(user_id).await?;
+
+    let tmpl = PartialTagsTemplate { tags: &tags };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+}
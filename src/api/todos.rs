@@ -1,130 +1,758 @@
 use askama::Template;
 use askama_axum::IntoResponse;
-use axum::{extract::Path, http::StatusCode, response::Html, routing::*, Extension, Form};
-use serde::Deserialize;
-use sqlx::PgPool;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, Response},
+    routing::*,
+    Form, Json,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::data::user::AuthSession;
-use crate::{data, error::Error, templates::*};
+use crate::api::listing::{parse_datetime_local, ListParams};
+use crate::data::{
+    ordering::Placement,
+    quick_add,
+    recurrence::Recurrence,
+    search::TodoMatch,
+    shorthand::{self, Shorthand},
+    subtasks::Subtasks,
+    todo::{NewTodo, Todo, TodoChanges},
+    user::AuthSession,
+};
+use crate::{error::Error, live::Change, templates::*, AppState};
 
 #[derive(Deserialize, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateTodoRequest {
+    /// Any `#tag`s, `!1`–`!4` and `@list` in it are taken out and put on the
+    /// todo; see [`shorthand::parse`]. New todos' dates and repeats can be
+    /// written in words too; see [`quick_add::parse`].
     #[validate(length(min = 1, max = 1000))]
     pub content: String,
+    /// From a `datetime-local` input; see [`parse_datetime_local`]. Unless
+    /// the content gives one.
+    pub due: Option<String>,
+    /// A priority name, unless the content gives one.
+    pub priority: Option<String>,
+    /// An `RRULE`, making the todo repeat; see [`Recurrence`]. Empty stops
+    /// the series. Unless the content gives one.
+    pub repeat: Option<String>,
+    /// Creates the todo as a subtask of this one.
+    pub parent: Option<Uuid>,
+    /// For edits, the version of the todo the form was rendered from; see
+    /// [`expected_version`].
+    pub version: Option<String>,
+    /// The filters the list is showing, so the re-rendered list matches them.
+    #[serde(flatten)]
+    pub listing: ListParams,
 }
 
-pub fn router() -> Router {
+impl CreateTodoRequest {
+    /// The content with its shorthand split out, plus any date, time and
+    /// repeat written in words if it's a `quick_add`. Whatever the content
+    /// gives wins over the form's other fields.
+    fn parse(&self, quick_add: bool) -> Result<Shorthand, Error> {
+        let mut parsed = if quick_add {
+            quick_add::parse(&self.content, OffsetDateTime::now_utc())
+        } else {
+            shorthand::parse(&self.content)
+        };
+        if parsed.content.trim().is_empty() {
+            return Err(Error::UnprocessableEntity(
+                "a todo needs some content besides its shorthand".to_owned(),
+            ));
+        }
+
+        if parsed.priority.is_none() {
+            parsed.priority = self
+                .priority
+                .as_deref()
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    name.parse().map_err(|_| {
+                        Error::UnprocessableEntity(format!("invalid priority: {}", name))
+                    })
+                })
+                .transpose()?;
+        }
+        if parsed.due_at.is_none() {
+            parsed.due_at = parse_datetime_local("due", &self.due)?;
+        }
+        if parsed.recurrence.is_none() {
+            parsed.recurrence = self.recurrence()?;
+        }
+
+        Ok(parsed)
+    }
+
+    fn recurrence(&self) -> Result<Option<Recurrence>, Error> {
+        self.repeat
+            .as_deref()
+            .filter(|rule| !rule.trim().is_empty())
+            .map(|rule| {
+                rule.parse()
+                    .map_err(|e| Error::UnprocessableEntity(format!("invalid repeat rule: {}", e)))
+            })
+            .transpose()
+    }
+}
+
+/// The version an edit was made against: the `If-Match` header's, or else the
+/// form's `version`. Edits with neither, or `If-Match: *`, aren't checked.
+fn expected_version(headers: &HeaderMap, req: &CreateTodoRequest) -> Result<Option<i64>, Error> {
+    let version = match headers.get(header::IF_MATCH) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .map(|tag| tag.trim().trim_start_matches("W/").trim_matches('"'))
+            .filter(|tag| *tag != "*"),
+        None => req.version.as_deref().filter(|version| !version.is_empty()),
+    };
+
+    version
+        .map(|version| {
+            version
+                .parse()
+                .map_err(|_| Error::UnprocessableEntity(format!("invalid version: {}", version)))
+        })
+        .transpose()
+}
+
+/// Adds the todo's `ETag` to a response.
+fn with_etag(todo: &Todo, response: impl IntoResponse) -> Response {
+    let mut response = response.into_response();
+    if let Ok(etag) = HeaderValue::from_str(&todo.etag()) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+
+    response
+}
+
+#[derive(Deserialize)]
+pub struct PreviewRequest {
+    pub content: Option<String>,
+}
+
+pub fn router() -> Router<AppState> {
     Router::new()
+        .route("/api/v1/todos", get(handle_list_todos_json))
+        .route("/api/v1/todos/:todo_id", get(handle_get_todo_json))
         .route("/todos", get(handle_get_todos))
+        .route("/todos/page", get(handle_get_todos_page_htmx))
+        .route("/todos/preview", get(handle_preview_todo_htmx))
         .route("/todos", post(handle_create_todo_htmx))
         .route(
             "/todos/:todo_id",
-            put(handle_update_todo_htmx).delete(handle_delete_todo_htmx),
+            get(handle_get_todo_htmx)
+                .put(handle_update_todo_htmx)
+                .delete(handle_delete_todo_htmx),
         )
         .route("/todos/:todo_id/edit", get(handle_edit_todo_htmx))
         .route("/todos/:todo_id/toggle", post(handle_toggle_todo_htmx))
+        .route("/todos/:todo_id/complete", post(handle_complete_todo_htmx))
+        .route("/todos/:todo_id/move", post(handle_move_todo_htmx))
+}
+
+/// Exactly one of `before` and `after`.
+#[derive(Deserialize)]
+pub struct MoveTodoRequest {
+    pub before: Option<Uuid>,
+    pub after: Option<Uuid>,
 }
 
 #[axum::debug_handler]
 pub async fn handle_get_todos(
     auth_session: AuthSession,
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
 ) -> Result<impl IntoResponse, Error> {
     let user = auth_session.user.unwrap();
 
-    //Result<Html<&'static str>> {
-    let todos = data::todo::get_todos(&db, user.user_id).await?;
+    let mut query = params.to_query()?;
+    query.top_level = true;
+    let page = state.todos.list_todos(user.user_id, &query).await?;
+    let subtasks = subtasks_of(&state, user.user_id, &page.todos).await?;
+    let tags = state.tags.list_tags(user.user_id).await?;
+    let lists = state.lists.get_lists(user.user_id).await?;
 
     let tmpl = TodosTemplate {
         user: &Some(user),
-        todos: &todos,
+        todos: &page.todos,
+        subtasks: &subtasks,
+        next_page: params.next_page(&page),
+        params: &params,
+        tags: &tags,
+        lists: &lists,
     };
 
     Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
 }
 
+/// The list for the current filters: a page of top-level todos with their
+/// subtasks, ending in a "load more" trigger for the next one. Searches list
+/// the matches instead, subtasks or not.
+#[axum::debug_handler]
+pub async fn handle_get_todos_page_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    render_list(&state, user.user_id, &params).await
+}
+
+/// `#todos` for the current filters; see [`handle_get_todos_page_htmx`].
+pub(crate) async fn render_list(
+    state: &AppState,
+    user_id: Uuid,
+    params: &ListParams,
+) -> Result<impl IntoResponse, Error> {
+    let mut query = params.to_query()?;
+
+    let html = match params.search() {
+        Some(text) => {
+            let results = state.todos.search_todos(user_id, text, &query).await?;
+            SearchResultsTemplate { results: &results }.render()
+        }
+        None => {
+            query.top_level = true;
+            let page = state.todos.list_todos(user_id, &query).await?;
+            let subtasks = subtasks_of(state, user_id, &page.todos).await?;
+            PartialTodosTemplate {
+                todos: &page.todos,
+                subtasks: &subtasks,
+                next_page: params.next_page(&page),
+            }
+            .render()
+        }
+    };
+
+    Ok((StatusCode::OK, Html(html.unwrap()).into_response()))
+}
+
+/// Everything nested under `todos`, to render along with them.
+async fn subtasks_of(state: &AppState, user_id: Uuid, todos: &[Todo]) -> Result<Subtasks, Error> {
+    let todo_ids: Vec<_> = todos.iter().map(|todo| todo.todo_id).collect();
+
+    Ok(Subtasks::new(
+        state.todos.get_subtasks(user_id, &todo_ids).await?,
+    ))
+}
+
+/// Re-renders the top-level todo that `todo` is nested under, or `todo` itself
+/// if it isn't a subtask: changing a subtask changes the progress shown on
+/// every todo above it.
+async fn render_tree(state: &AppState, user_id: Uuid, todo: Todo) -> Result<Response, Error> {
+    render_tree_with(state, user_id, todo, "").await
+}
+
+/// [`render_tree`], followed by `extra` HTML such as out-of-band swaps.
+async fn render_tree_with(
+    state: &AppState,
+    user_id: Uuid,
+    todo: Todo,
+    extra: &str,
+) -> Result<Response, Error> {
+    let mut root = todo;
+    while let Some(parent_id) = root.parent_id {
+        root = state.todos.get_todo_by_id(user_id, parent_id).await?;
+    }
+
+    let subtasks = subtasks_of(state, user_id, std::slice::from_ref(&root)).await?;
+    let tmpl = SingleTodoTemplate {
+        todo: &root,
+        subtasks: &subtasks,
+    };
+
+    let html = tmpl.render().unwrap() + extra;
+    let mut response = (StatusCode::OK, Html(html)).into_response();
+    // The request came from somewhere inside the tree.
+    let target = format!("#todo-{}", root.todo_id);
+    response
+        .headers_mut()
+        .insert("HX-Retarget", HeaderValue::from_str(&target).unwrap());
+
+    Ok(response)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodosResponse<T> {
+    pub todos: Vec<T>,
+    /// Pass as `cursor` to fetch the next page.
+    pub next_cursor: Option<String>,
+}
+
+/// Lists todos as JSON, taking the same parameters as the HTML list. With `q`,
+/// only matches are returned, best first, each with its `rank` and highlighted
+/// `snippet`.
+#[axum::debug_handler]
+pub async fn handle_list_todos_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let query = params.to_query()?;
+
+    Ok(match params.search() {
+        Some(text) => Json(TodosResponse::<TodoMatch> {
+            todos: state.todos.search_todos(user.user_id, text, &query).await?,
+            next_cursor: None,
+        })
+        .into_response(),
+        None => {
+            let page = state.todos.list_todos(user.user_id, &query).await?;
+            Json(TodosResponse::<Todo> {
+                todos: page.todos,
+                next_cursor: page.next.map(|cursor| cursor.encode()),
+            })
+            .into_response()
+        }
+    })
+}
+
+#[axum::debug_handler]
+pub async fn handle_get_todo_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<Response, Error> {
+    let user = auth_session.user.unwrap();
+
+    let todo = state.todos.get_todo_by_id(user.user_id, todo_id).await?;
+
+    Ok(with_etag(&todo, Json(&todo)))
+}
+
 #[axum::debug_handler]
 pub async fn handle_create_todo_htmx(
     auth_session: AuthSession,
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
     Form(req): Form<CreateTodoRequest>,
 ) -> Result<impl IntoResponse, Error> {
     let user = auth_session.user.unwrap();
 
     req.validate()?;
+    let parsed = req.parse(true)?;
 
-    data::todo::create_todo(&db, user.user_id, req.content).await?;
+    let todo = NewTodo {
+        todo_id: None,
+        content: parsed.content,
+        due_at: parsed.due_at,
+        priority: parsed.priority.unwrap_or_default(),
+        recurrence: parsed.recurrence,
+        parent_id: req.parent,
+        tags: parsed.tags,
+        list: parsed.list,
+    };
+    let todo = match state.todos.create_todo(user.user_id, todo).await {
+        Ok(todo) => todo,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(Error::UnprocessableEntity("unknown parent".to_owned()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    metrics::increment_counter!("todos_created_total");
+    state
+        .live
+        .publish(user.user_id, Change::Created(todo.todo_id))
+        .await;
 
-    let todos = data::todo::get_todos(&db, user.user_id).await?;
-    let tmpl = PartialTodosTemplate { todos: &todos };
+    let response = render_list(&state, user.user_id, &req.listing).await?;
 
-    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+    Ok(with_etag(&todo, response))
+}
+
+/// What a new todo's content would be split into, shown under the input while
+/// it's typed. Empty until something is recognized.
+#[axum::debug_handler]
+pub async fn handle_preview_todo_htmx(
+    Query(req): Query<PreviewRequest>,
+) -> Result<impl IntoResponse, Error> {
+    let content = req.content.unwrap_or_default();
+    let parsed = quick_add::parse(&content, OffsetDateTime::now_utc());
+
+    if !parsed.is_recognized() {
+        return Ok((StatusCode::OK, Html(String::new())).into_response());
+    }
+
+    let tmpl = QuickAddPreviewTemplate { parsed: &parsed };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap())).into_response())
 }
 
 #[axum::debug_handler]
 pub async fn handle_delete_todo_htmx(
     auth_session: AuthSession,
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
     Path(todo_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, Error> {
     let user = auth_session.user.unwrap();
 
-    data::todo::delete_todo_by_id(&db, user.user_id, todo_id).await?;
+    let todo = match state.todos.get_todo_by_id(user.user_id, todo_id).await {
+        Ok(todo) => todo,
+        // Already gone, say deleted from another tab.
+        Err(sqlx::Error::RowNotFound) => return Ok((StatusCode::OK, Html("")).into_response()),
+        Err(e) => return Err(e.into()),
+    };
+
+    state.todos.delete_todo_by_id(user.user_id, todo_id).await?;
+    state
+        .live
+        .publish(user.user_id, Change::Deleted(todo_id))
+        .await;
+
+    // Swapped in out of band, with the button to undo this.
+    let toast = TrashToastTemplate { todo: &todo }.render().unwrap();
 
-    Ok((StatusCode::OK, Html("").into_response()))
+    match todo.parent_id {
+        Some(parent_id) => {
+            let parent = state.todos.get_todo_by_id(user.user_id, parent_id).await?;
+            render_tree_with(&state, user.user_id, parent, &toast).await
+        }
+        None => Ok((StatusCode::OK, Html(toast)).into_response()),
+    }
 }
 
 #[axum::debug_handler]
 pub async fn handle_toggle_todo_htmx(
     auth_session: AuthSession,
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
     Path(todo_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, Error> {
     let user = auth_session.user.unwrap();
 
-    data::todo::toggle_todo_by_id(&db, user.user_id, todo_id).await?;
+    let next = state
+        .todos
+        .toggle_todo_by_id(user.user_id, todo_id, None)
+        .await?;
+    state
+        .live
+        .publish(user.user_id, Change::Updated(todo_id))
+        .await;
+    if let Some(next) = next {
+        state
+            .live
+            .publish(user.user_id, Change::Created(next))
+            .await;
+    }
 
-    let todo = data::todo::get_todo_by_id(&db, user.user_id, todo_id).await?;
+    let todo = state.todos.get_todo_by_id(user.user_id, todo_id).await?;
 
-    let tmpl = SingleTodoTemplate { todo: &todo };
+    if todo.done {
+        metrics::increment_counter!("todos_completed_total");
+    }
 
-    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+    let mut response = with_etag(
+        &todo,
+        render_tree(&state, user.user_id, todo.clone()).await?,
+    );
+    // The next occurrence of a recurring todo goes elsewhere in the list.
+    if next.is_some() {
+        response
+            .headers_mut()
+            .insert("HX-Trigger", HeaderValue::from_static("todos-changed"));
+    }
+
+    Ok(response)
+}
+
+/// Marks a todo done along with all of its subtasks.
+#[axum::debug_handler]
+pub async fn handle_complete_todo_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    state
+        .todos
+        .complete_todo_with_subtasks(user.user_id, todo_id)
+        .await?;
+    metrics::increment_counter!("todos_completed_total");
+    state
+        .live
+        .publish(user.user_id, Change::Updated(todo_id))
+        .await;
+
+    let todo = state.todos.get_todo_by_id(user.user_id, todo_id).await?;
+
+    Ok(with_etag(
+        &todo,
+        render_tree(&state, user.user_id, todo.clone()).await?,
+    ))
+}
+
+/// Drops a todo before or after another one, as dragged in the list.
+///
+/// The client has already moved the row, so there's nothing to swap in. If
+/// either todo has gone (say, deleted from another tab) the move is refused with
+/// a conflict, and the client reloads the list.
+#[axum::debug_handler]
+pub async fn handle_move_todo_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+    Form(req): Form<MoveTodoRequest>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let placement = match (req.before, req.after) {
+        (Some(before), None) => Placement::Before(before),
+        (None, Some(after)) => Placement::After(after),
+        _ => {
+            return Err(Error::UnprocessableEntity(
+                "expected exactly one of before and after".to_owned(),
+            ))
+        }
+    };
+
+    match state
+        .todos
+        .move_todo(user.user_id, todo_id, placement)
+        .await
+    {
+        Ok(()) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(Error::Conflict(
+                "the list has changed, reload it and try again".to_owned(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+    // Other tabs have to put it in its new place.
+    state.live.publish(user.user_id, Change::Many).await;
+
+    let todo = state.todos.get_todo_by_id(user.user_id, todo_id).await?;
+
+    Ok(with_etag(&todo, StatusCode::NO_CONTENT))
+}
+
+/// The todo's tree, re-rendered when it's changed in another tab.
+#[axum::debug_handler]
+pub async fn handle_get_todo_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let todo = state.todos.get_todo_by_id(user.user_id, todo_id).await?;
+
+    render_tree(&state, user.user_id, todo).await
 }
 
 #[axum::debug_handler]
 pub async fn handle_edit_todo_htmx(
     auth_session: AuthSession,
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
     Path(todo_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, Error> {
     let user = auth_session.user.unwrap();
 
-    let todo = data::todo::get_todo_by_id(&db, user.user_id, todo_id).await?;
+    let todo = state.todos.get_todo_by_id(user.user_id, todo_id).await?;
 
-    let tmpl = EditTodoTemplate { todo: &todo };
+    let tmpl = EditTodoTemplate {
+        todo: &todo,
+        conflict: None,
+    };
 
-    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+    Ok(with_etag(&todo, Html(tmpl.render().unwrap())))
 }
 
 #[axum::debug_handler]
 pub async fn handle_update_todo_htmx(
     auth_session: AuthSession,
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
     Path(todo_id): Path<Uuid>,
+    headers: HeaderMap,
     Form(req): Form<CreateTodoRequest>,
 ) -> Result<impl IntoResponse, Error> {
     let user = auth_session.user.unwrap();
 
     req.validate()?;
+    let parsed = req.parse(false)?;
+    let version = expected_version(&headers, &req)?;
 
-    data::todo::update_todo_by_id(&db, user.user_id, todo_id, req.content).await?;
+    let changes = TodoChanges {
+        content: parsed.content,
+        due_at: parsed.due_at,
+        priority: parsed.priority.unwrap_or_default(),
+        recurrence: parsed.recurrence,
+        tags: parsed.tags,
+        list: parsed.list,
+        done: None,
+        version,
+    };
+    match state
+        .todos
+        .update_todo_by_id(user.user_id, todo_id, changes)
+        .await
+    {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            let latest = match state.todos.get_todo_by_id(user.user_id, todo_id).await {
+                Ok(latest) => latest,
+                Err(sqlx::Error::RowNotFound) => {
+                    return Err(Error::Conflict(
+                        "this todo was deleted somewhere else".to_owned(),
+                    ))
+                }
+                Err(e) => return Err(e.into()),
+            };
+            // The form again, as the todo is now, to make the edit over.
+            let tmpl = EditTodoTemplate {
+                todo: &latest,
+                conflict: Some("This todo was changed somewhere else. Here's how it reads now."),
+            };
 
-    let todo = data::todo::get_todo_by_id(&db, user.user_id, todo_id).await?;
+            return Ok(with_etag(
+                &latest,
+                (StatusCode::CONFLICT, Html(tmpl.render().unwrap())),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    state
+        .live
+        .publish(user.user_id, Change::Updated(todo_id))
+        .await;
 
-    let tmpl = SingleTodoTemplate { todo: &todo };
+    let todo = state.todos.get_todo_by_id(user.user_id, todo_id).await?;
+    let subtasks = subtasks_of(&state, user.user_id, std::slice::from_ref(&todo)).await?;
 
-    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+    let tmpl = SingleTodoTemplate {
+        todo: &todo,
+        subtasks: &subtasks,
+    };
+
+    Ok(with_etag(&todo, Html(tmpl.render().unwrap())))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        Router,
+    };
+    use axum_login::tower_sessions::MemoryStore;
+    use tower::ServiceExt;
+
+    use crate::{app, AppState};
+
+    const EMAIL: &str = "ferris@example.com";
+    const PASSWORD: &str = "Sup3rSecret!";
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, String, Option<String>) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let cookie = response.headers().get(header::SET_COOKIE).map(|value| {
+            value
+                .to_str()
+                .unwrap()
+                .split(';')
+                .next()
+                .unwrap()
+                .to_owned()
+        });
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap(), cookie)
+    }
+
+    fn form(method: &str, uri: &str, cookie: Option<&str>, body: String) -> Request<Body> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+
+        request.body(Body::from(body)).unwrap()
+    }
+
+    fn get(uri: &str, cookie: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn logged_in_app() -> (Router, String) {
+        let app = app(AppState::memory(), MemoryStore::default());
+        let credentials = format!("email={}&password={}", EMAIL, PASSWORD);
+
+        let (status, _, _) = send(&app, form("POST", "/signup", None, credentials.clone())).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let (status, _, cookie) = send(&app, form("POST", "/login", None, credentials)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        (app, cookie.expect("login should set a session cookie"))
+    }
+
+    #[tokio::test]
+    async fn todos_require_login() {
+        let app = app(AppState::memory(), MemoryStore::default());
+
+        let request = Request::builder()
+            .uri("/todos")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _, _) = send(&app, request).await;
+
+        assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+    }
+
+    #[tokio::test]
+    async fn create_toggle_and_delete_todo() {
+        let (app, cookie) = logged_in_app().await;
+
+        let request = form("POST", "/todos", Some(&cookie), "content=Buy+milk".into());
+        let (status, body, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Buy milk"));
+
+        let todo_id = body
+            .split("hx-post=\"/todos/")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap()
+            .to_owned();
+
+        let toggle = format!("/todos/{}/toggle", todo_id);
+        let (status, body, _) = send(&app, form("POST", &toggle, Some(&cookie), "".into())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("checked"));
+
+        let delete = format!("/todos/{}", todo_id);
+        let (status, _, _) = send(&app, form("DELETE", &delete, Some(&cookie), "".into())).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body, _) = send(&app, get("/todos", &cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("Buy milk"));
+    }
+
+    #[tokio::test]
+    async fn create_todo_rejects_empty_content() {
+        let (app, cookie) = logged_in_app().await;
+
+        let request = form("POST", "/todos", Some(&cookie), "content=".into());
+        let (status, _, _) = send(&app, request).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
 }
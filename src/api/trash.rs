@@ -0,0 +1,120 @@
+//! The trash: deleted todos, which can be restored or deleted for good until
+//! they're purged; see [`crate::data::trash`].
+
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderValue, StatusCode},
+    response::Html,
+    routing::*,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::data::user::AuthSession;
+use crate::{error::Error, live::Change, templates::*, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/trash", get(handle_list_trash_json))
+        .route(
+            "/trash",
+            get(handle_get_trash).delete(handle_empty_trash_htmx),
+        )
+        .route("/trash/:todo_id", delete(handle_delete_forever_htmx))
+        .route("/trash/:todo_id/restore", post(handle_restore_todo_htmx))
+}
+
+#[axum::debug_handler]
+pub async fn handle_get_trash(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    let todos = state.trash.get_trash(user.user_id).await?;
+
+    let tmpl = TrashTemplate {
+        user: &Some(user),
+        todos: &todos,
+        retention_days: state.trash_retention_days,
+    };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+}
+
+/// What's in the trash, most recently deleted first.
+#[axum::debug_handler]
+pub async fn handle_list_trash_json(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    Ok(Json(state.trash.get_trash(user.user_id).await?))
+}
+
+/// Takes a todo back out of the trash, from the trash or the undo toast shown
+/// after deleting it. Either way the caller is left empty, and the list of todos
+/// is told to reload.
+#[axum::debug_handler]
+pub async fn handle_restore_todo_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    match state.trash.restore_todo(user.user_id, todo_id).await {
+        Ok(()) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(Error::Conflict("that todo isn't in the trash".to_owned()))
+        }
+        Err(e) => return Err(e.into()),
+    }
+    state
+        .live
+        .publish(user.user_id, Change::Created(todo_id))
+        .await;
+
+    let mut response = (StatusCode::OK, Html("")).into_response();
+    response
+        .headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("todos-changed"));
+
+    Ok(response)
+}
+
+#[axum::debug_handler]
+pub async fn handle_delete_forever_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(todo_id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    match state.trash.delete_todo_forever(user.user_id, todo_id).await {
+        Ok(()) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(Error::Conflict("that todo isn't in the trash".to_owned()))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok((StatusCode::OK, Html("")))
+}
+
+#[axum::debug_handler]
+pub async fn handle_empty_trash_htmx(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = auth_session.user.unwrap();
+
+    state.trash.empty_trash(user.user_id).await?;
+
+    let tmpl = PartialTrashTemplate { todos: &vec![] };
+
+    Ok((StatusCode::OK, Html(tmpl.render().unwrap()).into_response()))
+}
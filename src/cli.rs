@@ -0,0 +1,278 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::{anyhow, bail, Context};
+use clap::{Parser, Subcommand};
+use flyio_rust::{
+    api::auth::SignupForm,
+    data::{self, todo, todo::Todo, user, MIGRATOR},
+    sessions,
+};
+use fred::{
+    prelude::*,
+    types::{RedisKey, Scanner},
+};
+use futures::TryStreamExt;
+use serde::Serialize;
+use sqlx::{migrate::Migrate, PgPool};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{connect_db, connect_redis};
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Default)]
+pub enum Command {
+    /// Run the web server (the default).
+    #[default]
+    Serve,
+    /// Manage database migrations.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Manage user accounts.
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Print a user and their todos as JSON.
+    ExportUser { email: String },
+    /// Manage login sessions.
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+}
+
+#[derive(Subcommand)]
+pub enum MigrateCommand {
+    /// Apply all pending migrations.
+    Run,
+    /// Revert the most recently applied migration.
+    Revert,
+    /// List migrations and whether they have been applied.
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Create a user. The password is read from stdin unless given.
+    Create {
+        email: String,
+        #[arg(long, env = "USER_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Stop a user from logging in and end their sessions.
+    Disable { email: String },
+    /// Set a new password, ending the user's existing sessions. The password is
+    /// read from stdin unless given.
+    ResetPassword {
+        email: String,
+        #[arg(long, env = "USER_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// List all users.
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum SessionsCommand {
+    /// Delete every session from Redis, logging everyone out.
+    Purge,
+}
+
+pub async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Serve => bail!("the server isn't run from here"),
+        Command::Migrate(command) => {
+            let db = connect_db().await?;
+            migrate(&db, command).await
+        }
+        Command::User(command) => {
+            let db = connect_db().await?;
+            manage_user(&db, command).await
+        }
+        Command::ExportUser { email } => {
+            let db = connect_db().await?;
+            export_user(&db, &email).await
+        }
+        Command::Sessions(SessionsCommand::Purge) => {
+            let redis = connect_redis().await?;
+            let purged = purge_sessions(&redis).await?;
+            info!("purged {} session(s)", purged);
+            Ok(())
+        }
+    }
+}
+
+async fn migrate(db: &PgPool, command: MigrateCommand) -> anyhow::Result<()> {
+    match command {
+        MigrateCommand::Run => {
+            data::migrate(db).await?;
+        }
+        MigrateCommand::Revert => {
+            let mut conn = db.acquire().await?;
+            conn.ensure_migrations_table().await?;
+            let mut applied = conn.list_applied_migrations().await?;
+            applied.sort_by_key(|migration| migration.version);
+
+            let Some(last) = applied.pop() else {
+                bail!("no migrations have been applied");
+            };
+
+            let reversible = MIGRATOR.iter().any(|migration| {
+                migration.version == last.version && migration.migration_type.is_down_migration()
+            });
+            if !reversible {
+                bail!("migration {} has no down migration", last.version);
+            }
+
+            let target = applied.last().map_or(0, |migration| migration.version);
+            MIGRATOR.undo(&mut *conn, target).await?;
+            info!("reverted migration {}", last.version);
+        }
+        MigrateCommand::Status => {
+            let pending = data::pending_migrations(db).await?;
+
+            let mut stdout = io::stdout().lock();
+            for migration in MIGRATOR
+                .iter()
+                .filter(|migration| migration.migration_type.is_up_migration())
+            {
+                let status = if pending.iter().any(|p| p.version == migration.version) {
+                    "pending"
+                } else {
+                    "applied"
+                };
+
+                writeln!(
+                    stdout,
+                    "{} {:<8} {}",
+                    migration.version, status, migration.description
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn manage_user(db: &PgPool, command: UserCommand) -> anyhow::Result<()> {
+    match command {
+        UserCommand::Create { email, password } => {
+            let password = validated_password(&email, password)?;
+            let user = user::create_user(db, &email, &password).await?;
+            info!("created user {}", user.user_id);
+        }
+        UserCommand::Disable { email } => {
+            if !user::disable_user(db, &email).await? {
+                bail!("no user with email {}", email);
+            }
+            info!("disabled {}", email);
+        }
+        UserCommand::ResetPassword { email, password } => {
+            let password = validated_password(&email, password)?;
+            if !user::set_password(db, &email, &password).await? {
+                bail!("no user with email {}", email);
+            }
+            info!("reset password for {}", email);
+        }
+        UserCommand::List => {
+            let mut stdout = io::stdout().lock();
+            for user in user::list_users(db).await? {
+                let created_at = user.created_at.format(&Rfc3339)?;
+                let status = if user.is_disabled() {
+                    "disabled"
+                } else {
+                    "active"
+                };
+
+                writeln!(
+                    stdout,
+                    "{} {} {:<8} {}",
+                    user.user_id, created_at, status, user.email
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Falls back to reading the password from stdin, then applies the same
+/// validation as the signup form.
+fn validated_password(email: &str, password: Option<String>) -> anyhow::Result<String> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_owned()
+        }
+    };
+
+    let form = SignupForm {
+        email: email.to_owned(),
+        password,
+    };
+    form.validate()
+        .map_err(|e| anyhow!("invalid credentials: {}", e))?;
+
+    Ok(form.password)
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserExport {
+    user_id: Uuid,
+    email: String,
+    #[serde_as(as = "Rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde_as(as = "Option<Rfc3339>")]
+    disabled_at: Option<OffsetDateTime>,
+    todos: Vec<Todo>,
+}
+
+async fn export_user(db: &PgPool, email: &str) -> anyhow::Result<()> {
+    let user = user::get_user_by_email(db, email)
+        .await?
+        .with_context(|| format!("no user with email {}", email))?;
+    let todos = todo::get_todos(db, user.user_id).await?;
+
+    // Built field by field so the password hash never ends up in the export.
+    let export = UserExport {
+        user_id: user.user_id,
+        email: user.email,
+        created_at: user.created_at,
+        disabled_at: user.disabled_at,
+        todos,
+    };
+
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &export)?;
+    writeln!(stdout)?;
+
+    Ok(())
+}
+
+/// Returns how many sessions were deleted.
+async fn purge_sessions(redis: &RedisClient) -> anyhow::Result<usize> {
+    let mut purged = 0;
+    let pattern = format!("{}*", sessions::KEY_PREFIX);
+    let mut pages = redis.scan(pattern, Some(100), None);
+
+    while let Some(mut page) = pages.try_next().await? {
+        let keys: Vec<RedisKey> = page.take_results().unwrap_or_default();
+
+        if !keys.is_empty() {
+            purged += redis.del::<usize, _>(keys).await?;
+        }
+
+        page.next()?;
+    }
+
+    Ok(purged)
+}
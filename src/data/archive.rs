@@ -0,0 +1,264 @@
+//! Completed todos put away.
+//!
+//! Archiving a done todo takes it out of the list without deleting it: it's
+//! listed in the archive instead, by the day it was done. Only top-level todos
+//! are archived, taking their subtasks along with the same `archived_at`, which
+//! is how bringing the todo back knows to bring them too. Besides archiving
+//! everything done at once, the server archives whatever has been done for long
+//! enough in the background.
+
+use async_trait::async_trait;
+use sqlx::{types::Json, Error, PgConnection, PgPool};
+use time::{macros::format_description, Date, OffsetDateTime};
+use uuid::Uuid;
+
+use super::{
+    changes, priority::Priority, recurrence::Recurrence, tags::Tag, todo::Todo, ArchiveRepository,
+};
+
+/// After how many days done todos are archived unless `AUTO_ARCHIVE_DAYS`
+/// says otherwise.
+pub const DEFAULT_AUTO_ARCHIVE_DAYS: u32 = 14;
+
+/// The todos done on one day.
+#[derive(Debug)]
+pub struct ArchivedDay {
+    pub date: Date,
+    pub todos: Vec<Todo>,
+}
+
+impl ArchivedDay {
+    /// The date as a heading.
+    pub fn label(&self) -> String {
+        self.date
+            .format(format_description!(
+                "[weekday], [month repr:long] [day padding:none], [year]"
+            ))
+            .unwrap_or_default()
+    }
+}
+
+/// Groups archived todos, newest first, by the day they were done.
+pub fn by_day(todos: Vec<Todo>) -> Vec<ArchivedDay> {
+    let mut days: Vec<ArchivedDay> = vec![];
+
+    for todo in todos {
+        let date = todo.completed_at.unwrap_or(todo.created_at).date();
+
+        match days.last_mut() {
+            Some(day) if day.date == date => day.todos.push(todo),
+            _ => days.push(ArchivedDay {
+                date,
+                todos: vec![todo],
+            }),
+        }
+    }
+
+    days
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn get_archive(db: &PgPool, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+    sqlx::query_as!(
+        Todo,
+        r#"
+            select todo_id, content, done, user_id, created_at, due_at,
+                priority as "priority: Priority", recurrence as "recurrence: Recurrence",
+                position, parent_id,
+                tags_of_todo(todo_id) as "tags!: Json<Vec<Tag>>", list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
+            from todos
+            where user_id = $1 and parent_id is null
+                and archived_at is not null and deleted_at is null
+            order by completed_at desc, todo_id
+        "#,
+        user_id,
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Archives all of the user's done todos, returning how many were archived,
+/// subtasks included.
+#[tracing::instrument(skip(db))]
+pub async fn archive_completed(db: &PgPool, user_id: Uuid) -> Result<u64, Error> {
+    archive(db, user_id, None).await
+}
+
+/// Archives every user's todos that were done before `before`, returning how
+/// many were archived, subtasks included. Each user's are archived in a
+/// transaction of their own.
+#[tracing::instrument(skip(db))]
+pub async fn auto_archive(db: &PgPool, before: OffsetDateTime) -> Result<u64, Error> {
+    let user_ids = sqlx::query_scalar!(
+        "
+            select distinct user_id
+            from todos
+            where completed_at < $1 and parent_id is null and done
+                and archived_at is null and deleted_at is null
+        ",
+        before,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut archived = 0;
+    for user_id in user_ids {
+        archived += archive(db, user_id, Some(before)).await?;
+    }
+
+    Ok(archived)
+}
+
+async fn archive(
+    db: &PgPool,
+    user_id: Uuid,
+    completed_before: Option<OffsetDateTime>,
+) -> Result<u64, Error> {
+    let mut tx = db.begin().await?;
+
+    match archive_users_todos(&mut tx, user_id, completed_before).await {
+        Ok(archived) => {
+            tx.commit().await?;
+            Ok(archived)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+/// Takes the lock on the user's todos first; see [`changes::lock_todos`].
+async fn archive_users_todos(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    completed_before: Option<OffsetDateTime>,
+) -> Result<u64, Error> {
+    changes::lock_todos(conn, user_id).await?;
+
+    Ok(sqlx::query!(
+        "
+            with recursive subtree as (
+                select todo_id
+                from todos
+                where user_id = $1
+                    and ($2::timestamptz is null or completed_at < $2)
+                    and parent_id is null and done
+                    and archived_at is null and deleted_at is null
+                union all
+                select todos.todo_id
+                from todos
+                join subtree on todos.parent_id = subtree.todo_id
+                where todos.archived_at is null and todos.deleted_at is null
+            )
+            update todos
+            set archived_at = now()
+            where todo_id in (select todo_id from subtree)
+        ",
+        user_id,
+        completed_before,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected())
+}
+
+/// Errors with `RowNotFound` if the todo isn't in the user's archive.
+#[tracing::instrument(skip(db))]
+pub async fn unarchive_todo(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+    let unarchived = sqlx::query!(
+        "
+            with recursive subtree as (
+                select todo_id, archived_at
+                from todos
+                where user_id = $1 and todo_id = $2 and parent_id is null
+                    and archived_at is not null and deleted_at is null
+                union all
+                select todos.todo_id, todos.archived_at
+                from todos
+                join subtree on todos.parent_id = subtree.todo_id
+                    and todos.archived_at = subtree.archived_at
+            )
+            update todos
+            set archived_at = null
+            where todo_id in (select todo_id from subtree)
+        ",
+        user_id,
+        todo_id,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    if unarchived == 0 {
+        return Err(Error::RowNotFound);
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl ArchiveRepository for PgPool {
+    async fn get_archive(&self, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+        get_archive(self, user_id).await
+    }
+
+    async fn archive_completed(&self, user_id: Uuid) -> Result<u64, Error> {
+        archive_completed(self, user_id).await
+    }
+
+    async fn unarchive_todo(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        unarchive_todo(self, user_id, todo_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+
+    use super::*;
+
+    fn done_at(completed_at: OffsetDateTime) -> Todo {
+        Todo {
+            todo_id: Uuid::new_v4(),
+            content: completed_at.to_string(),
+            done: true,
+            user_id: Uuid::nil(),
+            created_at: completed_at - Duration::days(3),
+            due_at: None,
+            priority: Priority::None,
+            recurrence: None,
+            position: 0,
+            parent_id: None,
+            tags: Json(vec![]),
+            list_id: None,
+            list: None,
+            deleted_at: None,
+            completed_at: Some(completed_at),
+            archived_at: Some(completed_at),
+            version: 1,
+            updated_at: completed_at,
+        }
+    }
+
+    #[test]
+    fn todos_are_grouped_by_the_day_they_were_done() {
+        let days = by_day(vec![
+            done_at(datetime!(2023-11-27 18:00 UTC)),
+            done_at(datetime!(2023-11-27 09:30 UTC)),
+            done_at(datetime!(2023-11-25 23:59 UTC)),
+        ]);
+
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].label(), "Monday, November 27, 2023");
+        assert_eq!(days[0].todos.len(), 2);
+        assert_eq!(days[1].label(), "Saturday, November 25, 2023");
+        assert_eq!(days[1].todos.len(), 1);
+    }
+
+    #[test]
+    fn nothing_archived_means_no_days() {
+        assert!(by_day(vec![]).is_empty());
+    }
+}
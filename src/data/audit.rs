@@ -0,0 +1,112 @@
+//! The security audit log: logins, failed logins and password changes, shown
+//! to the user whose account they concern.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::{Error, FromRow, PgPool};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use super::{todo::due_label, AuditRepository};
+
+/// How many of a user's latest events are shown.
+pub const MAX_EVENTS: i64 = 100;
+
+#[derive(Serialize, sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    PasswordChanged,
+}
+
+impl AuditAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            AuditAction::Login => "Logged in",
+            AuditAction::LoginFailed => "Failed login",
+            AuditAction::PasswordChanged => "Password changed",
+        }
+    }
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, Clone, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub event_id: Uuid,
+    /// `None` for failed logins with an email nobody has.
+    pub user_id: Option<Uuid>,
+    pub action: AuditAction,
+    /// As given, for logins.
+    pub email: String,
+    /// Where the request came from, if it was made over HTTP.
+    pub ip: Option<String>,
+    #[serde_as(as = "Rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl AuditEvent {
+    /// `created_at` as shown in the log.
+    pub fn created_label(&self) -> Option<String> {
+        due_label(self.created_at)
+    }
+}
+
+/// What an event is recorded with.
+#[derive(Clone, Debug)]
+pub struct NewAuditEvent {
+    pub user_id: Option<Uuid>,
+    pub action: AuditAction,
+    pub email: String,
+    pub ip: Option<String>,
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn record_audit_event(db: &PgPool, event: NewAuditEvent) -> Result<(), Error> {
+    sqlx::query!(
+        "
+            insert into audit_log(user_id, action, email, ip)
+            values ($1, $2, $3, $4)
+        ",
+        event.user_id,
+        event.action as AuditAction,
+        event.email,
+        event.ip,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// The user's latest events, newest first.
+#[tracing::instrument(skip(db))]
+pub async fn get_audit_log(db: &PgPool, user_id: Uuid) -> Result<Vec<AuditEvent>, Error> {
+    sqlx::query_as!(
+        AuditEvent,
+        r#"
+            select event_id, user_id, action as "action: AuditAction", email, ip, created_at
+            from audit_log
+            where user_id = $1
+            order by created_at desc, event_id
+            limit $2
+        "#,
+        user_id,
+        MAX_EVENTS,
+    )
+    .fetch_all(db)
+    .await
+}
+
+#[async_trait]
+impl AuditRepository for PgPool {
+    async fn record_audit_event(&self, event: NewAuditEvent) -> Result<(), Error> {
+        record_audit_event(self, event).await
+    }
+
+    async fn get_audit_log(&self, user_id: Uuid) -> Result<Vec<AuditEvent>, Error> {
+        get_audit_log(self, user_id).await
+    }
+}
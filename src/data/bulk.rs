@@ -0,0 +1,313 @@
+//! Changing many todos at once.
+//!
+//! A bulk change applies one [`BulkAction`] to every todo picked, in a single
+//! transaction, so either all of them change or, if anything goes wrong, none
+//! do. Todos that aren't the user's, or are in the trash, are skipped and
+//! reported as such rather than failing the rest. Each todo is changed the
+//! same way as one at a time: marking a recurring todo done schedules its next
+//! occurrence, and deleting a todo takes its subtasks along.
+
+use serde::Serialize;
+use sqlx::{types::Json, Error, PgConnection, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{
+    history::{self, TodoChange},
+    lists,
+    priority::Priority,
+    recurrence::Recurrence,
+    tags::{self, Tag},
+    todo::{self, Todo},
+};
+
+/// The most todos changed by one request.
+pub const MAX_TODOS: usize = 500;
+
+/// What to do to every todo picked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BulkAction {
+    Done,
+    Undone,
+    /// Moves the todos to the trash; see [`super::trash`].
+    Delete,
+    /// Puts the todos on the list named, or takes them off their lists.
+    MoveToList(Option<String>),
+    AddTag(String),
+    RemoveTag(String),
+    /// Sets the due date, or clears it.
+    SetDue(Option<OffsetDateTime>),
+}
+
+impl BulkAction {
+    /// Whether doing this to `todo` changes anything.
+    pub fn changes(&self, todo: &Todo) -> bool {
+        match self {
+            BulkAction::Done => !todo.done,
+            BulkAction::Undone => todo.done,
+            BulkAction::Delete => true,
+            BulkAction::MoveToList(list) => todo.list != *list,
+            BulkAction::AddTag(name) => !has_tag(todo, name),
+            BulkAction::RemoveTag(name) => has_tag(todo, name),
+            BulkAction::SetDue(due_at) => todo.due_at != *due_at,
+        }
+    }
+
+    /// What's recorded in the history of each todo changed, for the actions
+    /// that don't record their own as they would one at a time.
+    pub fn change(&self) -> Option<TodoChange> {
+        match self {
+            BulkAction::Done | BulkAction::Undone | BulkAction::Delete => None,
+            BulkAction::MoveToList(_) => Some(TodoChange::Moved),
+            BulkAction::AddTag(_) | BulkAction::RemoveTag(_) => Some(TodoChange::Retagged),
+            BulkAction::SetDue(_) => Some(TodoChange::Rescheduled),
+        }
+    }
+
+    /// The names of the tags `todo` has afterwards.
+    pub fn tags(&self, todo: &Todo) -> Vec<String> {
+        let mut names: Vec<_> = todo.tags.iter().map(|tag| tag.name.clone()).collect();
+
+        match self {
+            BulkAction::AddTag(name) if !names.contains(name) => names.push(name.clone()),
+            BulkAction::RemoveTag(name) => names.retain(|tag| tag != name),
+            _ => {}
+        }
+
+        names
+    }
+}
+
+fn has_tag(todo: &Todo, name: &str) -> bool {
+    todo.tags.iter().any(|tag| tag.name == name)
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkStatus {
+    Updated,
+    /// The todo was already that way.
+    Unchanged,
+    /// The todo isn't the user's, or is in the trash.
+    NotFound,
+}
+
+/// What happened to one of the todos picked.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkResult {
+    pub todo_id: Uuid,
+    pub status: BulkStatus,
+}
+
+/// What `action` does to each of `todo_ids`, given the user's `todos` among
+/// them, in the order asked for. A todo picked twice is only changed once.
+pub fn plan(todo_ids: &[Uuid], todos: &[Todo], action: &BulkAction) -> Vec<BulkResult> {
+    let mut results: Vec<BulkResult> = vec![];
+
+    for &todo_id in todo_ids {
+        if results.iter().any(|result| result.todo_id == todo_id) {
+            continue;
+        }
+
+        let status = match todos.iter().find(|todo| todo.todo_id == todo_id) {
+            Some(todo) if action.changes(todo) => BulkStatus::Updated,
+            Some(_) => BulkStatus::Unchanged,
+            None => BulkStatus::NotFound,
+        };
+        results.push(BulkResult { todo_id, status });
+    }
+
+    results
+}
+
+/// Applies `action` to each of the user's todos among `todo_ids`, returning
+/// what happened to each of them.
+#[tracing::instrument(skip(db))]
+pub async fn update_todos(
+    db: &PgPool,
+    user_id: Uuid,
+    todo_ids: &[Uuid],
+    action: &BulkAction,
+) -> Result<Vec<BulkResult>, Error> {
+    let mut tx = db.begin().await?;
+
+    match apply(&mut tx, user_id, todo_ids, action).await {
+        Ok(results) => {
+            tx.commit().await?;
+            Ok(results)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn apply(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    todo_ids: &[Uuid],
+    action: &BulkAction,
+) -> Result<Vec<BulkResult>, Error> {
+    // Locked, so that what's planned is still true when it's done.
+    let todos = sqlx::query_as!(
+        Todo,
+        r#"
+            select todo_id, content, done, user_id, created_at, due_at,
+                priority as "priority: Priority", recurrence as "recurrence: Recurrence",
+                position, parent_id,
+                tags_of_todo(todo_id) as "tags!: Json<Vec<Tag>>", list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
+            from todos
+            where user_id = $1 and todo_id = any($2) and deleted_at is null
+            for update
+        "#,
+        user_id,
+        todo_ids,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let results = plan(todo_ids, &todos, action);
+    let updated = results
+        .iter()
+        .filter(|result| result.status == BulkStatus::Updated)
+        .filter_map(|result| todos.iter().find(|todo| todo.todo_id == result.todo_id));
+
+    for todo in updated {
+        let todo_id = todo.todo_id;
+
+        match action {
+            BulkAction::Done | BulkAction::Undone => {
+                todo::toggle(conn, user_id, todo_id, None).await?;
+                continue;
+            }
+            // Subtasks picked along with their parent are already in the trash
+            // by the time they come up, which leaves them be.
+            BulkAction::Delete => {
+                todo::delete_todo_by_id(&mut *conn, user_id, todo_id).await?;
+                continue;
+            }
+            BulkAction::MoveToList(list) => {
+                lists::set_todo_list(conn, user_id, todo_id, list.as_deref()).await?;
+            }
+            BulkAction::AddTag(_) | BulkAction::RemoveTag(_) => {
+                tags::set_todo_tags(conn, user_id, todo_id, &action.tags(todo)).await?;
+            }
+            BulkAction::SetDue(due_at) => {
+                sqlx::query!(
+                    "update todos set due_at = $2 where todo_id = $1",
+                    todo_id,
+                    *due_at,
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        // Like an edit; see `todo::update_todo_by_id`.
+        sqlx::query!(
+            "update todos set version = version + 1 where todo_id = $1",
+            todo_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if let Some(change) = action.change() {
+            history::record(&mut *conn, user_id, todo_id, &change).await?;
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(content: &str, done: bool, tags: &[&str]) -> Todo {
+        Todo {
+            todo_id: Uuid::new_v4(),
+            content: content.to_owned(),
+            done,
+            user_id: Uuid::nil(),
+            created_at: OffsetDateTime::now_utc(),
+            due_at: None,
+            priority: Priority::None,
+            recurrence: None,
+            position: 0,
+            parent_id: None,
+            tags: Json(
+                tags.iter()
+                    .map(|name| Tag {
+                        tag_id: Uuid::new_v4(),
+                        name: name.to_string(),
+                        color: tags::default_color(name).to_owned(),
+                    })
+                    .collect(),
+            ),
+            list_id: None,
+            list: None,
+            deleted_at: None,
+            completed_at: None,
+            archived_at: None,
+            version: 1,
+            updated_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn todos_already_that_way_are_unchanged() {
+        let open = todo("Call mum", false, &[]);
+        let done = todo("Call the bank", true, &[]);
+        let missing = Uuid::new_v4();
+
+        let results = plan(
+            &[open.todo_id, done.todo_id, missing],
+            &[open.clone(), done.clone()],
+            &BulkAction::Done,
+        );
+
+        let statuses: Vec<_> = results.iter().map(|result| result.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                BulkStatus::Updated,
+                BulkStatus::Unchanged,
+                BulkStatus::NotFound
+            ]
+        );
+        assert_eq!(results[2].todo_id, missing);
+    }
+
+    #[test]
+    fn todos_picked_twice_are_changed_once() {
+        let open = todo("Call mum", false, &[]);
+
+        let results = plan(
+            &[open.todo_id, open.todo_id],
+            std::slice::from_ref(&open),
+            &BulkAction::Done,
+        );
+
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn tags_are_added_and_removed_by_name() {
+        let tagged = todo("Call mum", false, &["family", "phone"]);
+
+        let add = BulkAction::AddTag("errands".to_owned());
+        assert!(add.changes(&tagged));
+        assert_eq!(add.tags(&tagged), ["family", "phone", "errands"]);
+
+        let add = BulkAction::AddTag("phone".to_owned());
+        assert!(!add.changes(&tagged));
+        assert_eq!(add.tags(&tagged), ["family", "phone"]);
+
+        let remove = BulkAction::RemoveTag("phone".to_owned());
+        assert!(remove.changes(&tagged));
+        assert_eq!(remove.tags(&tagged), ["family"]);
+    }
+}
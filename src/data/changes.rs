@@ -0,0 +1,298 @@
+//! The change log clients sync from.
+//!
+//! Every change to a user's todos is logged with the next number in the
+//! user's sequence, by triggers on the tables themselves so that no way of
+//! changing a todo can miss it. Numbers are handed out as changes are
+//! committed, so a client that has seen everything up to some `seq` never
+//! misses a change by reading the log again later. Todos deleted for good
+//! stay in the log, so clients hear about those too.
+//!
+//! Clients that poll instead go by when each todo last changed, its
+//! `updated_at`, and when todos deleted for good were, kept as a [`Tombstone`].
+//! Both are stamped in the order they're committed too, so a [`SyncCursor`]
+//! never skips a change.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::{types::Json, Error, PgConnection, PgPool};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use super::{priority::Priority, recurrence::Recurrence, tags::Tag, todo::Todo, ChangeRepository};
+
+/// The most changes read from the log at once.
+pub const PAGE_SIZE: i64 = 200;
+
+/// The latest change to a todo.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedChange {
+    pub seq: i64,
+    pub todo_id: Uuid,
+    /// The todo as it is now, or `None` if it's been deleted, whether into the
+    /// trash or for good.
+    pub todo: Option<Todo>,
+}
+
+/// When a todo was deleted, into the trash or for good.
+#[serde_as]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub todo_id: Uuid,
+    #[serde_as(as = "Rfc3339")]
+    pub deleted_at: OffsetDateTime,
+}
+
+/// Where a poll left off: just past the change to `todo_id` at `changed_at`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncCursor {
+    pub changed_at: OffsetDateTime,
+    pub todo_id: Uuid,
+}
+
+impl SyncCursor {
+    /// An opaque, URL-safe form of the cursor.
+    pub fn encode(&self) -> String {
+        format!(
+            "{}.{}",
+            self.changed_at.unix_timestamp_nanos(),
+            self.todo_id.simple()
+        )
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let (nanos, todo_id) = cursor.split_once('.')?;
+
+        Some(Self {
+            changed_at: OffsetDateTime::from_unix_timestamp_nanos(nanos.parse().ok()?).ok()?,
+            todo_id: Uuid::parse_str(todo_id).ok()?,
+        })
+    }
+}
+
+/// The latest change to a todo, as polled for.
+#[derive(Debug, Clone)]
+pub enum SyncedChange {
+    /// The todo as it is now.
+    Changed(Box<Todo>),
+    /// Deleted into the trash, or for good at `changed_at`.
+    Deleted {
+        tombstone: Tombstone,
+        changed_at: OffsetDateTime,
+    },
+}
+
+impl SyncedChange {
+    /// A cursor just past this change.
+    pub fn cursor(&self) -> SyncCursor {
+        match self {
+            Self::Changed(todo) => SyncCursor {
+                changed_at: todo.updated_at,
+                todo_id: todo.todo_id,
+            },
+            Self::Deleted {
+                tombstone,
+                changed_at,
+            } => SyncCursor {
+                changed_at: *changed_at,
+                todo_id: tombstone.todo_id,
+            },
+        }
+    }
+}
+
+/// Takes the lock on the user's todos that stamping them takes, until the
+/// transaction ends. Changes to many of the user's todos at once take it before
+/// locking any of them, so that they never wait for it while holding rows that
+/// whoever has it is waiting for.
+#[tracing::instrument(skip(conn))]
+pub async fn lock_todos(conn: &mut PgConnection, user_id: Uuid) -> Result<(), Error> {
+    sqlx::query!("select from lock_todos_for_sync($1)", user_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// What's changed in the user's todos since `since`, oldest first, with only
+/// the latest change to each todo. At most `limit` are returned; the rest
+/// follow on from the last one's `seq`.
+#[tracing::instrument(skip(db))]
+pub async fn changes_since(
+    db: &PgPool,
+    user_id: Uuid,
+    since: i64,
+    limit: i64,
+) -> Result<Vec<LoggedChange>, Error> {
+    let latest = sqlx::query!(
+        "
+            select seq, todo_id
+            from (
+                select distinct on (todo_id) seq, todo_id
+                from todo_changes
+                where user_id = $1 and seq > $2
+                order by todo_id, seq desc
+            ) latest
+            order by seq
+            limit $3
+        ",
+        user_id,
+        since,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let todo_ids: Vec<_> = latest.iter().map(|change| change.todo_id).collect();
+    let mut todos = sqlx::query_as!(
+        Todo,
+        r#"
+            select todo_id, content, done, user_id, created_at, due_at,
+                priority as "priority: Priority", recurrence as "recurrence: Recurrence",
+                position, parent_id,
+                tags_of_todo(todo_id) as "tags!: Json<Vec<Tag>>", list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
+            from todos
+            where user_id = $1 and todo_id = any($2) and deleted_at is null
+        "#,
+        user_id,
+        &todo_ids,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(latest
+        .into_iter()
+        .map(|change| LoggedChange {
+            seq: change.seq,
+            todo_id: change.todo_id,
+            todo: todos
+                .iter()
+                .position(|todo| todo.todo_id == change.todo_id)
+                .map(|i| todos.swap_remove(i)),
+        })
+        .collect())
+}
+
+/// The user's todos changed or deleted after `since`, or all of them, in the
+/// order of [`SyncCursor`]. At most `limit` are returned; the rest follow on
+/// from the last one's cursor.
+#[tracing::instrument(skip(db))]
+pub async fn changed_since(
+    db: &PgPool,
+    user_id: Uuid,
+    since: Option<SyncCursor>,
+    limit: i64,
+) -> Result<Vec<SyncedChange>, Error> {
+    let mut tx = db.begin().await?;
+
+    match read_changed_since(&mut tx, user_id, since, limit).await {
+        Ok(changes) => {
+            tx.commit().await?;
+            Ok(changes)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn read_changed_since(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    since: Option<SyncCursor>,
+    limit: i64,
+) -> Result<Vec<SyncedChange>, Error> {
+    // The todos are read as of the same moment as what changed.
+    sqlx::query!("set transaction isolation level repeatable read")
+        .execute(&mut *conn)
+        .await?;
+
+    let changed = sqlx::query!(
+        r#"
+            select todo_id as "todo_id!", changed_at as "changed_at!", deleted_at
+            from (
+                select todo_id, updated_at as changed_at, deleted_at
+                from todos
+                where user_id = $1
+                    and ($2::timestamptz is null or (updated_at, todo_id) > ($2, $3))
+                union all
+                select todo_id, deleted_at, deleted_at
+                from todo_tombstones
+                where user_id = $1
+                    and ($2::timestamptz is null or (deleted_at, todo_id) > ($2, $3))
+            ) changed
+            order by changed_at, todo_id
+            limit $4
+        "#,
+        user_id,
+        since.map(|since| since.changed_at),
+        since.map(|since| since.todo_id),
+        limit,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let todo_ids: Vec<_> = changed
+        .iter()
+        .filter(|change| change.deleted_at.is_none())
+        .map(|change| change.todo_id)
+        .collect();
+    let mut todos = sqlx::query_as!(
+        Todo,
+        r#"
+            select todo_id, content, done, user_id, created_at, due_at,
+                priority as "priority: Priority", recurrence as "recurrence: Recurrence",
+                position, parent_id,
+                tags_of_todo(todo_id) as "tags!: Json<Vec<Tag>>", list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
+            from todos
+            where user_id = $1 and todo_id = any($2)
+        "#,
+        user_id,
+        &todo_ids,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(changed
+        .into_iter()
+        .filter_map(|change| match change.deleted_at {
+            Some(deleted_at) => Some(SyncedChange::Deleted {
+                tombstone: Tombstone {
+                    todo_id: change.todo_id,
+                    deleted_at,
+                },
+                changed_at: change.changed_at,
+            }),
+            None => todos
+                .iter()
+                .position(|todo| todo.todo_id == change.todo_id)
+                .map(|i| SyncedChange::Changed(Box::new(todos.swap_remove(i)))),
+        })
+        .collect())
+}
+
+#[async_trait]
+impl ChangeRepository for PgPool {
+    async fn changes_since(
+        &self,
+        user_id: Uuid,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<LoggedChange>, Error> {
+        changes_since(self, user_id, since, limit).await
+    }
+
+    async fn changed_since(
+        &self,
+        user_id: Uuid,
+        since: Option<SyncCursor>,
+        limit: i64,
+    ) -> Result<Vec<SyncedChange>, Error> {
+        changed_since(self, user_id, since, limit).await
+    }
+}
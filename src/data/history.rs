@@ -0,0 +1,131 @@
+//! What happened to each todo.
+//!
+//! Every change to a todo is recorded as a [`TodoEvent`] in the same statement
+//! or transaction as the change itself, so that the history can't miss one.
+//! Events are never changed afterwards, and only go away with their todo when
+//! it's deleted for good.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, Error, FromRow, PgExecutor, PgPool};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use super::{todo::due_label, HistoryRepository};
+
+/// What happened to a todo.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TodoChange {
+    Created {
+        content: String,
+    },
+    ContentChanged {
+        before: String,
+        after: String,
+    },
+    Toggled {
+        done: bool,
+    },
+    /// Reordered, or put on another list.
+    Moved,
+    Retagged,
+    Rescheduled,
+    Deleted,
+    Restored,
+}
+
+impl TodoChange {
+    /// As shown in the history, after who did it.
+    pub fn describe(&self) -> String {
+        match self {
+            TodoChange::Created { content } => format!("created \"{}\"", content),
+            TodoChange::ContentChanged { before, after } => {
+                format!("changed \"{}\" to \"{}\"", before, after)
+            }
+            TodoChange::Toggled { done: true } => "marked it done".to_owned(),
+            TodoChange::Toggled { done: false } => "marked it not done".to_owned(),
+            TodoChange::Moved => "moved it".to_owned(),
+            TodoChange::Retagged => "changed its tags".to_owned(),
+            TodoChange::Rescheduled => "changed its due date".to_owned(),
+            TodoChange::Deleted => "moved it to the trash".to_owned(),
+            TodoChange::Restored => "restored it from the trash".to_owned(),
+        }
+    }
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, Clone, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoEvent {
+    pub event_id: Uuid,
+    pub todo_id: Uuid,
+    /// The user who made the change.
+    pub actor_id: Uuid,
+    /// Their email.
+    pub actor: String,
+    #[serde(flatten)]
+    pub change: Json<TodoChange>,
+    #[serde_as(as = "Rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl TodoEvent {
+    /// `created_at` as shown in the history.
+    pub fn created_label(&self) -> Option<String> {
+        due_label(self.created_at)
+    }
+}
+
+/// Records a change `actor_id` made to a todo.
+#[tracing::instrument(skip(conn))]
+pub async fn record(
+    conn: impl PgExecutor<'_>,
+    actor_id: Uuid,
+    todo_id: Uuid,
+    change: &TodoChange,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "insert into todo_events(todo_id, actor_id, change) values ($1, $2, $3)",
+        todo_id,
+        actor_id,
+        Json(change) as _,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Everything that happened to one of the user's todos, oldest first. Todos in
+/// the trash have a history too.
+#[tracing::instrument(skip(db))]
+pub async fn get_history(
+    db: &PgPool,
+    user_id: Uuid,
+    todo_id: Uuid,
+) -> Result<Vec<TodoEvent>, Error> {
+    sqlx::query_as!(
+        TodoEvent,
+        r#"
+            select event_id, todo_events.todo_id, actor_id, users.email as actor,
+                change as "change: Json<TodoChange>", todo_events.created_at
+            from todo_events
+            join todos on todos.todo_id = todo_events.todo_id
+            join users on users.user_id = todo_events.actor_id
+            where todos.user_id = $1 and todo_events.todo_id = $2
+            order by todo_events.created_at, event_id
+        "#,
+        user_id,
+        todo_id,
+    )
+    .fetch_all(db)
+    .await
+}
+
+#[async_trait]
+impl HistoryRepository for PgPool {
+    async fn get_history(&self, user_id: Uuid, todo_id: Uuid) -> Result<Vec<TodoEvent>, Error> {
+        get_history(self, user_id, todo_id).await
+    }
+}
@@ -0,0 +1,323 @@
+//! Filtering, sorting and keyset pagination of a user's todos.
+//!
+//! Pages are addressed by a [`Cursor`] holding the sort key and id of the last
+//! todo on the previous page rather than an offset, so that todos being added
+//! or removed in the meantime don't shift rows between pages.
+
+use std::cmp::Ordering;
+
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{priority::Priority, todo::Todo};
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+
+/// What todos are ordered by. Ties are broken by id, and todos without a value
+/// to sort by always come last.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TodoSort {
+    #[default]
+    Created,
+    Due,
+    /// The order the user dragged todos into; see [`super::ordering`].
+    Manual,
+    Priority,
+}
+
+impl TodoSort {
+    /// Whether a cursor's key could have come from this sort, so that a cursor
+    /// from one sort isn't compared against another's values.
+    pub fn fits(&self, key: Option<SortKey>) -> bool {
+        matches!(
+            (self, key),
+            (TodoSort::Created, Some(SortKey::Time(_)))
+                | (TodoSort::Due, None | Some(SortKey::Time(_)))
+                | (TodoSort::Manual, Some(SortKey::Position(_)))
+                | (TodoSort::Priority, Some(SortKey::Priority(_)))
+        )
+    }
+}
+
+/// The value a todo is sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortKey {
+    Time(OffsetDateTime),
+    Position(i64),
+    Priority(Priority),
+}
+
+/// A half-open range of timestamps, unbounded on either end when unset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeRange {
+    /// Inclusive.
+    pub from: Option<OffsetDateTime>,
+    /// Exclusive.
+    pub until: Option<OffsetDateTime>,
+}
+
+impl TimeRange {
+    pub fn is_unbounded(&self) -> bool {
+        self.from.is_none() && self.until.is_none()
+    }
+
+    /// Whether `time` falls in the range. Missing times only match an
+    /// unbounded range.
+    pub fn contains(&self, time: Option<OffsetDateTime>) -> bool {
+        match time {
+            None => self.is_unbounded(),
+            Some(time) => {
+                self.from.is_none_or(|from| time >= from)
+                    && self.until.is_none_or(|until| time < until)
+            }
+        }
+    }
+}
+
+/// Which page of which todos to list.
+#[derive(Clone, Debug)]
+pub struct TodoQuery {
+    /// Only todos that aren't subtasks.
+    pub top_level: bool,
+    /// Only done (or only open) todos.
+    pub done: Option<bool>,
+    /// Only todos with the tag of this name.
+    pub tag: Option<String>,
+    /// Only todos on the list of this name.
+    pub list: Option<String>,
+    /// Only todos at least this urgent.
+    pub min_priority: Option<Priority>,
+    pub created: TimeRange,
+    pub due: TimeRange,
+    pub sort: TodoSort,
+    pub descending: bool,
+    /// Start after this todo, i.e. fetch the next page.
+    pub after: Option<Cursor>,
+    pub limit: i64,
+}
+
+impl Default for TodoQuery {
+    fn default() -> Self {
+        Self {
+            top_level: false,
+            done: None,
+            tag: None,
+            list: None,
+            min_priority: None,
+            created: TimeRange::default(),
+            due: TimeRange::default(),
+            sort: TodoSort::default(),
+            descending: false,
+            after: None,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+impl TodoQuery {
+    /// The value `todo` is sorted by.
+    pub fn sort_key(&self, todo: &Todo) -> Option<SortKey> {
+        match self.sort {
+            TodoSort::Created => Some(SortKey::Time(todo.created_at)),
+            TodoSort::Due => todo.due_at.map(SortKey::Time),
+            TodoSort::Manual => Some(SortKey::Position(todo.position)),
+            TodoSort::Priority => Some(SortKey::Priority(todo.priority)),
+        }
+    }
+
+    /// Whether `todo` passes the filters.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        !(self.top_level && todo.parent_id.is_some())
+            && self.done.is_none_or(|done| todo.done == done)
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|name| todo.tags.iter().any(|tag| &tag.name == name))
+            && self
+                .list
+                .as_ref()
+                .is_none_or(|name| todo.list.as_ref() == Some(name))
+            && self.min_priority.is_none_or(|min| todo.priority >= min)
+            && self.created.contains(Some(todo.created_at))
+            && self.due.contains(todo.due_at)
+    }
+
+    /// Orders todos the way a page lists them.
+    pub fn compare(&self, a: &Todo, b: &Todo) -> Ordering {
+        self.compare_keys((self.sort_key(a), a.todo_id), (self.sort_key(b), b.todo_id))
+    }
+
+    /// Whether `todo` comes after the cursor, if there is one.
+    pub fn is_after_cursor(&self, todo: &Todo) -> bool {
+        self.after.as_ref().is_none_or(|cursor| {
+            self.compare_keys(
+                (self.sort_key(todo), todo.todo_id),
+                (cursor.key, cursor.todo_id),
+            ) == Ordering::Greater
+        })
+    }
+
+    fn compare_keys(
+        &self,
+        (a_key, a_id): (Option<SortKey>, Uuid),
+        (b_key, b_id): (Option<SortKey>, Uuid),
+    ) -> Ordering {
+        let direction = |ordering: Ordering| {
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+
+        // Nulls last regardless of direction, as in `order by ... nulls last`.
+        let by_key = match (a_key, b_key) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => direction(a.cmp(&b)),
+        };
+
+        by_key.then_with(|| direction(a_id.cmp(&b_id)))
+    }
+}
+
+/// Where a page left off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub key: Option<SortKey>,
+    pub todo_id: Uuid,
+}
+
+impl Cursor {
+    /// A cursor pointing just past `todo`.
+    pub fn after(query: &TodoQuery, todo: &Todo) -> Self {
+        Self {
+            key: query.sort_key(todo),
+            todo_id: todo.todo_id,
+        }
+    }
+
+    /// An opaque, URL-safe form of the cursor.
+    pub fn encode(&self) -> String {
+        let key = match self.key {
+            None => "-".to_owned(),
+            Some(SortKey::Time(time)) => format!("t{}", time.unix_timestamp_nanos()),
+            Some(SortKey::Position(position)) => format!("p{}", position),
+            Some(SortKey::Priority(priority)) => format!("r{}", priority.level()),
+        };
+
+        format!("{}.{}", key, self.todo_id.simple())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let (key, todo_id) = cursor.split_once('.')?;
+
+        let key = match key.split_at_checked(1)? {
+            ("-", "") => None,
+            ("t", nanos) => Some(SortKey::Time(
+                OffsetDateTime::from_unix_timestamp_nanos(nanos.parse().ok()?).ok()?,
+            )),
+            ("p", position) => Some(SortKey::Position(position.parse().ok()?)),
+            ("r", level) => Some(SortKey::Priority(Priority::from_level(
+                level.parse().ok()?,
+            )?)),
+            _ => return None,
+        };
+
+        Some(Self {
+            key,
+            todo_id: Uuid::parse_str(todo_id).ok()?,
+        })
+    }
+}
+
+/// One page of todos.
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub todos: Vec<Todo>,
+    /// Set when there are more todos after this page.
+    pub next: Option<Cursor>,
+}
+
+impl Page {
+    /// Builds a page from up to `query.limit + 1` todos, the extra one only
+    /// serving to tell whether there's another page.
+    pub fn from_overfetched(query: &TodoQuery, mut todos: Vec<Todo>) -> Self {
+        let limit = query.limit.max(0) as usize;
+
+        let next = if todos.len() > limit {
+            todos.truncate(limit);
+            todos.last().map(|todo| Cursor::after(query, todo))
+        } else {
+            None
+        };
+
+        Self { todos, next }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = Cursor {
+            key: Some(SortKey::Time(OffsetDateTime::now_utc())),
+            todo_id: Uuid::new_v4(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+
+        let cursor = Cursor {
+            key: Some(SortKey::Position(-65536)),
+            todo_id: Uuid::new_v4(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+
+        let cursor = Cursor {
+            key: Some(SortKey::Priority(Priority::High)),
+            todo_id: Uuid::new_v4(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+
+        let cursor = Cursor {
+            key: None,
+            todo_id: Uuid::new_v4(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+    }
+
+    #[test]
+    fn garbage_cursors_are_rejected() {
+        assert_eq!(Cursor::decode("nope"), None);
+        assert_eq!(Cursor::decode("t12.not-a-uuid"), None);
+        assert_eq!(
+            Cursor::decode(&format!("x1.{}", Uuid::new_v4().simple())),
+            None
+        );
+    }
+
+    #[test]
+    fn missing_times_only_match_unbounded_ranges() {
+        let now = OffsetDateTime::now_utc();
+
+        assert!(TimeRange::default().contains(None));
+        assert!(!TimeRange {
+            from: Some(now),
+            until: None
+        }
+        .contains(None));
+        assert!(TimeRange {
+            from: Some(now),
+            until: None
+        }
+        .contains(Some(now)));
+        assert!(!TimeRange {
+            from: None,
+            until: Some(now)
+        }
+        .contains(Some(now)));
+    }
+}
@@ -0,0 +1,130 @@
+//! Lists todos are filed under.
+//!
+//! A todo is on at most one list. Lists are written inline as `@name` in a
+//! todo's content (see [`super::shorthand`]) and created the first time they're
+//! used.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::{Error, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::ListRepository;
+
+/// The longest name a list can have.
+pub const MAX_NAME_LENGTH: usize = 50;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct List {
+    pub list_id: Uuid,
+    pub name: String,
+}
+
+/// A list and how many todos are on it.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListSummary {
+    #[serde(flatten)]
+    pub list: List,
+    pub todos: i64,
+}
+
+/// The list name for `name`, which may start with an `@`, if it's a valid one.
+pub fn normalize(name: &str) -> Option<String> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    let valid = !name.is_empty()
+        && name.chars().count() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+
+    valid.then(|| name.to_lowercase())
+}
+
+/// Puts a todo on the list named, creating it if the user doesn't have it yet,
+/// or takes it off its list.
+#[tracing::instrument(skip(conn))]
+pub async fn set_todo_list(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    todo_id: Uuid,
+    name: Option<&str>,
+) -> Result<(), Error> {
+    if let Some(name) = name {
+        sqlx::query!(
+            "
+                insert into lists(user_id, name)
+                values ($1, $2)
+                on conflict (user_id, name) do nothing
+            ",
+            user_id,
+            name,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    sqlx::query!(
+        "
+            update todos
+            set list_id = (select list_id from lists where user_id = $1 and name = $3)
+            where user_id = $1 and todo_id = $2
+        ",
+        user_id,
+        todo_id,
+        name,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn get_lists(db: &PgPool, user_id: Uuid) -> Result<Vec<ListSummary>, Error> {
+    let rows = sqlx::query!(
+        r#"
+            select lists.list_id, lists.name, count(todos.todo_id) as "todos!"
+            from lists
+            left join todos on todos.list_id = lists.list_id and todos.deleted_at is null
+            where lists.user_id = $1
+            group by lists.list_id
+            order by lists.name
+        "#,
+        user_id,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ListSummary {
+            list: List {
+                list_id: row.list_id,
+                name: row.name,
+            },
+            todos: row.todos,
+        })
+        .collect())
+}
+
+#[async_trait]
+impl ListRepository for PgPool {
+    async fn get_lists(&self, user_id: Uuid) -> Result<Vec<ListSummary>, Error> {
+        get_lists(self, user_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_normalized() {
+        assert_eq!(normalize("@Groceries"), Some("groceries".to_owned()));
+        assert_eq!(normalize("@"), None);
+        assert_eq!(normalize("@home.work"), None);
+        assert_eq!(normalize(&"x".repeat(MAX_NAME_LENGTH + 1)), None);
+    }
+}
@@ -0,0 +1,1108 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use sqlx::{types::Json, Error};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{
+    audit::{self, AuditEvent, NewAuditEvent},
+    bulk::{self, BulkAction, BulkResult, BulkStatus},
+    changes::{LoggedChange, SyncCursor, SyncedChange, Tombstone},
+    history::{TodoChange, TodoEvent},
+    listing::{Page, TodoQuery},
+    lists::{List, ListSummary},
+    ordering::{self, Placement},
+    search::{self, TodoMatch},
+    subtasks,
+    tags::{self, Tag, TagSummary},
+    todo::{NewTodo, Todo, TodoChanges},
+    user::User,
+    ArchiveRepository, AuditRepository, ChangeRepository, HistoryRepository, ListRepository,
+    TagRepository, TodoRepository, TrashRepository, UserRepository,
+};
+
+/// An in-memory stand-in for Postgres.
+///
+/// This mirrors the behaviour of the queries in [`super::todo`] and
+/// [`super::user`] closely enough to drive the handlers in tests, including
+/// returning `RowNotFound` for todos that don't exist or belong to someone else.
+/// Todos in the trash are kept with the rest and skipped by everything else, and
+/// archived ones are left out of listings.
+#[derive(Clone, Default)]
+pub struct MemoryRepository {
+    todos: Arc<Mutex<Vec<Todo>>>,
+    /// With the id of the user they belong to. Todos hold copies of their
+    /// tags, which are kept in step with these.
+    tags: Arc<Mutex<Vec<(Uuid, Tag)>>>,
+    /// With the id of the user they belong to.
+    lists: Arc<Mutex<Vec<(Uuid, List)>>>,
+    /// Oldest first, with `actor` filled in when they're read.
+    events: Arc<Mutex<Vec<TodoEvent>>>,
+    audit_log: Arc<Mutex<Vec<AuditEvent>>>,
+    /// `(user_id, seq, todo_id)`, like `todo_changes`.
+    change_log: Arc<Mutex<Vec<(Uuid, i64, Uuid)>>>,
+    /// With the id of the user they belong to.
+    tombstones: Arc<Mutex<Vec<(Uuid, Tombstone)>>>,
+    users: Arc<Mutex<HashMap<Uuid, User>>>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `todo` is one of the user's todos and not in the trash.
+    fn is_live(todo: &Todo, user_id: Uuid) -> bool {
+        todo.user_id == user_id && todo.deleted_at.is_none()
+    }
+
+    /// Like [`super::todo::toggle`], with `todos` already locked.
+    fn toggle(
+        &self,
+        todos: &mut Vec<Todo>,
+        user_id: Uuid,
+        todo_id: Uuid,
+        version: Option<i64>,
+    ) -> Result<Option<Uuid>, Error> {
+        let Some(todo) = todos.iter_mut().find(|todo| {
+            Self::is_live(todo, user_id)
+                && todo.todo_id == todo_id
+                && version.is_none_or(|version| todo.version == version)
+        }) else {
+            return match version {
+                Some(_) => Err(Error::RowNotFound),
+                None => Ok(None),
+            };
+        };
+        todo.done = !todo.done;
+        todo.version += 1;
+        todo.updated_at = OffsetDateTime::now_utc();
+        if todo.done {
+            todo.completed_at = Some(OffsetDateTime::now_utc());
+        } else {
+            todo.completed_at = None;
+            todo.archived_at = None;
+        }
+        self.record(user_id, todo_id, TodoChange::Toggled { done: todo.done });
+        self.log_changes(user_id, &[todo_id]);
+
+        let Some(next) = todo
+            .recurrence
+            .as_ref()
+            .filter(|_| todo.done)
+            .and_then(|recurrence| recurrence.next(todo.due_at, OffsetDateTime::now_utc()))
+        else {
+            return Ok(None);
+        };
+
+        let mut occurrence = Todo {
+            todo_id: Uuid::new_v4(),
+            done: false,
+            created_at: OffsetDateTime::now_utc(),
+            completed_at: None,
+            archived_at: None,
+            version: 1,
+            updated_at: OffsetDateTime::now_utc(),
+            due_at: Some(next.due_at),
+            recurrence: next.recurrence,
+            ..todo.clone()
+        };
+        todo.recurrence = None;
+
+        occurrence.position = todos
+            .iter()
+            .filter(|todo| todo.user_id == user_id)
+            .map(|todo| todo.position)
+            .max()
+            .unwrap_or(0)
+            + ordering::GAP;
+        let next_id = occurrence.todo_id;
+        self.record(
+            user_id,
+            next_id,
+            TodoChange::Created {
+                content: occurrence.content.clone(),
+            },
+        );
+        self.log_changes(user_id, &[next_id]);
+        todos.push(occurrence);
+
+        Ok(Some(next_id))
+    }
+
+    /// Adds to a todo's history, like [`super::history::record`].
+    fn record(&self, actor_id: Uuid, todo_id: Uuid, change: TodoChange) {
+        self.events.lock().unwrap().push(TodoEvent {
+            event_id: Uuid::new_v4(),
+            todo_id,
+            actor_id,
+            actor: String::new(),
+            change: Json(change),
+            created_at: OffsetDateTime::now_utc(),
+        });
+    }
+
+    /// Logs changes to the user's todos, like the triggers behind
+    /// [`super::changes`].
+    fn log_changes(&self, user_id: Uuid, todo_ids: &[Uuid]) {
+        let mut log = self.change_log.lock().unwrap();
+
+        for &todo_id in todo_ids {
+            let seq = log.iter().filter(|(owner, _, _)| *owner == user_id).count() as i64 + 1;
+            log.push((user_id, seq, todo_id));
+        }
+    }
+
+    /// Remembers when todos were deleted for good, like the trigger behind
+    /// `todo_tombstones`.
+    fn bury(&self, user_id: Uuid, todo_ids: &[Uuid]) {
+        let mut tombstones = self.tombstones.lock().unwrap();
+        let deleted_at = OffsetDateTime::now_utc();
+
+        tombstones.retain(|(_, tombstone)| !todo_ids.contains(&tombstone.todo_id));
+        tombstones.extend(todo_ids.iter().map(|&todo_id| {
+            let tombstone = Tombstone {
+                todo_id,
+                deleted_at,
+            };
+            (user_id, tombstone)
+        }));
+    }
+
+    /// Drops the history of todos deleted for good, like `on delete cascade`.
+    fn forget_deleted(&self, todos: &[Todo]) {
+        self.events
+            .lock()
+            .unwrap()
+            .retain(|event| todos.iter().any(|todo| todo.todo_id == event.todo_id));
+    }
+
+    /// The user's tags with the given names, by name, creating any that are
+    /// missing.
+    fn tags_named(&self, user_id: Uuid, names: &[String]) -> Json<Vec<Tag>> {
+        let mut tags = self.tags.lock().unwrap();
+
+        let mut named: Vec<_> = names
+            .iter()
+            .map(|name| {
+                let existing = tags
+                    .iter()
+                    .find(|(owner, tag)| *owner == user_id && tag.name == *name);
+
+                match existing {
+                    Some((_, tag)) => tag.clone(),
+                    None => {
+                        let tag = Tag {
+                            tag_id: Uuid::new_v4(),
+                            name: name.clone(),
+                            color: tags::default_color(name).to_owned(),
+                        };
+                        tags.push((user_id, tag.clone()));
+                        tag
+                    }
+                }
+            })
+            .collect();
+        named.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Json(named)
+    }
+
+    /// The user's list with the given name, if any, creating it if it's
+    /// missing.
+    fn list_named(&self, user_id: Uuid, name: Option<&str>) -> Option<List> {
+        let name = name?;
+        let mut lists = self.lists.lock().unwrap();
+
+        let existing = lists
+            .iter()
+            .find(|(owner, list)| *owner == user_id && list.name == name);
+
+        Some(match existing {
+            Some((_, list)) => list.clone(),
+            None => {
+                let list = List {
+                    list_id: Uuid::new_v4(),
+                    name: name.to_owned(),
+                };
+                lists.push((user_id, list.clone()));
+                list
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl TodoRepository for MemoryRepository {
+    async fn create_todo(&self, user_id: Uuid, new: NewTodo) -> Result<Todo, Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        if let Some(parent_id) = new.parent_id {
+            if !todos
+                .iter()
+                .any(|todo| Self::is_live(todo, user_id) && todo.todo_id == parent_id)
+            {
+                return Err(Error::RowNotFound);
+            }
+        }
+
+        // `on conflict do nothing` in Postgres.
+        let todo_id = new.todo_id.unwrap_or_else(Uuid::new_v4);
+        if todos.iter().any(|todo| todo.todo_id == todo_id) {
+            return Err(Error::RowNotFound);
+        }
+
+        let last = todos
+            .iter()
+            .filter(|todo| todo.user_id == user_id)
+            .map(|todo| todo.position)
+            .max();
+
+        let list = self.list_named(user_id, new.list.as_deref());
+        let now = OffsetDateTime::now_utc();
+        let todo = Todo {
+            todo_id,
+            content: new.content,
+            done: false,
+            user_id,
+            created_at: now,
+            due_at: new.due_at,
+            priority: new.priority,
+            recurrence: new.recurrence,
+            position: last.unwrap_or(0) + ordering::GAP,
+            parent_id: new.parent_id,
+            tags: self.tags_named(user_id, &new.tags),
+            list_id: list.as_ref().map(|list| list.list_id),
+            list: list.map(|list| list.name),
+            deleted_at: None,
+            completed_at: None,
+            archived_at: None,
+            version: 1,
+            updated_at: now,
+        };
+
+        todos.push(todo.clone());
+        self.tombstones
+            .lock()
+            .unwrap()
+            .retain(|(_, tombstone)| tombstone.todo_id != todo_id);
+        self.record(
+            user_id,
+            todo.todo_id,
+            TodoChange::Created {
+                content: todo.content.clone(),
+            },
+        );
+        self.log_changes(user_id, &[todo_id]);
+
+        Ok(todo)
+    }
+
+    async fn get_todos(&self, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+        // Todos are kept in insertion order, which matches `order by created_at`.
+        Ok(self
+            .todos
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|todo| Self::is_live(todo, user_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn list_todos(&self, user_id: Uuid, query: &TodoQuery) -> Result<Page, Error> {
+        let mut todos: Vec<_> = self
+            .get_todos(user_id)
+            .await?
+            .into_iter()
+            .filter(|todo| todo.archived_at.is_none())
+            .filter(|todo| query.matches(todo) && query.is_after_cursor(todo))
+            .collect();
+
+        todos.sort_by(|a, b| query.compare(a, b));
+        todos.truncate(query.limit.max(0) as usize + 1);
+
+        Ok(Page::from_overfetched(query, todos))
+    }
+
+    async fn search_todos(
+        &self,
+        user_id: Uuid,
+        text: &str,
+        filter: &TodoQuery,
+    ) -> Result<Vec<TodoMatch>, Error> {
+        let terms = search::terms(text);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Ranked by how many words matched, in place of `ts_rank`.
+        let mut matches: Vec<_> = self
+            .get_todos(user_id)
+            .await?
+            .into_iter()
+            .filter(|todo| todo.archived_at.is_none() && filter.matches(todo))
+            .filter_map(|todo| {
+                let words = search::terms(&todo.content);
+                let matching = |term: &String| {
+                    words
+                        .iter()
+                        .filter(|w| w.starts_with(term.as_str()))
+                        .count()
+                };
+
+                if !terms.iter().all(|term| matching(term) > 0) {
+                    return None;
+                }
+
+                Some(TodoMatch {
+                    rank: terms.iter().map(matching).sum::<usize>() as f32,
+                    snippet: search::highlight(&todo.content, &terms),
+                    todo,
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+        matches.truncate(search::MAX_MATCHES as usize);
+
+        Ok(matches)
+    }
+
+    async fn get_todo_by_id(&self, user_id: Uuid, todo_id: Uuid) -> Result<Todo, Error> {
+        self.todos
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|todo| Self::is_live(todo, user_id) && todo.todo_id == todo_id)
+            .cloned()
+            .ok_or(Error::RowNotFound)
+    }
+
+    async fn delete_todo_by_id(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        if !todos
+            .iter()
+            .any(|todo| Self::is_live(todo, user_id) && todo.todo_id == todo_id)
+        {
+            return Ok(());
+        }
+
+        let live: Vec<_> = todos
+            .iter()
+            .filter(|todo| todo.deleted_at.is_none())
+            .cloned()
+            .collect();
+        let mut deleted = subtasks::descendants(&live, &[todo_id]);
+        deleted.push(todo_id);
+
+        let now = OffsetDateTime::now_utc();
+        todos
+            .iter_mut()
+            .filter(|todo| deleted.contains(&todo.todo_id))
+            .for_each(|todo| {
+                todo.deleted_at = Some(now);
+                todo.updated_at = now;
+            });
+        self.record(user_id, todo_id, TodoChange::Deleted);
+        self.log_changes(user_id, &deleted);
+
+        Ok(())
+    }
+
+    async fn toggle_todo_by_id(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        version: Option<i64>,
+    ) -> Result<Option<Uuid>, Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        self.toggle(&mut todos, user_id, todo_id, version)
+    }
+
+    async fn get_subtasks(&self, user_id: Uuid, todo_ids: &[Uuid]) -> Result<Vec<Todo>, Error> {
+        let todos = self.get_todos(user_id).await?;
+        let nested = subtasks::descendants(&todos, todo_ids);
+
+        let mut found: Vec<_> = todos
+            .into_iter()
+            .filter(|todo| nested.contains(&todo.todo_id))
+            .collect();
+        found.sort_by_key(|todo| (todo.position, todo.todo_id));
+
+        Ok(found)
+    }
+
+    async fn complete_todo_with_subtasks(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        let mut completed = subtasks::descendants(&todos, &[todo_id]);
+        completed.push(todo_id);
+        todos
+            .iter_mut()
+            .filter(|todo| Self::is_live(todo, user_id) && !todo.done)
+            .filter(|todo| completed.contains(&todo.todo_id))
+            .for_each(|todo| {
+                todo.done = true;
+                todo.completed_at = Some(OffsetDateTime::now_utc());
+                todo.version += 1;
+                todo.updated_at = OffsetDateTime::now_utc();
+                self.record(user_id, todo.todo_id, TodoChange::Toggled { done: true });
+                self.log_changes(user_id, &[todo.todo_id]);
+            });
+
+        Ok(())
+    }
+
+    async fn move_todo(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        placement: Placement,
+    ) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        let (list_id, parent_id) = todos
+            .iter()
+            .find(|todo| Self::is_live(todo, user_id) && todo.todo_id == todo_id)
+            .map(|todo| (todo.list_id, todo.parent_id))
+            .ok_or(Error::RowNotFound)?;
+        let mut list: Vec<_> = todos
+            .iter()
+            .filter(|todo| Self::is_live(todo, user_id))
+            .filter(|todo| todo.list_id == list_id && todo.parent_id == parent_id)
+            .map(|todo| (todo.todo_id, todo.position))
+            .collect();
+        list.sort_by_key(|&(id, position)| (position, id));
+
+        let position = match ordering::new_position(&list, todo_id, placement)? {
+            Some(position) => position,
+            None => {
+                list = ordering::renumber(&list);
+                for todo in todos.iter_mut() {
+                    if let Some((_, position)) = list.iter().find(|(id, _)| *id == todo.todo_id) {
+                        todo.position = *position;
+                        todo.updated_at = OffsetDateTime::now_utc();
+                    }
+                }
+                let renumbered: Vec<_> = list.iter().map(|(id, _)| *id).collect();
+                self.log_changes(user_id, &renumbered);
+
+                ordering::new_position(&list, todo_id, placement)?.ok_or_else(ordering::no_room)?
+            }
+        };
+
+        todos
+            .iter_mut()
+            .filter(|todo| todo.todo_id == todo_id)
+            .for_each(|todo| {
+                todo.position = position;
+                todo.updated_at = OffsetDateTime::now_utc();
+            });
+        self.record(user_id, todo_id, TodoChange::Moved);
+        self.log_changes(user_id, &[todo_id]);
+
+        Ok(())
+    }
+
+    async fn update_todo_by_id(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        changes: TodoChanges,
+    ) -> Result<Option<Uuid>, Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        if let Some(todo) = todos
+            .iter_mut()
+            .find(|todo| Self::is_live(todo, user_id) && todo.todo_id == todo_id)
+        {
+            if changes
+                .version
+                .is_some_and(|version| version != todo.version)
+            {
+                return Err(Error::RowNotFound);
+            }
+            if todo.content != changes.content {
+                let change = TodoChange::ContentChanged {
+                    before: todo.content.clone(),
+                    after: changes.content.clone(),
+                };
+                self.record(user_id, todo_id, change);
+            }
+            todo.content = changes.content;
+            todo.due_at = changes.due_at;
+            todo.priority = changes.priority;
+            todo.recurrence = changes.recurrence;
+            todo.tags = self.tags_named(user_id, &changes.tags);
+            if todo.list != changes.list {
+                let list = self.list_named(user_id, changes.list.as_deref());
+                todo.list_id = list.as_ref().map(|list| list.list_id);
+                todo.list = list.map(|list| list.name);
+                self.record(user_id, todo_id, TodoChange::Moved);
+            }
+            todo.version += 1;
+            todo.updated_at = OffsetDateTime::now_utc();
+            self.log_changes(user_id, &[todo_id]);
+
+            if changes.done.is_some_and(|done| done != todo.done) {
+                return self.toggle(&mut todos, user_id, todo_id, None);
+            }
+        } else if changes.version.is_some() {
+            return Err(Error::RowNotFound);
+        }
+
+        Ok(None)
+    }
+
+    async fn update_todos(
+        &self,
+        user_id: Uuid,
+        todo_ids: &[Uuid],
+        action: &BulkAction,
+    ) -> Result<Vec<BulkResult>, Error> {
+        let picked: Vec<_> = self
+            .todos
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|todo| Self::is_live(todo, user_id) && todo_ids.contains(&todo.todo_id))
+            .cloned()
+            .collect();
+        let results = bulk::plan(todo_ids, &picked, action);
+
+        for result in &results {
+            if result.status != BulkStatus::Updated {
+                continue;
+            }
+            let todo_id = result.todo_id;
+
+            match action {
+                BulkAction::Done | BulkAction::Undone => {
+                    self.toggle_todo_by_id(user_id, todo_id, None).await?;
+                }
+                BulkAction::Delete => self.delete_todo_by_id(user_id, todo_id).await?,
+                _ => {
+                    let mut todos = self.todos.lock().unwrap();
+                    let todo = todos
+                        .iter_mut()
+                        .find(|todo| todo.todo_id == todo_id)
+                        .ok_or(Error::RowNotFound)?;
+
+                    match action {
+                        BulkAction::MoveToList(name) => {
+                            let list = self.list_named(user_id, name.as_deref());
+                            todo.list_id = list.as_ref().map(|list| list.list_id);
+                            todo.list = list.map(|list| list.name);
+                        }
+                        BulkAction::SetDue(due_at) => todo.due_at = *due_at,
+                        _ => todo.tags = self.tags_named(user_id, &action.tags(todo)),
+                    }
+                    todo.version += 1;
+                    todo.updated_at = OffsetDateTime::now_utc();
+                    self.log_changes(user_id, &[todo_id]);
+                    if let Some(change) = action.change() {
+                        self.record(user_id, todo_id, change);
+                    }
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+#[async_trait]
+impl TrashRepository for MemoryRepository {
+    async fn get_trash(&self, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+        let todos = self.todos.lock().unwrap();
+
+        let deleted_with_parent = |todo: &Todo| {
+            todos.iter().any(|parent| {
+                Some(parent.todo_id) == todo.parent_id && parent.deleted_at == todo.deleted_at
+            })
+        };
+        let mut trash: Vec<_> = todos
+            .iter()
+            .filter(|todo| todo.user_id == user_id && todo.deleted_at.is_some())
+            .filter(|todo| !deleted_with_parent(todo))
+            .cloned()
+            .collect();
+        trash.sort_by(|a, b| (b.deleted_at, a.todo_id).cmp(&(a.deleted_at, b.todo_id)));
+
+        Ok(trash)
+    }
+
+    async fn restore_todo(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        let Some(deleted_at) = todos
+            .iter()
+            .find(|todo| todo.user_id == user_id && todo.todo_id == todo_id)
+            .and_then(|todo| todo.deleted_at)
+        else {
+            return Err(Error::RowNotFound);
+        };
+
+        let deleted_together: Vec<_> = todos
+            .iter()
+            .filter(|todo| todo.deleted_at == Some(deleted_at))
+            .cloned()
+            .collect();
+        let mut restored = subtasks::descendants(&deleted_together, &[todo_id]);
+        restored.push(todo_id);
+        todos
+            .iter_mut()
+            .filter(|todo| restored.contains(&todo.todo_id))
+            .for_each(|todo| {
+                todo.deleted_at = None;
+                todo.updated_at = OffsetDateTime::now_utc();
+            });
+        self.record(user_id, todo_id, TodoChange::Restored);
+        self.log_changes(user_id, &restored);
+
+        let trashed: Vec<_> = todos
+            .iter()
+            .filter(|todo| todo.deleted_at.is_some())
+            .map(|todo| todo.todo_id)
+            .collect();
+        todos
+            .iter_mut()
+            .filter(|todo| todo.todo_id == todo_id)
+            .filter(|todo| {
+                todo.parent_id
+                    .is_some_and(|parent| trashed.contains(&parent))
+            })
+            .for_each(|todo| todo.parent_id = None);
+
+        Ok(())
+    }
+
+    async fn delete_todo_forever(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        if !todos.iter().any(|todo| {
+            todo.user_id == user_id && todo.todo_id == todo_id && todo.deleted_at.is_some()
+        }) {
+            return Err(Error::RowNotFound);
+        }
+
+        // `on delete cascade` in Postgres.
+        let mut deleted = subtasks::descendants(&todos, &[todo_id]);
+        deleted.push(todo_id);
+        todos.retain(|todo| !deleted.contains(&todo.todo_id));
+        self.forget_deleted(&todos);
+        self.bury(user_id, &deleted);
+        self.log_changes(user_id, &deleted);
+
+        Ok(())
+    }
+
+    async fn empty_trash(&self, user_id: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        let deleted: Vec<_> = todos
+            .iter()
+            .filter(|todo| todo.user_id == user_id && todo.deleted_at.is_some())
+            .map(|todo| todo.todo_id)
+            .collect();
+        todos.retain(|todo| todo.user_id != user_id || todo.deleted_at.is_none());
+        self.forget_deleted(&todos);
+        self.bury(user_id, &deleted);
+        self.log_changes(user_id, &deleted);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ArchiveRepository for MemoryRepository {
+    async fn get_archive(&self, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+        let mut archive: Vec<_> = self
+            .get_todos(user_id)
+            .await?
+            .into_iter()
+            .filter(|todo| todo.parent_id.is_none() && todo.archived_at.is_some())
+            .collect();
+        archive.sort_by(|a, b| (b.completed_at, a.todo_id).cmp(&(a.completed_at, b.todo_id)));
+
+        Ok(archive)
+    }
+
+    async fn archive_completed(&self, user_id: Uuid) -> Result<u64, Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        let unarchived: Vec<_> = todos
+            .iter()
+            .filter(|todo| Self::is_live(todo, user_id) && todo.archived_at.is_none())
+            .cloned()
+            .collect();
+        let done: Vec<_> = unarchived
+            .iter()
+            .filter(|todo| todo.parent_id.is_none() && todo.done)
+            .map(|todo| todo.todo_id)
+            .collect();
+        let mut archived = subtasks::descendants(&unarchived, &done);
+        archived.extend(done);
+
+        let now = OffsetDateTime::now_utc();
+        todos
+            .iter_mut()
+            .filter(|todo| archived.contains(&todo.todo_id))
+            .for_each(|todo| {
+                todo.archived_at = Some(now);
+                todo.updated_at = now;
+            });
+        self.log_changes(user_id, &archived);
+
+        Ok(archived.len() as u64)
+    }
+
+    async fn unarchive_todo(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+
+        let Some(archived_at) = todos
+            .iter()
+            .find(|todo| {
+                Self::is_live(todo, user_id) && todo.todo_id == todo_id && todo.parent_id.is_none()
+            })
+            .and_then(|todo| todo.archived_at)
+        else {
+            return Err(Error::RowNotFound);
+        };
+
+        let archived_together: Vec<_> = todos
+            .iter()
+            .filter(|todo| todo.archived_at == Some(archived_at))
+            .cloned()
+            .collect();
+        let mut unarchived = subtasks::descendants(&archived_together, &[todo_id]);
+        unarchived.push(todo_id);
+        todos
+            .iter_mut()
+            .filter(|todo| unarchived.contains(&todo.todo_id))
+            .for_each(|todo| {
+                todo.archived_at = None;
+                todo.updated_at = OffsetDateTime::now_utc();
+            });
+        self.log_changes(user_id, &unarchived);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HistoryRepository for MemoryRepository {
+    async fn get_history(&self, user_id: Uuid, todo_id: Uuid) -> Result<Vec<TodoEvent>, Error> {
+        if !self
+            .todos
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|todo| todo.user_id == user_id && todo.todo_id == todo_id)
+        {
+            return Ok(vec![]);
+        }
+
+        let users = self.users.lock().unwrap();
+
+        Ok(self
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.todo_id == todo_id)
+            .map(|event| TodoEvent {
+                actor: users
+                    .get(&event.actor_id)
+                    .map(|user| user.email.clone())
+                    .unwrap_or_default(),
+                ..event.clone()
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl AuditRepository for MemoryRepository {
+    async fn record_audit_event(&self, event: NewAuditEvent) -> Result<(), Error> {
+        self.audit_log.lock().unwrap().push(AuditEvent {
+            event_id: Uuid::new_v4(),
+            user_id: event.user_id,
+            action: event.action,
+            email: event.email,
+            ip: event.ip,
+            created_at: OffsetDateTime::now_utc(),
+        });
+
+        Ok(())
+    }
+
+    async fn get_audit_log(&self, user_id: Uuid) -> Result<Vec<AuditEvent>, Error> {
+        Ok(self
+            .audit_log
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|event| event.user_id == Some(user_id))
+            .take(audit::MAX_EVENTS as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ListRepository for MemoryRepository {
+    async fn get_lists(&self, user_id: Uuid) -> Result<Vec<ListSummary>, Error> {
+        let todos = self.todos.lock().unwrap();
+
+        let mut summaries: Vec<_> = self
+            .lists
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(owner, _)| *owner == user_id)
+            .map(|(_, list)| ListSummary {
+                list: list.clone(),
+                todos: todos
+                    .iter()
+                    .filter(|todo| todo.deleted_at.is_none() && todo.list_id == Some(list.list_id))
+                    .count() as i64,
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.list.name.cmp(&b.list.name));
+
+        Ok(summaries)
+    }
+}
+
+#[async_trait]
+impl TagRepository for MemoryRepository {
+    async fn list_tags(&self, user_id: Uuid) -> Result<Vec<TagSummary>, Error> {
+        let todos = self.todos.lock().unwrap();
+
+        let mut summaries: Vec<_> = self
+            .tags
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(owner, _)| *owner == user_id)
+            .map(|(_, tag)| TagSummary {
+                tag: tag.clone(),
+                todos: todos
+                    .iter()
+                    .filter(|todo| {
+                        todo.deleted_at.is_none()
+                            && todo.tags.iter().any(|t| t.tag_id == tag.tag_id)
+                    })
+                    .count() as i64,
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.tag.name.cmp(&b.tag.name));
+
+        Ok(summaries)
+    }
+
+    async fn update_tag(
+        &self,
+        user_id: Uuid,
+        tag_id: Uuid,
+        name: &str,
+        color: &str,
+    ) -> Result<Tag, Error> {
+        let mut todos = self.todos.lock().unwrap();
+        let mut tags = self.tags.lock().unwrap();
+
+        let (_, tag) = tags
+            .iter_mut()
+            .find(|(owner, tag)| *owner == user_id && tag.tag_id == tag_id)
+            .ok_or(Error::RowNotFound)?;
+        tag.name = name.to_owned();
+        tag.color = color.to_owned();
+
+        for todo in todos.iter_mut() {
+            if let Some(copy) = todo.tags.iter_mut().find(|t| t.tag_id == tag_id) {
+                *copy = tag.clone();
+                todo.tags.sort_by(|a, b| a.name.cmp(&b.name));
+                todo.updated_at = OffsetDateTime::now_utc();
+                self.log_changes(user_id, &[todo.todo_id]);
+            }
+        }
+
+        Ok(tag.clone())
+    }
+
+    async fn merge_tags(&self, user_id: Uuid, from: Uuid, into: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+        let mut tags = self.tags.lock().unwrap();
+
+        let find = |tag_id: Uuid| {
+            tags.iter()
+                .find(|(owner, tag)| *owner == user_id && tag.tag_id == tag_id)
+                .map(|(_, tag)| tag.clone())
+        };
+        let (Some(_), Some(into)) = (find(from), find(into)) else {
+            return Err(Error::RowNotFound);
+        };
+        if from == into.tag_id {
+            return Err(Error::RowNotFound);
+        }
+
+        for todo in todos.iter_mut() {
+            if todo.tags.iter().any(|tag| tag.tag_id == from) {
+                todo.tags
+                    .retain(|tag| tag.tag_id != from && tag.tag_id != into.tag_id);
+                todo.tags.push(into.clone());
+                todo.tags.sort_by(|a, b| a.name.cmp(&b.name));
+                todo.updated_at = OffsetDateTime::now_utc();
+                self.log_changes(user_id, &[todo.todo_id]);
+            }
+        }
+        tags.retain(|(_, tag)| tag.tag_id != from);
+
+        Ok(())
+    }
+
+    async fn delete_tag(&self, user_id: Uuid, tag_id: Uuid) -> Result<(), Error> {
+        let mut todos = self.todos.lock().unwrap();
+        let mut tags = self.tags.lock().unwrap();
+
+        if !tags
+            .iter()
+            .any(|(owner, tag)| *owner == user_id && tag.tag_id == tag_id)
+        {
+            return Ok(());
+        }
+
+        for todo in todos.iter_mut() {
+            if todo.tags.iter().any(|tag| tag.tag_id == tag_id) {
+                todo.tags.retain(|tag| tag.tag_id != tag_id);
+                todo.updated_at = OffsetDateTime::now_utc();
+                self.log_changes(user_id, &[todo.todo_id]);
+            }
+        }
+        tags.retain(|(_, tag)| tag.tag_id != tag_id);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChangeRepository for MemoryRepository {
+    async fn changes_since(
+        &self,
+        user_id: Uuid,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<LoggedChange>, Error> {
+        // In the same order as changes are logged.
+        let todos = self.todos.lock().unwrap();
+        let log = self.change_log.lock().unwrap();
+
+        let mut latest: Vec<(i64, Uuid)> = vec![];
+        for &(owner, seq, todo_id) in log.iter() {
+            if owner == user_id && seq > since {
+                latest.retain(|(_, id)| *id != todo_id);
+                latest.push((seq, todo_id));
+            }
+        }
+        latest.truncate(limit.max(0) as usize);
+
+        Ok(latest
+            .into_iter()
+            .map(|(seq, todo_id)| LoggedChange {
+                seq,
+                todo_id,
+                todo: todos
+                    .iter()
+                    .find(|todo| Self::is_live(todo, user_id) && todo.todo_id == todo_id)
+                    .cloned(),
+            })
+            .collect())
+    }
+
+    async fn changed_since(
+        &self,
+        user_id: Uuid,
+        since: Option<SyncCursor>,
+        limit: i64,
+    ) -> Result<Vec<SyncedChange>, Error> {
+        let todos = self.todos.lock().unwrap();
+        let tombstones = self.tombstones.lock().unwrap();
+
+        let mut changes: Vec<_> = todos
+            .iter()
+            .filter(|todo| todo.user_id == user_id)
+            .map(|todo| match todo.deleted_at {
+                Some(deleted_at) => SyncedChange::Deleted {
+                    tombstone: Tombstone {
+                        todo_id: todo.todo_id,
+                        deleted_at,
+                    },
+                    changed_at: todo.updated_at,
+                },
+                None => SyncedChange::Changed(Box::new(todo.clone())),
+            })
+            .chain(
+                tombstones
+                    .iter()
+                    .filter(|(owner, _)| *owner == user_id)
+                    .map(|(_, tombstone)| SyncedChange::Deleted {
+                        tombstone: tombstone.clone(),
+                        changed_at: tombstone.deleted_at,
+                    }),
+            )
+            .filter(|change| {
+                since.is_none_or(|since| {
+                    let cursor = change.cursor();
+                    (cursor.changed_at, cursor.todo_id) > (since.changed_at, since.todo_id)
+                })
+            })
+            .collect();
+        changes.sort_by_key(|change| {
+            let cursor = change.cursor();
+            (cursor.changed_at, cursor.todo_id)
+        });
+        changes.truncate(limit.max(0) as usize);
+
+        Ok(changes)
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryRepository {
+    async fn create_user(&self, email: &str, password: &str) -> Result<User, Error> {
+        let mut users = self.users.lock().unwrap();
+
+        // Postgres enforces this with the unique constraint on `users.email`.
+        if users.values().any(|user| user.email == email) {
+            return Err(Error::Protocol(format!("duplicate email: {}", email)));
+        }
+
+        let user = User::new(email, password);
+        users.insert(user.user_id, user.clone());
+
+        Ok(user)
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, Error> {
+        Ok(self
+            .users
+            .lock()
+            .unwrap()
+            .values()
+            .find(|user| user.email == email)
+            .cloned())
+    }
+
+    async fn get_user_by_id(&self, user_id: Uuid) -> Result<Option<User>, Error> {
+        Ok(self.users.lock().unwrap().get(&user_id).cloned())
+    }
+}
@@ -1,2 +1,308 @@
+use std::future::Future;
+
+use async_trait::async_trait;
+use sqlx::{
+    migrate::{Migrate, Migration, Migrator},
+    Connection, Error, PgPool,
+};
+use tracing::info;
+use uuid::Uuid;
+
+use audit::{AuditEvent, NewAuditEvent};
+use bulk::{BulkAction, BulkResult};
+use changes::{LoggedChange, SyncCursor, SyncedChange};
+use history::TodoEvent;
+use listing::{Page, TodoQuery};
+use lists::ListSummary;
+use ordering::Placement;
+use search::TodoMatch;
+use tags::{Tag, TagSummary};
+use todo::{NewTodo, Todo, TodoChanges};
+use user::User;
+
+pub mod archive;
+pub mod audit;
+pub mod bulk;
+pub mod changes;
+pub mod history;
+pub mod listing;
+pub mod lists;
+pub mod memory;
+pub mod ordering;
+pub mod priority;
+pub mod quick_add;
+pub mod recurrence;
+pub mod search;
+pub mod shorthand;
+pub mod subtasks;
+pub mod tags;
 pub mod todo;
+pub mod trash;
 pub mod user;
+
+/// The migrations in `migrations/`, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Applies any pending migrations. sqlx holds an advisory lock while it does,
+/// so that when several machines boot at once only one of them applies
+/// migrations and the rest wait for it.
+#[tracing::instrument(skip(db))]
+pub async fn migrate(db: &PgPool) -> Result<(), Error> {
+    info!("applying pending migrations");
+    MIGRATOR.run(db).await?;
+    info!("migrations are up to date");
+
+    Ok(())
+}
+
+/// The embedded migrations that have not been applied to the database yet.
+#[tracing::instrument(skip(db))]
+pub async fn pending_migrations(db: &PgPool) -> Result<Vec<&'static Migration>, Error> {
+    let mut conn = db.acquire().await?;
+
+    // Nothing has been applied to a fresh database, which won't have sqlx's
+    // bookkeeping table yet.
+    let initialized: bool =
+        sqlx::query_scalar("select to_regclass('_sqlx_migrations') is not null")
+            .fetch_one(&mut *conn)
+            .await?;
+    let applied = if initialized {
+        conn.list_applied_migrations().await?
+    } else {
+        vec![]
+    };
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .filter(|migration| !applied.iter().any(|a| a.version == migration.version))
+        .collect())
+}
+
+/// Runs `task` unless another instance is already running the one called
+/// `name`, returning its output if it ran. Instances take turns by way of an
+/// advisory lock held for as long as the task runs.
+#[tracing::instrument(skip(db, task))]
+pub async fn run_alone<T>(
+    db: &PgPool,
+    name: &str,
+    task: impl Future<Output = T>,
+) -> Result<Option<T>, Error> {
+    let mut conn = db.acquire().await?;
+
+    let locked: bool = sqlx::query_scalar("select pg_try_advisory_lock(hashtextextended($1, 0))")
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !locked {
+        return Ok(None);
+    }
+
+    let output = task.await;
+
+    // The lock is the connection's rather than a transaction's, so it mustn't
+    // go back to the pool still holding it.
+    let unlocked = sqlx::query("select pg_advisory_unlock(hashtextextended($1, 0))")
+        .bind(name)
+        .execute(&mut *conn)
+        .await;
+    if let Err(e) = unlocked {
+        conn.detach().close().await.ok();
+        return Err(e);
+    }
+
+    Ok(Some(output))
+}
+
+/// Storage for a user's todos.
+///
+/// Handlers only ever talk to this trait so that they can be exercised against
+/// the in-memory implementation in [`memory`] as well as Postgres.
+#[async_trait]
+pub trait TodoRepository: Send + Sync {
+    /// Errors with `RowNotFound` if the parent isn't one of the user's todos,
+    /// or if there's a todo with the id asked for already.
+    async fn create_todo(&self, user_id: Uuid, todo: NewTodo) -> Result<Todo, Error>;
+
+    /// All of the user's todos, oldest first.
+    async fn get_todos(&self, user_id: Uuid) -> Result<Vec<Todo>, Error>;
+
+    /// One page of the user's todos; see [`listing`].
+    async fn list_todos(&self, user_id: Uuid, query: &TodoQuery) -> Result<Page, Error>;
+
+    /// Full-text search over the user's todos, best matches first. Only the
+    /// filters in `filter` apply, not its sorting or paging.
+    async fn search_todos(
+        &self,
+        user_id: Uuid,
+        text: &str,
+        filter: &TodoQuery,
+    ) -> Result<Vec<TodoMatch>, Error>;
+
+    async fn get_todo_by_id(&self, user_id: Uuid, todo_id: Uuid) -> Result<Todo, Error>;
+
+    /// Moves the todo to the trash along with its subtasks; see [`trash`].
+    async fn delete_todo_by_id(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error>;
+
+    /// Marks a todo done, or not done. Marking a recurring todo done schedules
+    /// its next occurrence, whose id is returned; see [`recurrence`]. Errors
+    /// with `RowNotFound` if `version` is given and the todo has changed since.
+    async fn toggle_todo_by_id(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        version: Option<i64>,
+    ) -> Result<Option<Uuid>, Error>;
+
+    /// Everything nested under the given todos, at any depth; see [`subtasks`].
+    async fn get_subtasks(&self, user_id: Uuid, todo_ids: &[Uuid]) -> Result<Vec<Todo>, Error>;
+
+    /// Marks a todo done along with everything nested under it.
+    async fn complete_todo_with_subtasks(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error>;
+
+    /// Moves a todo next to another one; see [`ordering`]. Errors with
+    /// `RowNotFound` if either isn't the user's.
+    async fn move_todo(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        placement: Placement,
+    ) -> Result<(), Error>;
+
+    /// Returns the id of the todo's next occurrence if marking it done
+    /// scheduled one. Errors with `RowNotFound` if `changes.version` is given
+    /// and the todo has been changed since.
+    async fn update_todo_by_id(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        changes: TodoChanges,
+    ) -> Result<Option<Uuid>, Error>;
+
+    /// Does the same to many todos at once; see [`bulk`].
+    async fn update_todos(
+        &self,
+        user_id: Uuid,
+        todo_ids: &[Uuid],
+        action: &BulkAction,
+    ) -> Result<Vec<BulkResult>, Error>;
+}
+
+/// Management of a user's tags. Tags are put on todos through
+/// [`TodoRepository`].
+#[async_trait]
+pub trait TagRepository: Send + Sync {
+    /// The user's tags by name, each with how many todos it's on.
+    async fn list_tags(&self, user_id: Uuid) -> Result<Vec<TagSummary>, Error>;
+
+    /// Renames and recolors a tag. Errors with `RowNotFound` if it isn't the
+    /// user's.
+    async fn update_tag(
+        &self,
+        user_id: Uuid,
+        tag_id: Uuid,
+        name: &str,
+        color: &str,
+    ) -> Result<Tag, Error>;
+
+    /// Moves every todo tagged `from` over to `into`, then deletes `from`.
+    /// Errors with `RowNotFound` unless both are the user's.
+    async fn merge_tags(&self, user_id: Uuid, from: Uuid, into: Uuid) -> Result<(), Error>;
+
+    /// Deletes a tag, taking it off every todo.
+    async fn delete_tag(&self, user_id: Uuid, tag_id: Uuid) -> Result<(), Error>;
+}
+
+/// The lists a user files todos under. Todos are put on lists through
+/// [`TodoRepository`].
+#[async_trait]
+pub trait ListRepository: Send + Sync {
+    /// The user's lists by name, each with how many todos are on it.
+    async fn get_lists(&self, user_id: Uuid) -> Result<Vec<ListSummary>, Error>;
+}
+
+/// The user's deleted todos; see [`trash`]. Todos are put in the trash
+/// through [`TodoRepository`].
+#[async_trait]
+pub trait TrashRepository: Send + Sync {
+    /// What's in the trash, most recently deleted first. Subtasks deleted along
+    /// with their parent are left out, as they come and go with it.
+    async fn get_trash(&self, user_id: Uuid) -> Result<Vec<Todo>, Error>;
+
+    /// Takes a todo out of the trash along with the subtasks deleted with it.
+    /// Errors with `RowNotFound` if it isn't in the user's trash.
+    async fn restore_todo(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error>;
+
+    /// Deletes a todo in the trash for good, along with its subtasks.
+    /// Errors with `RowNotFound` if it isn't in the user's trash.
+    async fn delete_todo_forever(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error>;
+
+    /// Deletes everything in the user's trash for good.
+    async fn empty_trash(&self, user_id: Uuid) -> Result<(), Error>;
+}
+
+/// The user's archived todos; see [`archive`].
+#[async_trait]
+pub trait ArchiveRepository: Send + Sync {
+    /// What's in the archive, most recently done first. Subtasks archived along
+    /// with their parent are left out, as they come and go with it.
+    async fn get_archive(&self, user_id: Uuid) -> Result<Vec<Todo>, Error>;
+
+    /// Archives every done top-level todo along with its subtasks, returning
+    /// how many todos were archived.
+    async fn archive_completed(&self, user_id: Uuid) -> Result<u64, Error>;
+
+    /// Takes a todo out of the archive along with its subtasks. Errors with
+    /// `RowNotFound` if it isn't in the user's archive.
+    async fn unarchive_todo(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error>;
+}
+
+/// What happened to the user's todos; see [`history`]. Events are recorded
+/// through [`TodoRepository`] and [`TrashRepository`] along with the changes.
+#[async_trait]
+pub trait HistoryRepository: Send + Sync {
+    /// Everything that happened to one of the user's todos, oldest first.
+    async fn get_history(&self, user_id: Uuid, todo_id: Uuid) -> Result<Vec<TodoEvent>, Error>;
+}
+
+/// The security audit log; see [`audit`].
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn record_audit_event(&self, event: NewAuditEvent) -> Result<(), Error>;
+
+    /// The user's latest events, newest first.
+    async fn get_audit_log(&self, user_id: Uuid) -> Result<Vec<AuditEvent>, Error>;
+}
+
+/// The log of changes to the user's todos; see [`changes`]. Changes are
+/// logged as they're made through the other repositories.
+#[async_trait]
+pub trait ChangeRepository: Send + Sync {
+    /// The latest change to each todo changed since `since`, oldest first, up
+    /// to `limit` of them.
+    async fn changes_since(
+        &self,
+        user_id: Uuid,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<LoggedChange>, Error>;
+
+    /// The user's todos changed or deleted since `since`, in the order of
+    /// [`SyncCursor`], up to `limit` of them.
+    async fn changed_since(
+        &self,
+        user_id: Uuid,
+        since: Option<SyncCursor>,
+        limit: i64,
+    ) -> Result<Vec<SyncedChange>, Error>;
+}
+
+/// Storage for user accounts.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn create_user(&self, email: &str, password: &str) -> Result<User, Error>;
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, Error>;
+
+    async fn get_user_by_id(&self, user_id: Uuid) -> Result<Option<User>, Error>;
+}
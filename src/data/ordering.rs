@@ -0,0 +1,160 @@
+//! Manual ordering of a user's todos, within each list and among the
+//! subtasks of each todo.
+//!
+//! Each todo has a `position`, and new todos go [`GAP`] after the last one.
+//! Only the positions of todos in the same list with the same parent are
+//! compared, so moving a todo leaves every other list alone.
+//! Moving a todo puts it halfway between its new neighbours, so usually only
+//! its own row changes; once two neighbours end up adjacent the whole list is
+//! spread out again first.
+
+use uuid::Uuid;
+
+/// The spacing between positions after renumbering.
+pub const GAP: i64 = 1 << 16;
+
+/// Where to move a todo to, relative to another one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    Before(Uuid),
+    After(Uuid),
+}
+
+impl Placement {
+    pub fn anchor(&self) -> Uuid {
+        match *self {
+            Placement::Before(anchor) | Placement::After(anchor) => anchor,
+        }
+    }
+}
+
+/// A position strictly between two neighbours, either of which may be missing
+/// at the ends of the list. `None` when there's no room left.
+pub fn between(before: Option<i64>, after: Option<i64>) -> Option<i64> {
+    match (before, after) {
+        (None, None) => Some(GAP),
+        (Some(before), None) => before.checked_add(GAP),
+        (None, Some(after)) => after.checked_sub(GAP),
+        (Some(before), Some(after)) => (after - before >= 2).then(|| before + (after - before) / 2),
+    }
+}
+
+/// Where `todo_id` ends up, given the list's `(todo_id, position)`s in order.
+///
+/// Returns `None` if there's no room, in which case the list should be spread
+/// out with [`renumber`] and this tried again, and errors with `RowNotFound`
+/// if the anchor isn't in the list. Moving a todo next to itself leaves it
+/// where it is.
+pub fn new_position(
+    list: &[(Uuid, i64)],
+    todo_id: Uuid,
+    placement: Placement,
+) -> Result<Option<i64>, sqlx::Error> {
+    if placement.anchor() == todo_id {
+        return list
+            .iter()
+            .find(|(id, _)| *id == todo_id)
+            .map(|(_, position)| Some(*position))
+            .ok_or(sqlx::Error::RowNotFound);
+    }
+
+    let others: Vec<_> = list.iter().filter(|(id, _)| *id != todo_id).collect();
+
+    let anchor = others
+        .iter()
+        .position(|(id, _)| *id == placement.anchor())
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    let (before, after) = match placement {
+        Placement::Before(_) => (anchor.checked_sub(1), Some(anchor)),
+        Placement::After(_) => (Some(anchor), Some(anchor + 1)),
+    };
+    let position = |index: Option<usize>| {
+        index
+            .and_then(|index| others.get(index))
+            .map(|(_, position)| *position)
+    };
+
+    Ok(between(position(before), position(after)))
+}
+
+/// Positions spread [`GAP`] apart, keeping the list's order.
+pub fn renumber(list: &[(Uuid, i64)]) -> Vec<(Uuid, i64)> {
+    list.iter()
+        .zip(1..)
+        .map(|((id, _), rank)| (*id, rank * GAP))
+        .collect()
+}
+
+/// For when a renumbered list still has no room, which renumbering should
+/// make impossible.
+pub fn no_room() -> sqlx::Error {
+    sqlx::Error::Protocol("no room to move the todo, even after renumbering".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn between_splits_the_gap() {
+        assert_eq!(between(Some(0), Some(10)), Some(5));
+        assert_eq!(between(Some(4), Some(6)), Some(5));
+        assert_eq!(between(Some(5), Some(6)), None);
+        assert_eq!(between(Some(5), Some(5)), None);
+        assert_eq!(between(None, Some(GAP)), Some(0));
+        assert_eq!(between(Some(GAP), None), Some(2 * GAP));
+        assert_eq!(between(None, None), Some(GAP));
+    }
+
+    #[test]
+    fn moves_land_between_neighbours() {
+        let [a, b, c] = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let list = [(a, GAP), (b, 2 * GAP), (c, 3 * GAP)];
+
+        // c before b, i.e. between a and b.
+        assert_eq!(
+            new_position(&list, c, Placement::Before(b)).unwrap(),
+            Some(GAP + GAP / 2)
+        );
+        // a after c, i.e. to the end.
+        assert_eq!(
+            new_position(&list, a, Placement::After(c)).unwrap(),
+            Some(4 * GAP)
+        );
+        // c before a, i.e. to the start.
+        assert_eq!(
+            new_position(&list, c, Placement::Before(a)).unwrap(),
+            Some(0)
+        );
+        // b after a, where it already is: between a and c.
+        assert_eq!(
+            new_position(&list, b, Placement::After(a)).unwrap(),
+            Some(2 * GAP)
+        );
+    }
+
+    #[test]
+    fn crowded_lists_need_renumbering() {
+        let [a, b, c] = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let list = [(a, 1), (b, 2), (c, 3)];
+
+        assert_eq!(new_position(&list, c, Placement::After(a)).unwrap(), None);
+
+        let list = renumber(&list);
+        assert_eq!(list, [(a, GAP), (b, 2 * GAP), (c, 3 * GAP)]);
+        assert!(new_position(&list, c, Placement::After(a))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn missing_anchors_are_not_found() {
+        let a = Uuid::new_v4();
+
+        assert!(matches!(
+            new_position(&[(a, GAP)], a, Placement::Before(Uuid::new_v4())),
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+}
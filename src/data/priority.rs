@@ -0,0 +1,153 @@
+//! How urgent a todo is.
+//!
+//! Set from a select on the forms, or typed into the content as `!1` (low)
+//! through `!4` (urgent) or by name as in `!high`; see [`super::shorthand`].
+//! Stored as a `smallint` so that sorting and "at least" filters compare levels
+//! directly.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "lowercase")]
+#[repr(i16)]
+pub enum Priority {
+    #[default]
+    None = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    Urgent = 4,
+}
+
+impl Priority {
+    /// Least urgent first.
+    pub const ALL: [Priority; 5] = [
+        Priority::None,
+        Priority::Low,
+        Priority::Medium,
+        Priority::High,
+        Priority::Urgent,
+    ];
+
+    pub fn from_level(level: i16) -> Option<Self> {
+        Self::ALL.get(usize::try_from(level).ok()?).copied()
+    }
+
+    pub fn level(self) -> i16 {
+        self as i16
+    }
+
+    /// The priority a `!1`–`!4` or `!low`–`!urgent` word stands for.
+    pub fn from_shorthand(word: &str) -> Option<Self> {
+        match word.to_lowercase().as_str() {
+            "!1" | "!low" => Some(Priority::Low),
+            "!2" | "!medium" => Some(Priority::Medium),
+            "!3" | "!high" => Some(Priority::High),
+            "!4" | "!urgent" => Some(Priority::Urgent),
+            _ => None,
+        }
+    }
+
+    /// As used in forms, query strings and JSON.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::None => "none",
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        }
+    }
+
+    /// For people.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Priority::None => "No priority",
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+            Priority::Urgent => "Urgent",
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Priority::None
+    }
+
+    /// One `!` per level, shown before the todo.
+    pub fn marker(&self) -> String {
+        "!".repeat(self.level() as usize)
+    }
+
+    /// `#rrggbb` for the marker.
+    pub fn color(&self) -> &'static str {
+        match self {
+            Priority::None => "#9ca3af",
+            Priority::Low => "#3b82f6",
+            Priority::Medium => "#eab308",
+            Priority::High => "#f97316",
+            Priority::Urgent => "#ef4444",
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Priority {
+    type Err = ();
+
+    /// Parses a [`Priority::name`].
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|priority| priority.name() == name)
+            .ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for priority in Priority::ALL {
+            assert_eq!(priority.name().parse(), Ok(priority));
+            assert_eq!(Priority::from_level(priority.level()), Some(priority));
+        }
+        assert_eq!("High".parse::<Priority>(), Err(()));
+        assert_eq!(Priority::from_level(5), None);
+        assert_eq!(Priority::from_level(-1), None);
+    }
+
+    #[test]
+    fn shorthand_goes_from_low_to_urgent() {
+        assert_eq!(Priority::from_shorthand("!1"), Some(Priority::Low));
+        assert_eq!(Priority::from_shorthand("!4"), Some(Priority::Urgent));
+        assert_eq!(Priority::from_shorthand("!High"), Some(Priority::High));
+        assert_eq!(Priority::from_shorthand("!none"), None);
+        assert_eq!(Priority::from_shorthand("!0"), None);
+        assert_eq!(Priority::from_shorthand("!5"), None);
+        assert_eq!(Priority::from_shorthand("!!"), None);
+        assert_eq!(Priority::from_shorthand("4"), None);
+    }
+}
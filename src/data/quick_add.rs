@@ -0,0 +1,466 @@
+//! Natural-language quick add: dates, times and repeats written in words in a
+//! new todo's content, as in "Water plants every monday 9am #garden".
+//!
+//! The phrases recognized are taken out of the content along with the
+//! [shorthand](super::shorthand) symbols. All times are in UTC, like the
+//! `datetime-local` inputs.
+//!
+//! - Dates: `today`, `tomorrow`, `monday` (the next one), `next monday`,
+//!   `on mon`, `next week`, `in 3 days`, `in 2 weeks`, `dec 5`, `5 december`
+//!   and `2023-12-05`.
+//! - Times: `5pm`, `5:30pm`, `5 pm`, `17:00`, `noon` and `midnight`, each
+//!   optionally after `at`. Dates without one are due at [`DEFAULT_TIME`].
+//! - Repeats: `daily`, `weekly`, `monthly`, `every day`, `every weekday`,
+//!   `every week`, `every month`, `every other week`, `every 3 days` and
+//!   `every monday and thursday`. Repeating todos without a date are first due
+//!   on the next day the rule allows.
+
+use time::{
+    macros::{format_description, time},
+    Date, Duration, Month, OffsetDateTime, Time, Weekday,
+};
+
+use super::{
+    recurrence::{Frequency, Recurrence},
+    shorthand::{self, Shorthand},
+};
+
+/// When todos given a date but no time are due.
+pub const DEFAULT_TIME: Time = time!(9:00);
+
+/// Splits the shorthand and any date, time and repeat out of a new todo's
+/// content. Only the first of each counts; later ones are left in the content,
+/// as is all of it if nothing else would be.
+pub fn parse(content: &str, now: OffsetDateTime) -> Shorthand {
+    let mut parsed = shorthand::parse(content);
+
+    let words: Vec<&str> = parsed.content.split_whitespace().collect();
+    let lower: Vec<String> = words
+        .iter()
+        .map(|word| word.trim_end_matches([',', '.']).to_lowercase())
+        .collect();
+    let lower: Vec<&str> = lower.iter().map(String::as_str).collect();
+
+    let mut date = None;
+    let mut at = None;
+    let mut recurrence = None;
+    let mut kept = vec![];
+
+    let mut i = 0;
+    while i < words.len() {
+        let rest = &lower[i..];
+
+        let consumed = if let Some((n, found)) =
+            recurrence.is_none().then(|| repeat(rest)).flatten()
+        {
+            recurrence = Some(found);
+            n
+        } else if let Some((n, found)) = date.is_none().then(|| day(rest, now.date())).flatten() {
+            date = Some(found);
+            n
+        } else if let Some((n, found)) = at.is_none().then(|| time_of_day(rest)).flatten() {
+            at = Some(found);
+            n
+        } else {
+            kept.push(words[i]);
+            1
+        };
+
+        i += consumed;
+    }
+
+    // A todo that's nothing but a date, say "Tomorrow", keeps it as content.
+    if (date.is_none() && at.is_none() && recurrence.is_none()) || kept.is_empty() {
+        return parsed;
+    }
+
+    parsed.content = kept.join(" ");
+    parsed.due_at = match (date, recurrence.as_ref()) {
+        (Some(date), _) => Some(date.with_time(at.unwrap_or(DEFAULT_TIME)).assume_utc()),
+        (None, Some(recurrence)) => first_occurrence(recurrence, at.unwrap_or(DEFAULT_TIME), now),
+        (None, None) => at.map(|at| {
+            let today = now.replace_time(at);
+            if today > now {
+                today
+            } else {
+                today + Duration::days(1)
+            }
+        }),
+    };
+    parsed.recurrence = recurrence;
+
+    parsed
+}
+
+/// The first time a new series is due: today if the rule allows it and `at`
+/// hasn't gone by yet, otherwise the next day it allows.
+fn first_occurrence(
+    recurrence: &Recurrence,
+    at: Time,
+    now: OffsetDateTime,
+) -> Option<OffsetDateTime> {
+    let today = now.replace_time(at);
+    if today > now && recurrence.falls_on(today.date()) {
+        return Some(today);
+    }
+
+    // The interval counts from the first occurrence, so it doesn't apply yet.
+    let first = Recurrence {
+        interval: 1,
+        count: None,
+        ..recurrence.clone()
+    };
+    first.next(Some(today), now).map(|next| next.due_at)
+}
+
+fn repeat(words: &[&str]) -> Option<(usize, Recurrence)> {
+    let rule = |frequency, interval| Recurrence {
+        frequency,
+        interval,
+        from_completion: false,
+        count: None,
+        until: None,
+    };
+    let unit = |word: &str| match word.strip_suffix('s').unwrap_or(word) {
+        "day" => Some(Frequency::Daily),
+        "week" => Some(Frequency::Weekly(vec![])),
+        "month" => Some(Frequency::Monthly(None)),
+        _ => None,
+    };
+
+    match words {
+        ["daily", ..] => Some((1, rule(Frequency::Daily, 1))),
+        ["weekly", ..] => Some((1, rule(Frequency::Weekly(vec![]), 1))),
+        ["monthly", ..] => Some((1, rule(Frequency::Monthly(None), 1))),
+        ["every", "weekday" | "weekdays", ..] => Some((
+            2,
+            rule(
+                Frequency::Weekly(vec![
+                    Weekday::Monday,
+                    Weekday::Tuesday,
+                    Weekday::Wednesday,
+                    Weekday::Thursday,
+                    Weekday::Friday,
+                ]),
+                1,
+            ),
+        )),
+        ["every", "other", next, ..] => Some((3, rule(unit(next)?, 2))),
+        ["every", n, next, ..] if n.parse::<u32>().is_ok_and(|n| n >= 1) => {
+            Some((3, rule(unit(next)?, n.parse().ok()?)))
+        }
+        ["every", next, ..] if !next.ends_with('s') && unit(next).is_some() => {
+            Some((2, rule(unit(next)?, 1)))
+        }
+        ["every", rest @ ..] => {
+            // "every monday", "every mon, thu" or "every monday and thursday".
+            let mut days = vec![];
+            let mut used = 0;
+            for (i, word) in rest.iter().enumerate() {
+                match weekday(word, true) {
+                    Some(day) => {
+                        if !days.contains(&day) {
+                            days.push(day);
+                        }
+                        used = i + 1;
+                    }
+                    None if *word == "and" && !days.is_empty() => {}
+                    None => break,
+                }
+            }
+            days.sort_by_key(|day| day.number_days_from_monday());
+
+            (!days.is_empty()).then(|| (1 + used, rule(Frequency::Weekly(days), 1)))
+        }
+        _ => None,
+    }
+}
+
+fn day(words: &[&str], today: Date) -> Option<(usize, Date)> {
+    match words {
+        ["today", ..] => Some((1, today)),
+        ["tomorrow", ..] => Some((1, today.next_day()?)),
+        ["next", "week", ..] => Some((2, today + Duration::weeks(1))),
+        ["next" | "on", name, ..] if weekday(name, true).is_some() => {
+            Some((2, following(today, weekday(name, true)?)))
+        }
+        ["on", rest @ ..] => calendar(rest, today).map(|(n, date)| (n + 1, date)),
+        ["in", n, unit, ..] => {
+            let n: i64 = n.parse().ok().filter(|n| (1..=1000).contains(n))?;
+            let days = match unit.strip_suffix('s').unwrap_or(unit) {
+                "day" => n,
+                "week" => n * 7,
+                _ => return None,
+            };
+            Some((3, today + Duration::days(days)))
+        }
+        [name, ..] if weekday(name, false).is_some() => {
+            Some((1, following(today, weekday(name, false)?)))
+        }
+        _ => calendar(words, today),
+    }
+}
+
+/// A date written out: `dec 5`, `5 december` or `2023-12-05`. Dates without a
+/// year are the next one to come.
+fn calendar(words: &[&str], today: Date) -> Option<(usize, Date)> {
+    let upcoming = |month: Month, day: u8| {
+        let this_year = Date::from_calendar_date(today.year(), month, day).ok();
+        match this_year {
+            Some(date) if date >= today => Some(date),
+            _ => Date::from_calendar_date(today.year() + 1, month, day).ok(),
+        }
+    };
+
+    match words {
+        [first, second, ..] if month(first).is_some() && day_of_month(second).is_some() => {
+            Some((2, upcoming(month(first)?, day_of_month(second)?)?))
+        }
+        [first, second, ..] if day_of_month(first).is_some() && month(second).is_some() => {
+            Some((2, upcoming(month(second)?, day_of_month(first)?)?))
+        }
+        [iso, ..] => Date::parse(iso, format_description!("[year]-[month]-[day]"))
+            .ok()
+            .map(|date| (1, date)),
+        [] => None,
+    }
+}
+
+fn time_of_day(words: &[&str]) -> Option<(usize, Time)> {
+    if let ["at", rest @ ..] = words {
+        return time_of_day(rest).map(|(n, time)| (n + 1, time));
+    }
+
+    let clock = |text: &str| -> Option<(u8, u8)> {
+        let (hour, minute) = match text.split_once(':') {
+            Some((hour, minute)) if minute.len() == 2 => (hour.parse().ok()?, minute.parse().ok()?),
+            Some(_) => return None,
+            None => (text.parse().ok()?, 0),
+        };
+        (minute < 60).then_some((hour, minute))
+    };
+    let meridiem = |hour: u8, minute: u8, suffix: &str| -> Option<Time> {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+        let hour = match suffix {
+            "am" => hour % 12,
+            "pm" => hour % 12 + 12,
+            _ => return None,
+        };
+        Time::from_hms(hour, minute, 0).ok()
+    };
+
+    match words {
+        ["noon", ..] => Some((1, time!(12:00))),
+        ["midnight", ..] => Some((1, time!(0:00))),
+        [text, suffix, ..] if clock(text).is_some() && matches!(*suffix, "am" | "pm") => {
+            let (hour, minute) = clock(text)?;
+            Some((2, meridiem(hour, minute, suffix)?))
+        }
+        [text, ..] => {
+            if let Some(number) = text.strip_suffix("am").or_else(|| text.strip_suffix("pm")) {
+                let (hour, minute) = clock(number)?;
+                return Some((1, meridiem(hour, minute, &text[number.len()..])?));
+            }
+
+            // 24-hour times need the minutes, so plain numbers aren't taken.
+            let (hour, minute) = text.contains(':').then(|| clock(text)).flatten()?;
+            Some((1, Time::from_hms(hour, minute, 0).ok()?))
+        }
+        [] => None,
+    }
+}
+
+/// The first `weekday` after `today`.
+fn following(today: Date, weekday: Weekday) -> Date {
+    let mut date = today.next_day().unwrap_or(today);
+    while date.weekday() != weekday {
+        date = date.next_day().unwrap_or(date);
+    }
+    date
+}
+
+/// A weekday by name. Abbreviations like "sun" are everyday words too, so only
+/// count when `short` is allowed, after words like "every" and "next".
+fn weekday(word: &str, short: bool) -> Option<Weekday> {
+    let days = [
+        ("monday", "mon", Weekday::Monday),
+        ("tuesday", "tue", Weekday::Tuesday),
+        ("wednesday", "wed", Weekday::Wednesday),
+        ("thursday", "thu", Weekday::Thursday),
+        ("friday", "fri", Weekday::Friday),
+        ("saturday", "sat", Weekday::Saturday),
+        ("sunday", "sun", Weekday::Sunday),
+    ];
+
+    days.iter()
+        .find(|(long, abbreviation, _)| word == *long || (short && word == *abbreviation))
+        .map(|(_, _, day)| *day)
+}
+
+fn month(word: &str) -> Option<Month> {
+    let months = [
+        Month::January,
+        Month::February,
+        Month::March,
+        Month::April,
+        Month::May,
+        Month::June,
+        Month::July,
+        Month::August,
+        Month::September,
+        Month::October,
+        Month::November,
+        Month::December,
+    ];
+
+    months.into_iter().find(|month| {
+        let name = month.to_string().to_lowercase();
+        word == name || (word.len() >= 3 && name.starts_with(word))
+    })
+}
+
+/// "5", or "5th".
+fn day_of_month(word: &str) -> Option<u8> {
+    let number = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| word.strip_suffix(suffix))
+        .unwrap_or(word);
+
+    number.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::data::priority::Priority;
+
+    /// A Monday afternoon.
+    const NOW: OffsetDateTime = datetime!(2023-11-27 14:30 UTC);
+
+    fn due(content: &str) -> Option<OffsetDateTime> {
+        parse(content, NOW).due_at
+    }
+
+    #[test]
+    fn everything_is_split_out() {
+        let parsed = parse("Buy milk tomorrow 5pm #errands !high @home", NOW);
+
+        assert_eq!(parsed.content, "Buy milk");
+        assert_eq!(parsed.due_at, Some(datetime!(2023-11-28 17:00 UTC)));
+        assert_eq!(parsed.tags, ["errands"]);
+        assert_eq!(parsed.priority, Some(Priority::High));
+        assert_eq!(parsed.list.as_deref(), Some("home"));
+        assert_eq!(parsed.recurrence, None);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(due("Call mum today"), Some(datetime!(2023-11-27 9:00 UTC)));
+        assert_eq!(due("Call mum monday"), Some(datetime!(2023-12-04 9:00 UTC)));
+        assert_eq!(due("Call mum on wed"), Some(datetime!(2023-11-29 9:00 UTC)));
+        assert_eq!(
+            due("Call mum next fri"),
+            Some(datetime!(2023-12-01 9:00 UTC))
+        );
+        assert_eq!(
+            due("Call mum next week"),
+            Some(datetime!(2023-12-04 9:00 UTC))
+        );
+        assert_eq!(
+            due("Call mum in 3 days"),
+            Some(datetime!(2023-11-30 9:00 UTC))
+        );
+        assert_eq!(
+            due("Call mum in 2 weeks"),
+            Some(datetime!(2023-12-11 9:00 UTC))
+        );
+        assert_eq!(
+            due("Call mum dec 5th"),
+            Some(datetime!(2023-12-05 9:00 UTC))
+        );
+        assert_eq!(
+            due("Call mum 3 January"),
+            Some(datetime!(2024-01-03 9:00 UTC))
+        );
+        assert_eq!(
+            due("Call mum 2024-02-29"),
+            Some(datetime!(2024-02-29 9:00 UTC))
+        );
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(
+            due("Run at 6:15am tomorrow"),
+            Some(datetime!(2023-11-28 6:15 UTC))
+        );
+        assert_eq!(
+            due("Run tomorrow 7 pm"),
+            Some(datetime!(2023-11-28 19:00 UTC))
+        );
+        assert_eq!(
+            due("Run tomorrow at noon"),
+            Some(datetime!(2023-11-28 12:00 UTC))
+        );
+        // Later today, or tomorrow if it's gone by.
+        assert_eq!(due("Run 17:45"), Some(datetime!(2023-11-27 17:45 UTC)));
+        assert_eq!(due("Run 12am"), Some(datetime!(2023-11-28 0:00 UTC)));
+    }
+
+    #[test]
+    fn repeats() {
+        let parsed = parse("Water plants every monday and thu 8am", NOW);
+        assert_eq!(parsed.content, "Water plants");
+        assert_eq!(
+            parsed.recurrence.unwrap().to_string(),
+            "FREQ=WEEKLY;BYDAY=MO,TH"
+        );
+        assert_eq!(parsed.due_at, Some(datetime!(2023-11-30 8:00 UTC)));
+
+        let rule = |content: &str| parse(content, NOW).recurrence.map(|rule| rule.to_string());
+        assert_eq!(rule("Stretch daily").as_deref(), Some("FREQ=DAILY"));
+        assert_eq!(
+            rule("Stretch every 3 days").as_deref(),
+            Some("FREQ=DAILY;INTERVAL=3")
+        );
+        assert_eq!(
+            rule("Bins every other week").as_deref(),
+            Some("FREQ=WEEKLY;INTERVAL=2")
+        );
+        assert_eq!(
+            rule("Stand-up every weekday").as_deref(),
+            Some("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR")
+        );
+
+        // Today's time has gone by, so the first one is tomorrow.
+        assert_eq!(
+            due("Stretch every day at 9am"),
+            Some(datetime!(2023-11-28 9:00 UTC))
+        );
+        assert_eq!(
+            due("Stretch every day at 6pm"),
+            Some(datetime!(2023-11-27 18:00 UTC))
+        );
+    }
+
+    #[test]
+    fn ordinary_words_are_left_alone() {
+        for content in [
+            "Buy sun cream",
+            "Read chapter 5",
+            "Fix the 10 most common bugs",
+            "Plan may day",
+            "Ask about every option",
+            "Work on the report",
+            "Tomorrow",
+        ] {
+            let parsed = parse(content, NOW);
+            assert_eq!(parsed.content, content);
+            assert!(!parsed.is_recognized(), "{}", content);
+        }
+    }
+}
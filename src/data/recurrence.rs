@@ -0,0 +1,548 @@
+//! Todos that repeat.
+//!
+//! A recurring todo carries its rule as a subset of an RFC 5545 `RRULE`, e.g.
+//! `FREQ=WEEKLY;BYDAY=MO,TH`. Marking it done creates the next occurrence, due
+//! when the rule says, and hands the rule over to it, so the series always lives
+//! on its one open todo. Subtasks aren't carried over.
+//!
+//! Supported parts are `FREQ` (`DAILY`, `WEEKLY` or `MONTHLY`), `INTERVAL` (up
+//! to [`MAX_INTERVAL`]), `BYDAY` (plain weekdays, weekly only), `BYMONTHDAY` (a single day, monthly
+//! only), `COUNT` and `UNTIL`, plus the non-standard `X-FROM=COMPLETION` for
+//! rules counted from when the todo was done rather than when it was due.
+
+use std::{fmt, str::FromStr};
+
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
+use time::{
+    macros::format_description, util::days_in_year_month, Date, Duration, Month, OffsetDateTime,
+    Weekday,
+};
+
+/// How often a todo repeats.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    /// Every how many days, weeks or months.
+    pub interval: u32,
+    /// Step from when the todo was done instead of when it was due.
+    pub from_completion: bool,
+    /// How many occurrences are left, this one included.
+    pub count: Option<u32>,
+    /// The last day an occurrence can fall on.
+    pub until: Option<Date>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    /// On these weekdays, or the due date's if none are given.
+    Weekly(Vec<Weekday>),
+    /// On this day of the month, or the due date's if none is given. Months
+    /// too short for it use their last day instead.
+    Monthly(Option<u8>),
+}
+
+/// When the next occurrence of a series is due, and its rule from then on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    pub due_at: OffsetDateTime,
+    pub recurrence: Option<Recurrence>,
+}
+
+impl Recurrence {
+    /// The occurrence after one due at `due_at` and done at `done_at`, unless
+    /// the series is over.
+    ///
+    /// Rules that follow the calendar skip any occurrences that have already
+    /// gone by, so finishing a weekly chore late doesn't leave a backlog of them.
+    pub fn next(
+        &self,
+        due_at: Option<OffsetDateTime>,
+        done_at: OffsetDateTime,
+    ) -> Option<Occurrence> {
+        if self.count.is_some_and(|count| count <= 1) {
+            return None;
+        }
+
+        let base = match due_at {
+            Some(due_at) if !self.from_completion => due_at,
+            _ => done_at,
+        };
+        let frequency = self.frequency.anchored(base.date());
+
+        let mut next = frequency.step(base, self.interval)?;
+        while next <= done_at {
+            next = frequency.step(next, self.interval)?;
+        }
+
+        if self.until.is_some_and(|until| next.date() > until) {
+            return None;
+        }
+
+        Some(Occurrence {
+            due_at: next,
+            recurrence: Some(Self {
+                frequency,
+                count: self.count.map(|count| count - 1),
+                ..self.clone()
+            }),
+        })
+    }
+
+    /// Whether the rule allows an occurrence on `date`, ignoring the interval.
+    /// Rules left to the due date allow any.
+    pub fn falls_on(&self, date: Date) -> bool {
+        match &self.frequency {
+            Frequency::Daily => true,
+            Frequency::Weekly(days) => days.is_empty() || days.contains(&date.weekday()),
+            Frequency::Monthly(None) => true,
+            Frequency::Monthly(Some(day)) => {
+                date.day() == (*day).min(days_in_year_month(date.year(), date.month()))
+            }
+        }
+    }
+
+    /// For people, e.g. "Every 2 weeks on Mon, Thu".
+    pub fn describe(&self) -> String {
+        let (unit, every) = match self.frequency {
+            Frequency::Daily => ("day", "Daily"),
+            Frequency::Weekly(_) => ("week", "Weekly"),
+            Frequency::Monthly(_) => ("month", "Monthly"),
+        };
+        let mut description = match self.interval {
+            1 => every.to_owned(),
+            n => format!("Every {} {}s", n, unit),
+        };
+
+        match &self.frequency {
+            Frequency::Weekly(days) if !days.is_empty() => {
+                let days: Vec<_> = days
+                    .iter()
+                    .map(|day| day.to_string()[..3].to_owned())
+                    .collect();
+                description += &format!(" on {}", days.join(", "));
+            }
+            Frequency::Monthly(Some(day)) => description += &format!(" on day {}", day),
+            _ => {}
+        }
+
+        if self.from_completion {
+            description += " after completion";
+        }
+        if let Some(until) = self.until {
+            if let Ok(until) = until.format(format_description!(
+                "[month repr:short] [day padding:none], [year]"
+            )) {
+                description += &format!(", until {}", until);
+            }
+        }
+        if let Some(count) = self.count {
+            description += &format!(", {} left", count);
+        }
+
+        description
+    }
+}
+
+impl Frequency {
+    /// Pins down the day the rule falls on if it was left to the due date.
+    fn anchored(&self, date: Date) -> Self {
+        match self {
+            Frequency::Weekly(days) if days.is_empty() => Frequency::Weekly(vec![date.weekday()]),
+            Frequency::Monthly(None) => Frequency::Monthly(Some(date.day())),
+            other => other.clone(),
+        }
+    }
+
+    /// The first occurrence after `from`, at the same time of day.
+    fn step(&self, from: OffsetDateTime, interval: u32) -> Option<OffsetDateTime> {
+        match self {
+            Frequency::Daily => from.checked_add(Duration::days(interval.into())),
+            Frequency::Weekly(days) => {
+                let weekday = i64::from(from.weekday().number_days_from_monday());
+                let monday = from.checked_sub(Duration::days(weekday))?;
+                // Later the same week, or else in the week `interval` weeks on.
+                let this_week = weekday + 1..7;
+                let that_week = 7 * i64::from(interval)..7 * i64::from(interval) + 7;
+
+                this_week
+                    .chain(that_week)
+                    .filter_map(|offset| monday.checked_add(Duration::days(offset)))
+                    .find(|date| days.contains(&date.weekday()))
+            }
+            Frequency::Monthly(day) => {
+                let months = i64::from(from.year()) * 12
+                    + i64::from(u8::from(from.month()) - 1)
+                    + i64::from(interval);
+                let year = i32::try_from(months.div_euclid(12)).ok()?;
+                let month = Month::try_from(u8::try_from(months.rem_euclid(12)).ok()? + 1).ok()?;
+                let day = day
+                    .unwrap_or(from.day())
+                    .min(days_in_year_month(year, month));
+
+                Some(from.replace_date(Date::from_calendar_date(year, month, day).ok()?))
+            }
+        }
+    }
+}
+
+/// The most days, weeks or months between occurrences.
+pub const MAX_INTERVAL: u32 = 1000;
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Monday),
+    ("TU", Weekday::Tuesday),
+    ("WE", Weekday::Wednesday),
+    ("TH", Weekday::Thursday),
+    ("FR", Weekday::Friday),
+    ("SA", Weekday::Saturday),
+    ("SU", Weekday::Sunday),
+];
+
+impl fmt::Display for Recurrence {
+    /// As an `RRULE`, which [`FromStr`] reads back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.frequency {
+            Frequency::Daily => f.write_str("FREQ=DAILY")?,
+            Frequency::Weekly(_) => f.write_str("FREQ=WEEKLY")?,
+            Frequency::Monthly(_) => f.write_str("FREQ=MONTHLY")?,
+        }
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        match &self.frequency {
+            Frequency::Weekly(days) if !days.is_empty() => {
+                let days: Vec<_> = WEEKDAYS
+                    .iter()
+                    .filter(|(_, day)| days.contains(day))
+                    .map(|(code, _)| *code)
+                    .collect();
+                write!(f, ";BYDAY={}", days.join(","))?;
+            }
+            Frequency::Monthly(Some(day)) => write!(f, ";BYMONTHDAY={}", day)?,
+            _ => {}
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(
+                f,
+                ";UNTIL={:04}{:02}{:02}",
+                until.year(),
+                u8::from(until.month()),
+                until.day()
+            )?;
+        }
+        if self.from_completion {
+            f.write_str(";X-FROM=COMPLETION")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    /// Parses an `RRULE`, with or without the `RRULE:` prefix and in any case.
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim().to_uppercase();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(&rule);
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = vec![];
+        let mut by_month_day = None;
+        let mut count = None;
+        let mut until = None;
+        let mut from_completion = false;
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected NAME=VALUE, got {}", part))?;
+            let invalid = || format!("invalid {}: {}", name, value);
+
+            match name {
+                "FREQ" => frequency = Some(value),
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|n| (1..=MAX_INTERVAL).contains(n))
+                        .ok_or_else(invalid)?;
+                }
+                "BYDAY" => {
+                    for code in value.split(',') {
+                        let (_, day) = WEEKDAYS
+                            .iter()
+                            .find(|(name, _)| *name == code)
+                            .ok_or_else(invalid)?;
+                        if !by_day.contains(day) {
+                            by_day.push(*day);
+                        }
+                    }
+                }
+                "BYMONTHDAY" => {
+                    by_month_day = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|day| (1..=31).contains(day))
+                            .ok_or_else(invalid)?,
+                    );
+                }
+                "COUNT" => {
+                    count = Some(value.parse().ok().filter(|n| *n >= 1).ok_or_else(invalid)?)
+                }
+                "UNTIL" => {
+                    // A date, or a date-time of which only the date counts.
+                    let date = value.get(..8).ok_or_else(invalid)?;
+                    until = Some(
+                        Date::parse(date, format_description!("[year][month][day]"))
+                            .map_err(|_| invalid())?,
+                    );
+                }
+                "X-FROM" => {
+                    from_completion = match value {
+                        "COMPLETION" => true,
+                        "DUE" => false,
+                        _ => return Err(invalid()),
+                    };
+                }
+                _ => return Err(format!("unsupported rule part {}", name)),
+            }
+        }
+
+        by_day.sort_by_key(|day| day.number_days_from_monday());
+        let frequency = match frequency {
+            Some("DAILY") => Frequency::Daily,
+            Some("WEEKLY") => Frequency::Weekly(by_day.clone()),
+            Some("MONTHLY") => Frequency::Monthly(by_month_day),
+            Some(other) => return Err(format!("unsupported FREQ: {}", other)),
+            None => return Err("missing FREQ".to_owned()),
+        };
+        match frequency {
+            Frequency::Weekly(_) => {}
+            _ if !by_day.is_empty() => return Err("BYDAY only works with FREQ=WEEKLY".to_owned()),
+            _ => {}
+        }
+        if by_month_day.is_some() && !matches!(frequency, Frequency::Monthly(_)) {
+            return Err("BYMONTHDAY only works with FREQ=MONTHLY".to_owned());
+        }
+
+        Ok(Self {
+            frequency,
+            interval,
+            from_completion,
+            count,
+            until,
+        })
+    }
+}
+
+// Stored as its `RRULE` text.
+impl Type<Postgres> for Recurrence {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Recurrence {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <String as Encode<Postgres>>::encode(self.to_string(), buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Recurrence {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn rule(rule: &str) -> Recurrence {
+        rule.parse().unwrap()
+    }
+
+    fn next_due(
+        rule_text: &str,
+        due_at: OffsetDateTime,
+        done_at: OffsetDateTime,
+    ) -> Option<OffsetDateTime> {
+        rule(rule_text)
+            .next(Some(due_at), done_at)
+            .map(|next| next.due_at)
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        for text in [
+            "FREQ=DAILY",
+            "FREQ=DAILY;INTERVAL=3;X-FROM=COMPLETION",
+            "FREQ=WEEKLY;BYDAY=MO,TH",
+            "FREQ=MONTHLY;INTERVAL=2;BYMONTHDAY=31;COUNT=4;UNTIL=20241231",
+        ] {
+            assert_eq!(rule(text).to_string(), text);
+        }
+
+        assert_eq!(
+            rule("rrule:freq=weekly;byday=th,mo;until=20241231T235959Z").to_string(),
+            "FREQ=WEEKLY;BYDAY=MO,TH;UNTIL=20241231"
+        );
+    }
+
+    #[test]
+    fn unsupported_rules_are_rejected() {
+        for text in [
+            "",
+            "FREQ=YEARLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;INTERVAL=1001",
+            "FREQ=WEEKLY;INTERVAL=4294967295",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYDAY=1MO",
+            "FREQ=MONTHLY;BYMONTHDAY=32",
+            "FREQ=DAILY;BYHOUR=9",
+            "every day",
+        ] {
+            assert!(text.parse::<Recurrence>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn daily_rules_keep_the_time_of_day() {
+        let due = datetime!(2023-11-27 17:00 UTC);
+
+        assert_eq!(
+            next_due("FREQ=DAILY", due, datetime!(2023-11-27 09:00 UTC)),
+            Some(datetime!(2023-11-28 17:00 UTC))
+        );
+        // Overdue occurrences are skipped.
+        assert_eq!(
+            next_due(
+                "FREQ=DAILY;INTERVAL=2",
+                due,
+                datetime!(2023-12-02 09:00 UTC)
+            ),
+            Some(datetime!(2023-12-03 17:00 UTC))
+        );
+        assert_eq!(
+            next_due(
+                "FREQ=DAILY;INTERVAL=3;X-FROM=COMPLETION",
+                due,
+                datetime!(2023-12-02 09:00 UTC)
+            ),
+            Some(datetime!(2023-12-05 09:00 UTC))
+        );
+    }
+
+    #[test]
+    fn weekly_rules_land_on_their_weekdays() {
+        // A Monday.
+        let due = datetime!(2023-11-27 08:00 UTC);
+        let done = datetime!(2023-11-27 07:00 UTC);
+
+        assert_eq!(
+            next_due("FREQ=WEEKLY;BYDAY=MO,TH", due, done),
+            Some(datetime!(2023-11-30 08:00 UTC))
+        );
+        assert_eq!(
+            next_due(
+                "FREQ=WEEKLY;BYDAY=MO,TH",
+                datetime!(2023-11-30 08:00 UTC),
+                done
+            ),
+            Some(datetime!(2023-12-04 08:00 UTC))
+        );
+        assert_eq!(
+            next_due(
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH",
+                datetime!(2023-11-30 08:00 UTC),
+                done
+            ),
+            Some(datetime!(2023-12-11 08:00 UTC))
+        );
+        assert_eq!(
+            next_due(
+                "FREQ=WEEKLY;INTERVAL=1000;BYDAY=MO",
+                datetime!(2023-11-30 08:00 UTC),
+                done
+            ),
+            Some(datetime!(2043-01-26 08:00 UTC))
+        );
+
+        let next = rule("FREQ=WEEKLY").next(Some(due), done).unwrap();
+        assert_eq!(next.due_at, datetime!(2023-12-04 08:00 UTC));
+        assert_eq!(next.recurrence.unwrap().to_string(), "FREQ=WEEKLY;BYDAY=MO");
+    }
+
+    #[test]
+    fn monthly_rules_fall_back_to_the_last_day() {
+        let due = datetime!(2024-01-31 12:00 UTC);
+        let done = datetime!(2024-01-01 12:00 UTC);
+
+        let next = rule("FREQ=MONTHLY").next(Some(due), done).unwrap();
+        assert_eq!(next.due_at, datetime!(2024-02-29 12:00 UTC));
+
+        let after = next
+            .recurrence
+            .unwrap()
+            .next(Some(next.due_at), done)
+            .unwrap();
+        assert_eq!(after.due_at, datetime!(2024-03-31 12:00 UTC));
+
+        assert_eq!(
+            next_due("FREQ=MONTHLY;INTERVAL=12;BYMONTHDAY=15", due, done),
+            Some(datetime!(2025-01-15 12:00 UTC))
+        );
+    }
+
+    #[test]
+    fn series_end() {
+        let due = datetime!(2023-11-27 08:00 UTC);
+        let done = datetime!(2023-11-27 07:00 UTC);
+
+        let next = rule("FREQ=DAILY;COUNT=2").next(Some(due), done).unwrap();
+        assert_eq!(next.recurrence.as_ref().unwrap().count, Some(1));
+        assert_eq!(next.recurrence.unwrap().next(Some(next.due_at), done), None);
+
+        assert_eq!(
+            next_due("FREQ=DAILY;UNTIL=20231128", due, done),
+            Some(datetime!(2023-11-28 08:00 UTC))
+        );
+        assert_eq!(next_due("FREQ=DAILY;UNTIL=20231127", due, done), None);
+    }
+
+    #[test]
+    fn rules_are_described() {
+        assert_eq!(rule("FREQ=DAILY").describe(), "Daily");
+        assert_eq!(
+            rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH").describe(),
+            "Every 2 weeks on Mon, Thu"
+        );
+        assert_eq!(
+            rule("FREQ=DAILY;INTERVAL=3;X-FROM=COMPLETION;COUNT=5").describe(),
+            "Every 3 days after completion, 5 left"
+        );
+        assert_eq!(
+            rule("FREQ=MONTHLY;BYMONTHDAY=1;UNTIL=20240601").describe(),
+            "Monthly on day 1, until Jun 1, 2024"
+        );
+    }
+}
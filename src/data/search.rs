@@ -0,0 +1,198 @@
+//! Full-text search over todo content.
+//!
+//! Postgres does the matching and ranking (see [`super::todo::search_todos`]);
+//! this module turns what the user typed into a `tsquery` and the highlighted
+//! headline Postgres hands back into something templates can render safely.
+
+use serde::Serialize;
+
+use super::todo::Todo;
+
+/// Wraps highlighted words in the headlines returned by `ts_headline`.
+///
+/// Control characters rather than `<mark>` so that the content itself never has
+/// to be trusted as HTML: templates escape each [`Fragment`] on its own.
+pub const START_SELECTION: char = '\u{2}';
+pub const STOP_SELECTION: char = '\u{3}';
+
+/// The most matches a search returns.
+pub const MAX_MATCHES: i64 = 50;
+
+/// A todo matching a search.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoMatch {
+    #[serde(flatten)]
+    pub todo: Todo,
+    pub rank: f32,
+    pub snippet: Vec<Fragment>,
+}
+
+/// A run of a snippet that either did or didn't match the search.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Fragment {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+/// Splits search input into lowercase words, dropping punctuation so that
+/// nothing the user types can be misread as `tsquery` syntax.
+pub fn terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// A `tsquery` matching todos containing every term. Terms match as prefixes,
+/// so that "gro" already finds "groceries" while it's still being typed.
+pub fn prefix_tsquery(query: &str) -> Option<String> {
+    let terms = terms(query);
+
+    (!terms.is_empty()).then(|| {
+        terms
+            .iter()
+            .map(|term| format!("{}:*", term))
+            .collect::<Vec<_>>()
+            .join(" & ")
+    })
+}
+
+/// Options for `ts_headline`.
+pub fn headline_options() -> String {
+    format!(
+        "StartSel={}, StopSel={}, MaxWords=20, MinWords=8, MaxFragments=2, FragmentDelimiter=\" … \"",
+        START_SELECTION, STOP_SELECTION
+    )
+}
+
+/// Splits a headline from `ts_headline` into fragments.
+pub fn parse_headline(headline: &str) -> Vec<Fragment> {
+    let mut fragments = vec![];
+    let mut text = String::new();
+    let mut highlighted = false;
+
+    for c in headline.chars() {
+        let toggles = match c {
+            START_SELECTION => !highlighted,
+            STOP_SELECTION => highlighted,
+            _ => {
+                text.push(c);
+                false
+            }
+        };
+
+        if toggles {
+            if !text.is_empty() {
+                fragments.push(Fragment {
+                    text: std::mem::take(&mut text),
+                    highlighted,
+                });
+            }
+            highlighted = !highlighted;
+        }
+    }
+
+    if !text.is_empty() {
+        fragments.push(Fragment { text, highlighted });
+    }
+
+    fragments
+}
+
+/// Highlights every word of `content` that starts with one of `terms`, for
+/// repositories that can't ask Postgres to.
+pub fn highlight(content: &str, terms: &[String]) -> Vec<Fragment> {
+    let mut headline = String::new();
+    let mut word = String::new();
+
+    let flush = |word: &mut String, headline: &mut String| {
+        let lowercase = word.to_lowercase();
+        if !word.is_empty()
+            && terms
+                .iter()
+                .any(|term| lowercase.starts_with(term.as_str()))
+        {
+            headline.push(START_SELECTION);
+            headline.push_str(word);
+            headline.push(STOP_SELECTION);
+        } else {
+            headline.push_str(word);
+        }
+        word.clear();
+    };
+
+    for c in content.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut headline);
+            headline.push(c);
+        }
+    }
+    flush(&mut word, &mut headline);
+
+    parse_headline(&headline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(text: &str, highlighted: bool) -> Fragment {
+        Fragment {
+            text: text.to_owned(),
+            highlighted,
+        }
+    }
+
+    #[test]
+    fn tsquery_prefix_matches_every_term() {
+        assert_eq!(prefix_tsquery("Buy gro").as_deref(), Some("buy:* & gro:*"));
+    }
+
+    #[test]
+    fn tsquery_ignores_operators() {
+        assert_eq!(
+            prefix_tsquery("milk & !eggs | (bread):*").as_deref(),
+            Some("milk:* & eggs:* & bread:*")
+        );
+        assert_eq!(prefix_tsquery("  !& "), None);
+    }
+
+    #[test]
+    fn headlines_are_split_into_fragments() {
+        let headline = format!(
+            "buy {}milk{} and {}eggs{}",
+            START_SELECTION, STOP_SELECTION, START_SELECTION, STOP_SELECTION
+        );
+
+        assert_eq!(
+            parse_headline(&headline),
+            [
+                fragment("buy ", false),
+                fragment("milk", true),
+                fragment(" and ", false),
+                fragment("eggs", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_matches_word_prefixes() {
+        let terms = terms("GRO");
+
+        assert_eq!(
+            highlight("Buy groceries, not grog-free <b>", &terms),
+            [
+                fragment("Buy ", false),
+                fragment("groceries", true),
+                fragment(", not ", false),
+                fragment("grog", true),
+                fragment("-free <b>", false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Shorthand typed into a todo's content: `#tag`s, a `!1`–`!4` (or `!high`)
+//! priority and an `@list`.
+//!
+//! New todos can also have dates and repeats written in words; see
+//! [`super::quick_add`].
+
+use time::OffsetDateTime;
+
+use super::{lists, priority::Priority, recurrence::Recurrence, tags, todo};
+
+/// A todo's content with its shorthand split out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Shorthand {
+    /// Content without shorthand comes back untouched; otherwise the remaining
+    /// words are joined by single spaces.
+    pub content: String,
+    /// Tag names in the order they first appear.
+    pub tags: Vec<String>,
+    /// The last priority given, if any.
+    pub priority: Option<Priority>,
+    /// The last list named, if any.
+    pub list: Option<String>,
+    /// Only set by [`super::quick_add::parse`].
+    pub due_at: Option<OffsetDateTime>,
+    /// Only set by [`super::quick_add::parse`].
+    pub recurrence: Option<Recurrence>,
+}
+
+impl Shorthand {
+    /// Whether anything besides the content was found.
+    pub fn is_recognized(&self) -> bool {
+        !self.tags.is_empty()
+            || self.priority.is_some()
+            || self.list.is_some()
+            || self.due_at.is_some()
+            || self.recurrence.is_some()
+    }
+
+    /// `due_at` as shown next to a todo.
+    pub fn due_label(&self) -> Option<String> {
+        self.due_at.and_then(todo::due_label)
+    }
+}
+
+pub fn parse(content: &str) -> Shorthand {
+    let mut parsed = Shorthand::default();
+    let mut words = vec![];
+
+    for word in content.split_whitespace() {
+        if let Some(priority) = Priority::from_shorthand(word) {
+            parsed.priority = Some(priority);
+        } else if let Some(tag) = word
+            .starts_with('#')
+            .then(|| tags::normalize(word))
+            .flatten()
+        {
+            if !parsed.tags.contains(&tag) {
+                parsed.tags.push(tag);
+            }
+        } else if let Some(list) = word
+            .starts_with('@')
+            .then(|| lists::normalize(word))
+            .flatten()
+        {
+            parsed.list = Some(list);
+        } else {
+            words.push(word);
+        }
+    }
+
+    parsed.content = if !parsed.is_recognized() {
+        content.to_owned()
+    } else {
+        words.join(" ")
+    };
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags_of(content: &str) -> (String, Vec<String>) {
+        let parsed = parse(content);
+        (parsed.content, parsed.tags)
+    }
+
+    #[test]
+    fn tags_are_split_out_of_content() {
+        assert_eq!(
+            tags_of("Buy milk #Errands #home #errands"),
+            ("Buy milk".to_owned(), vec!["errands".into(), "home".into()])
+        );
+        assert_eq!(
+            tags_of("Call  mum re: #1 priority"),
+            ("Call mum re: priority".to_owned(), vec!["1".into()])
+        );
+    }
+
+    #[test]
+    fn content_without_shorthand_is_untouched() {
+        assert_eq!(
+            tags_of("  C# and F#  "),
+            ("  C# and F#  ".to_owned(), vec![])
+        );
+        assert_eq!(tags_of("# not a tag"), ("# not a tag".to_owned(), vec![]));
+        assert_eq!(tags_of("#no.dots"), ("#no.dots".to_owned(), vec![]));
+        assert_eq!(parse("Wow!1 !5 !!").priority, None);
+    }
+
+    #[test]
+    fn the_last_priority_and_list_win() {
+        assert_eq!(
+            parse("File taxes !2 @home #admin !high @Work"),
+            Shorthand {
+                content: "File taxes".to_owned(),
+                tags: vec!["admin".into()],
+                priority: Some(Priority::High),
+                list: Some("work".to_owned()),
+                ..Shorthand::default()
+            }
+        );
+        assert_eq!(parse("email me @ work").list, None);
+    }
+}
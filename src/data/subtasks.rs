@@ -0,0 +1,153 @@
+//! Todos nested under other todos.
+//!
+//! Any todo can have subtasks, and those can have subtasks of their own. The
+//! list pages through top-level todos only, then fetches everything nested
+//! under the page in one go and hands it to the templates as [`Subtasks`].
+
+use std::{collections::HashMap, fmt};
+
+use uuid::Uuid;
+
+use super::todo::Todo;
+
+/// The subtasks under some todos, looked up by parent.
+#[derive(Clone, Debug, Default)]
+pub struct Subtasks {
+    children: HashMap<Uuid, Vec<Todo>>,
+}
+
+impl Subtasks {
+    /// Groups todos by their parent, each group in manual order.
+    pub fn new(todos: Vec<Todo>) -> Self {
+        let mut children: HashMap<Uuid, Vec<Todo>> = HashMap::new();
+        for todo in todos {
+            if let Some(parent_id) = todo.parent_id {
+                children.entry(parent_id).or_default().push(todo);
+            }
+        }
+
+        for todos in children.values_mut() {
+            todos.sort_by_key(|todo| (todo.position, todo.todo_id));
+        }
+
+        Self { children }
+    }
+
+    /// The todo's direct subtasks.
+    pub fn of(&self, todo: &Todo) -> &[Todo] {
+        self.children.get(&todo.todo_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// How many of the todo's direct subtasks are done, if it has any.
+    pub fn progress(&self, todo: &Todo) -> Option<Progress> {
+        let children = self.of(todo);
+
+        (!children.is_empty()).then(|| Progress {
+            done: children.iter().filter(|todo| todo.done).count(),
+            total: children.len(),
+        })
+    }
+}
+
+/// Shown on a todo with subtasks as "3/5".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn is_complete(&self) -> bool {
+        self.done == self.total
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.done, self.total)
+    }
+}
+
+/// The ids of every todo nested under `parents`, at any depth.
+pub fn descendants(todos: &[Todo], parents: &[Uuid]) -> Vec<Uuid> {
+    let mut found = vec![];
+    let mut level = parents.to_vec();
+
+    while !level.is_empty() {
+        level = todos
+            .iter()
+            .filter(|todo| todo.parent_id.is_some_and(|parent| level.contains(&parent)))
+            .map(|todo| todo.todo_id)
+            .collect();
+        found.extend(&level);
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::Json;
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::data::priority::Priority;
+
+    fn todo(parent_id: Option<Uuid>, position: i64, done: bool) -> Todo {
+        Todo {
+            todo_id: Uuid::new_v4(),
+            content: position.to_string(),
+            done,
+            user_id: Uuid::nil(),
+            created_at: OffsetDateTime::now_utc(),
+            due_at: None,
+            priority: Priority::None,
+            recurrence: None,
+            position,
+            parent_id,
+            tags: Json(vec![]),
+            list_id: None,
+            list: None,
+            deleted_at: None,
+            completed_at: None,
+            archived_at: None,
+            version: 1,
+            updated_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn subtasks_are_grouped_in_order() {
+        let parent = todo(None, 0, false);
+        let (a, b, c) = (
+            todo(Some(parent.todo_id), 3, true),
+            todo(Some(parent.todo_id), 1, false),
+            todo(Some(parent.todo_id), 2, true),
+        );
+        let subtasks = Subtasks::new(vec![a.clone(), b.clone(), c.clone()]);
+
+        let ids: Vec<_> = subtasks.of(&parent).iter().map(|t| t.todo_id).collect();
+        assert_eq!(ids, [b.todo_id, c.todo_id, a.todo_id]);
+        assert!(subtasks.of(&a).is_empty());
+
+        let progress = subtasks.progress(&parent).unwrap();
+        assert_eq!(progress.to_string(), "2/3");
+        assert!(!progress.is_complete());
+        assert_eq!(subtasks.progress(&a), None);
+    }
+
+    #[test]
+    fn descendants_include_every_level() {
+        let root = todo(None, 1, false);
+        let child = todo(Some(root.todo_id), 2, false);
+        let grandchild = todo(Some(child.todo_id), 3, false);
+        let unrelated = todo(None, 4, false);
+        let todos = [root.clone(), child.clone(), grandchild.clone(), unrelated];
+
+        assert_eq!(
+            descendants(&todos, &[root.todo_id]),
+            [child.todo_id, grandchild.todo_id]
+        );
+        assert!(descendants(&todos, &[grandchild.todo_id]).is_empty());
+    }
+}
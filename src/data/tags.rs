@@ -0,0 +1,275 @@
+//! User-defined tags on todos.
+//!
+//! Tags are written inline as `#name` in a todo's content (see
+//! [`super::shorthand`]), and created the first time they're used with a color
+//! picked from [`PALETTE`]. Each todo is loaded with its tags, by name.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::TagRepository;
+
+/// The longest name a tag can have.
+pub const MAX_NAME_LENGTH: usize = 50;
+
+/// Colors new tags get, picked by name so a tag keeps its color if it's deleted
+/// and used again.
+pub const PALETTE: [&str; 8] = [
+    "#ef4444", "#f97316", "#eab308", "#22c55e", "#14b8a6", "#3b82f6", "#8b5cf6", "#ec4899",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub tag_id: Uuid,
+    pub name: String,
+    /// `#rrggbb`.
+    pub color: String,
+}
+
+/// A tag and how many todos it's on.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TagSummary {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub todos: i64,
+}
+
+/// The tag name for `name`, which may start with a `#`, if it's a valid one.
+pub fn normalize(name: &str) -> Option<String> {
+    let name = name.strip_prefix('#').unwrap_or(name);
+    let valid = !name.is_empty()
+        && name.chars().count() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+
+    valid.then(|| name.to_lowercase())
+}
+
+/// The color a new tag called `name` gets.
+pub fn default_color(name: &str) -> &'static str {
+    // FNV-1a, which unlike `DefaultHasher` is the same from one build to the next.
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+
+    PALETTE[(hash % PALETTE.len() as u64) as usize]
+}
+
+/// Replaces a todo's tags with the ones named, creating any the user doesn't
+/// have yet.
+#[tracing::instrument(skip(conn))]
+pub async fn set_todo_tags(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    todo_id: Uuid,
+    names: &[String],
+) -> Result<(), Error> {
+    let colors: Vec<_> = names.iter().map(|name| default_color(name)).collect();
+
+    sqlx::query!(
+        "
+            insert into tags(user_id, name, color)
+            select $1, name, color
+            from unnest($2::text[], $3::text[]) as new(name, color)
+            on conflict (user_id, name) do nothing
+        ",
+        user_id,
+        names,
+        &colors as &[&str],
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "
+            delete from todo_tags
+            where todo_id = $1 and tag_id not in (
+                select tag_id from tags where user_id = $2 and name = any($3)
+            )
+        ",
+        todo_id,
+        user_id,
+        names,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "
+            insert into todo_tags(todo_id, tag_id)
+            select $1, tag_id
+            from tags
+            where user_id = $2 and name = any($3)
+            on conflict do nothing
+        ",
+        todo_id,
+        user_id,
+        names,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn list_tags(db: &PgPool, user_id: Uuid) -> Result<Vec<TagSummary>, Error> {
+    let rows = sqlx::query!(
+        r#"
+            select tags.tag_id, tags.name, tags.color, count(todos.todo_id) as "todos!"
+            from tags
+            left join todo_tags on todo_tags.tag_id = tags.tag_id
+            left join todos on todos.todo_id = todo_tags.todo_id and todos.deleted_at is null
+            where tags.user_id = $1
+            group by tags.tag_id
+            order by tags.name
+        "#,
+        user_id,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| TagSummary {
+            tag: Tag {
+                tag_id: row.tag_id,
+                name: row.name,
+                color: row.color,
+            },
+            todos: row.todos,
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn update_tag(
+    db: &PgPool,
+    user_id: Uuid,
+    tag_id: Uuid,
+    name: &str,
+    color: &str,
+) -> Result<Tag, Error> {
+    sqlx::query_as!(
+        Tag,
+        "
+            update tags
+            set name = $3, color = $4
+            where user_id = $1 and tag_id = $2
+            returning tag_id, name, color
+        ",
+        user_id,
+        tag_id,
+        name,
+        color,
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Moves every todo tagged `from` over to `into`, then deletes `from`.
+#[tracing::instrument(skip(db))]
+pub async fn merge_tags(db: &PgPool, user_id: Uuid, from: Uuid, into: Uuid) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    let found = sqlx::query_scalar!(
+        r#"select count(*) as "count!" from tags where user_id = $1 and tag_id = any($2)"#,
+        user_id,
+        &[from, into],
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if found != 2 {
+        tx.rollback().await?;
+        return Err(Error::RowNotFound);
+    }
+
+    sqlx::query!(
+        "
+            insert into todo_tags(todo_id, tag_id)
+            select todo_id, $2
+            from todo_tags
+            where tag_id = $1
+            on conflict do nothing
+        ",
+        from,
+        into,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!("delete from tags where tag_id = $1", from)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn delete_tag(db: &PgPool, user_id: Uuid, tag_id: Uuid) -> Result<(), Error> {
+    sqlx::query!(
+        "
+            delete from tags
+            where user_id = $1 and tag_id = $2
+        ",
+        user_id,
+        tag_id,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl TagRepository for PgPool {
+    async fn list_tags(&self, user_id: Uuid) -> Result<Vec<TagSummary>, Error> {
+        list_tags(self, user_id).await
+    }
+
+    async fn update_tag(
+        &self,
+        user_id: Uuid,
+        tag_id: Uuid,
+        name: &str,
+        color: &str,
+    ) -> Result<Tag, Error> {
+        update_tag(self, user_id, tag_id, name, color).await
+    }
+
+    async fn merge_tags(&self, user_id: Uuid, from: Uuid, into: Uuid) -> Result<(), Error> {
+        merge_tags(self, user_id, from, into).await
+    }
+
+    async fn delete_tag(&self, user_id: Uuid, tag_id: Uuid) -> Result<(), Error> {
+        delete_tag(self, user_id, tag_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_normalized() {
+        assert_eq!(normalize("#Work"), Some("work".to_owned()));
+        assert_eq!(
+            normalize("side-project_2"),
+            Some("side-project_2".to_owned())
+        );
+        assert_eq!(normalize("#"), None);
+        assert_eq!(normalize("two words"), None);
+        assert_eq!(normalize(&"x".repeat(MAX_NAME_LENGTH + 1)), None);
+    }
+
+    #[test]
+    fn default_colors_are_stable() {
+        assert_eq!(default_color("work"), default_color("work"));
+        assert!(PALETTE.contains(&default_color("")));
+    }
+}
@@ -1,10 +1,29 @@
+use async_trait::async_trait;
 use serde::Serialize;
-use sqlx::{Error, PgPool};
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use serde_with::DisplayFromStr;
+use sqlx::{types::Json, Error, FromRow, PgConnection, PgExecutor, PgPool, Postgres, QueryBuilder};
+use time::{
+    format_description::{well_known::Rfc3339, FormatItem},
+    macros::format_description,
+    OffsetDateTime,
+};
 use uuid::Uuid;
 
+use super::{
+    bulk::{self, BulkAction, BulkResult},
+    history::{self, TodoChange},
+    listing::{Page, SortKey, TimeRange, TodoQuery, TodoSort},
+    lists,
+    ordering::{self, Placement},
+    priority::Priority,
+    recurrence::Recurrence,
+    search::{self, TodoMatch},
+    tags::{self, Tag},
+    TodoRepository,
+};
+
 #[serde_with::serde_as]
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct Todo {
     pub todo_id: Uuid,
@@ -14,49 +33,384 @@ pub struct Todo {
     // `OffsetDateTime`'s default serialization format is not standard.
     #[serde_as(as = "Rfc3339")]
     pub created_at: OffsetDateTime,
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub due_at: Option<OffsetDateTime>,
+    pub priority: Priority,
+    /// As an `RRULE`; see [`super::recurrence`].
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub recurrence: Option<Recurrence>,
+    /// Where the user dragged the todo to; see [`super::ordering`].
+    pub position: i64,
+    /// The todo this is a subtask of; see [`super::subtasks`].
+    pub parent_id: Option<Uuid>,
+    /// By name; see [`super::tags`].
+    pub tags: Json<Vec<Tag>>,
+    /// See [`super::lists`].
+    pub list_id: Option<Uuid>,
+    /// The name of the list.
+    pub list: Option<String>,
+    /// When the todo was moved to the trash; see [`super::trash`].
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub deleted_at: Option<OffsetDateTime>,
+    /// When the todo was done, if it is.
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub completed_at: Option<OffsetDateTime>,
+    /// When the todo was put in the archive; see [`super::archive`].
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub archived_at: Option<OffsetDateTime>,
+    /// Bumped by every edit, so stale edits can be refused; see
+    /// [`update_todo_by_id`].
+    pub version: i64,
+    /// When the todo last changed in any way, tags included.
+    #[serde_as(as = "Rfc3339")]
+    pub updated_at: OffsetDateTime,
 }
 
-pub async fn create_todo(db: &PgPool, user_id: Uuid, content: String) -> Result<Todo, Error> {
-    sqlx::query_as!(
-        Todo,
-        r#"
-            with inserted_todo as (
-                insert into todos(user_id, content)
-                values($1, $2)
-                returning todo_id, content, done, user_id, created_at
-            ) 
-            select todo_id, content, done, user_id, created_at from inserted_todo
-        "#,
+/// What a todo is created with.
+#[derive(Clone, Debug, Default)]
+pub struct NewTodo {
+    /// The id a client made up for it, say while offline; see
+    /// [`super::changes`]. Otherwise one is generated.
+    pub todo_id: Option<Uuid>,
+    pub content: String,
+    pub due_at: Option<OffsetDateTime>,
+    pub priority: Priority,
+    pub recurrence: Option<Recurrence>,
+    /// Makes the todo a subtask of another of the user's todos.
+    pub parent_id: Option<Uuid>,
+    /// Tag names, created as needed.
+    pub tags: Vec<String>,
+    /// A list name, created as needed.
+    pub list: Option<String>,
+}
+
+/// What editing a todo changes.
+#[derive(Clone, Debug, Default)]
+pub struct TodoChanges {
+    pub content: String,
+    pub due_at: Option<OffsetDateTime>,
+    pub priority: Priority,
+    /// Replaces the series' rule, or stops it when `None`.
+    pub recurrence: Option<Recurrence>,
+    /// Replaces the todo's tags.
+    pub tags: Vec<String>,
+    /// Moves the todo to this list, or takes it off its list.
+    pub list: Option<String>,
+    /// Marks the todo done, or not, if it isn't already; see
+    /// [`toggle_todo_by_id`].
+    pub done: Option<bool>,
+    /// Only makes the changes if the todo is still at this version.
+    pub version: Option<i64>,
+}
+
+impl Todo {
+    /// `due_at` as shown next to the todo.
+    pub fn due_label(&self) -> Option<String> {
+        self.due_at.and_then(due_label)
+    }
+
+    /// `deleted_at` as shown in the trash.
+    pub fn deleted_label(&self) -> Option<String> {
+        self.deleted_at.and_then(due_label)
+    }
+
+    /// `completed_at` as shown in the archive.
+    pub fn completed_label(&self) -> Option<String> {
+        self.completed_at.and_then(due_label)
+    }
+
+    /// The content with the tags and list written back in, for editing.
+    pub fn content_with_shorthand(&self) -> String {
+        let content = self.tags.iter().fold(self.content.clone(), |content, tag| {
+            format!("{} #{}", content, tag.name)
+        });
+
+        match &self.list {
+            Some(list) => format!("{} @{}", content, list),
+            None => content,
+        }
+    }
+
+    /// `recurrence` for the edit form, empty if the todo doesn't repeat.
+    pub fn recurrence_input(&self) -> String {
+        self.recurrence
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default()
+    }
+
+    /// The version as an HTTP entity tag, for `ETag` and `If-Match`.
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
+
+    /// `due_at` in the format of a `datetime-local` input.
+    pub fn due_input(&self) -> String {
+        self.due_at
+            .and_then(|due_at| due_at.format(DATETIME_LOCAL_FORMAT).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// A due date, or other time, as shown next to a todo.
+pub fn due_label(due_at: OffsetDateTime) -> Option<String> {
+    due_at.format(DUE_LABEL_FORMAT).ok()
+}
+
+const DUE_LABEL_FORMAT: &[FormatItem] =
+    format_description!("[month repr:short] [day padding:none], [hour]:[minute]");
+
+pub const DATETIME_LOCAL_FORMAT: &[FormatItem] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]");
+
+/// Errors with `RowNotFound` if the parent isn't one of the user's todos, or
+/// if there's a todo with the id asked for already.
+#[tracing::instrument(skip(db, todo))]
+pub async fn create_todo(db: &PgPool, user_id: Uuid, todo: NewTodo) -> Result<Todo, Error> {
+    let mut tx = db.begin().await?;
+
+    match insert_todo(&mut tx, user_id, todo).await {
+        Ok(todo) => {
+            tx.commit().await?;
+            Ok(todo)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn insert_todo(conn: &mut PgConnection, user_id: Uuid, todo: NewTodo) -> Result<Todo, Error> {
+    let todo_id = sqlx::query_scalar!(
+        "
+            insert into todos(
+                todo_id, user_id, content, due_at, parent_id, priority, recurrence, position
+            )
+            select coalesce($8, gen_random_uuid()), $1, $2, $3, $4, $5, $6, (
+                select coalesce(max(position), 0) + $7
+                from todos
+                where user_id = $1
+            )
+            where $4::uuid is null
+                or exists(
+                    select from todos where user_id = $1 and todo_id = $4 and deleted_at is null
+                )
+            on conflict (todo_id) do nothing
+            returning todo_id
+        ",
         user_id,
-        content,
+        todo.content,
+        todo.due_at,
+        todo.parent_id,
+        todo.priority as Priority,
+        todo.recurrence as Option<Recurrence>,
+        ordering::GAP,
+        todo.todo_id,
     )
-    .fetch_one(db)
-    .await
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let created = TodoChange::Created {
+        content: todo.content,
+    };
+    history::record(&mut *conn, user_id, todo_id, &created).await?;
+    tags::set_todo_tags(conn, user_id, todo_id, &todo.tags).await?;
+    lists::set_todo_list(conn, user_id, todo_id, todo.list.as_deref()).await?;
+
+    get_todo_by_id(conn, user_id, todo_id).await
 }
 
+#[tracing::instrument(skip(db))]
 pub async fn get_todos(db: &PgPool, user_id: Uuid) -> Result<Vec<Todo>, Error> {
     sqlx::query_as!(
         Todo,
-        "
-            select todo_id, content, done, user_id, created_at
+        r#"
+            select todo_id, content, done, user_id, created_at, due_at,
+                priority as "priority: Priority", recurrence as "recurrence: Recurrence",
+                position, parent_id,
+                tags_of_todo(todo_id) as "tags!: Json<Vec<Tag>>", list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
             from todos
-            where user_id = $1
+            where user_id = $1 and deleted_at is null
             order by created_at
-        ",
+        "#,
         user_id,
     )
     .fetch_all(db)
     .await
 }
 
-pub async fn get_todo_by_id(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Result<Todo, Error> {
+/// One page of the todos matching `query`.
+#[tracing::instrument(skip(db))]
+pub async fn list_todos(db: &PgPool, user_id: Uuid, query: &TodoQuery) -> Result<Page, Error> {
+    let column = match query.sort {
+        TodoSort::Created => "created_at",
+        TodoSort::Due => "due_at",
+        TodoSort::Manual => "position",
+        TodoSort::Priority => "priority",
+    };
+    let (direction, past) = if query.descending {
+        ("desc", "<")
+    } else {
+        ("asc", ">")
+    };
+
+    let mut sql = QueryBuilder::new(
+        "select todo_id, content, done, user_id, created_at, due_at, priority, recurrence, position, parent_id, tags_of_todo(todo_id) as tags, list_id, list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at from todos where deleted_at is null and archived_at is null and user_id = ",
+    );
+    sql.push_bind(user_id);
+
+    push_filters(&mut sql, query);
+
+    // Keyset condition for `order by <column> nulls last, todo_id`.
+    if let Some(after) = &query.after {
+        match after.key {
+            Some(key) => {
+                sql.push(format_args!(" and ({} {} ", column, past));
+                push_key(&mut sql, key);
+                sql.push(format_args!(" or ({} = ", column));
+                push_key(&mut sql, key);
+                sql.push(format_args!(" and todo_id {} ", past))
+                    .push_bind(after.todo_id)
+                    .push(format_args!(") or {} is null)", column));
+            }
+            None => {
+                sql.push(format_args!(
+                    " and {} is null and todo_id {} ",
+                    column, past
+                ))
+                .push_bind(after.todo_id);
+            }
+        }
+    }
+
+    sql.push(format_args!(
+        " order by {} {} nulls last, todo_id {} limit ",
+        column, direction, direction
+    ))
+    .push_bind(query.limit + 1);
+
+    let todos = sql.build_query_as::<Todo>().fetch_all(db).await?;
+
+    Ok(Page::from_overfetched(query, todos))
+}
+
+fn push_key(sql: &mut QueryBuilder<'_, Postgres>, key: SortKey) {
+    match key {
+        SortKey::Time(time) => sql.push_bind(time),
+        SortKey::Position(position) => sql.push_bind(position),
+        SortKey::Priority(priority) => sql.push_bind(priority),
+    };
+}
+
+fn push_filters(sql: &mut QueryBuilder<'_, Postgres>, query: &TodoQuery) {
+    if query.top_level {
+        sql.push(" and parent_id is null");
+    }
+    if let Some(done) = query.done {
+        sql.push(" and done = ").push_bind(done);
+    }
+    if let Some(tag) = &query.tag {
+        sql.push(
+            " and exists(select from todo_tags join tags on tags.tag_id = todo_tags.tag_id \
+             where todo_tags.todo_id = todos.todo_id and tags.name = ",
+        )
+        .push_bind(tag.clone())
+        .push(")");
+    }
+    if let Some(list) = &query.list {
+        sql.push(
+            " and exists(select from lists where lists.list_id = todos.list_id and lists.name = ",
+        )
+        .push_bind(list.clone())
+        .push(")");
+    }
+    if let Some(priority) = query.min_priority {
+        sql.push(" and priority >= ").push_bind(priority);
+    }
+    push_range(sql, "created_at", &query.created);
+    push_range(sql, "due_at", &query.due);
+}
+
+fn push_range(sql: &mut QueryBuilder<'_, Postgres>, column: &str, range: &TimeRange) {
+    if let Some(from) = range.from {
+        sql.push(format_args!(" and {} >= ", column))
+            .push_bind(from);
+    }
+    if let Some(until) = range.until {
+        sql.push(format_args!(" and {} < ", column))
+            .push_bind(until);
+    }
+}
+
+/// Todos matching the search `text` and the filters in `filter`, best
+/// matches first.
+#[tracing::instrument(skip(db))]
+pub async fn search_todos(
+    db: &PgPool,
+    user_id: Uuid,
+    text: &str,
+    filter: &TodoQuery,
+) -> Result<Vec<TodoMatch>, Error> {
+    #[derive(FromRow)]
+    struct Row {
+        #[sqlx(flatten)]
+        todo: Todo,
+        rank: f32,
+        headline: String,
+    }
+
+    let Some(tsquery) = search::prefix_tsquery(text) else {
+        return Ok(vec![]);
+    };
+
+    let mut sql = QueryBuilder::new(
+        "
+            select todo_id, content, done, user_id, created_at, due_at, priority, recurrence,
+                position, parent_id, tags_of_todo(todo_id) as tags, list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at,
+                ts_rank(search, query) as rank,
+                ts_headline('english', content, query, ",
+    );
+    sql.push_bind(search::headline_options())
+        .push(") as headline from todos, to_tsquery('english', ")
+        .push_bind(tsquery)
+        .push(") query where search @@ query and deleted_at is null and archived_at is null and user_id = ")
+        .push_bind(user_id);
+    push_filters(&mut sql, filter);
+    sql.push(" order by rank desc, created_at limit ")
+        .push_bind(search::MAX_MATCHES);
+
+    let rows = sql.build_query_as::<Row>().fetch_all(db).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| TodoMatch {
+            todo: row.todo,
+            rank: row.rank,
+            snippet: search::parse_headline(&row.headline),
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn get_todo_by_id(
+    db: impl PgExecutor<'_>,
+    user_id: Uuid,
+    todo_id: Uuid,
+) -> Result<Todo, Error> {
     sqlx::query_as!(
         Todo,
-        "
-            select todo_id, content, done, user_id, created_at
+        r#"
+            select todo_id, content, done, user_id, created_at, due_at,
+                priority as "priority: Priority", recurrence as "recurrence: Recurrence",
+                position, parent_id,
+                tags_of_todo(todo_id) as "tags!: Json<Vec<Tag>>", list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
             from todos
-            where user_id = $1 and todo_id = $2
-        ",
+            where user_id = $1 and todo_id = $2 and deleted_at is null
+        "#,
         user_id,
         todo_id,
     )
@@ -64,14 +418,39 @@ pub async fn get_todo_by_id(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Result
     .await
 }
 
-pub async fn delete_todo_by_id(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+/// Moves a todo to the trash along with everything nested under it, all with
+/// the same `deleted_at` so that they're restored together. Only the todo
+/// itself has the deletion in its history.
+#[tracing::instrument(skip(db))]
+pub async fn delete_todo_by_id(
+    db: impl PgExecutor<'_>,
+    user_id: Uuid,
+    todo_id: Uuid,
+) -> Result<(), Error> {
     sqlx::query!(
         "
-            delete from todos
-            where user_id = $1 and todo_id = $2
+            with recursive subtree as (
+                select todo_id
+                from todos
+                where user_id = $1 and todo_id = $2 and deleted_at is null
+                union all
+                select todos.todo_id
+                from todos
+                join subtree on todos.parent_id = subtree.todo_id
+                where todos.deleted_at is null
+            ),
+            deleted as (
+                update todos
+                set deleted_at = now()
+                where todo_id in (select todo_id from subtree)
+                returning todo_id
+            )
+            insert into todo_events(todo_id, actor_id, change)
+            select todo_id, $1, $3 from deleted where todo_id = $2
         ",
         user_id,
         todo_id,
+        Json(TodoChange::Deleted) as _,
     )
     .execute(db)
     .await?;
@@ -79,15 +458,191 @@ pub async fn delete_todo_by_id(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Res
     Ok(())
 }
 
-pub async fn toggle_todo_by_id(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
-    sqlx::query!(
-        "
+/// Marks a todo done, or not done. Marking a recurring todo done schedules its
+/// next occurrence, whose id is returned; see [`super::recurrence`].
+#[tracing::instrument(skip(db))]
+pub async fn toggle_todo_by_id(
+    db: &PgPool,
+    user_id: Uuid,
+    todo_id: Uuid,
+    version: Option<i64>,
+) -> Result<Option<Uuid>, Error> {
+    let mut tx = db.begin().await?;
+
+    match toggle(&mut tx, user_id, todo_id, version).await {
+        Ok(next) => {
+            tx.commit().await?;
+            Ok(next)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+pub(super) async fn toggle(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    todo_id: Uuid,
+    version: Option<i64>,
+) -> Result<Option<Uuid>, Error> {
+    let toggled = sqlx::query!(
+        r#"
             update todos
-            set done = not done
+            set done = not done, version = version + 1,
+                completed_at = case when done then null else now() end,
+                -- Only done todos are kept in the archive.
+                archived_at = case when done then null else archived_at end
+            where user_id = $1 and todo_id = $2 and deleted_at is null
+                and ($3::bigint is null or version = $3)
+            returning content, done, due_at, recurrence as "recurrence: Recurrence"
+        "#,
+        user_id,
+        todo_id,
+        version,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(toggled) = toggled else {
+        return match version {
+            Some(_) => Err(Error::RowNotFound),
+            None => Ok(None),
+        };
+    };
+    let change = TodoChange::Toggled { done: toggled.done };
+    history::record(&mut *conn, user_id, todo_id, &change).await?;
+
+    let Some(next) = toggled
+        .recurrence
+        .filter(|_| toggled.done)
+        .and_then(|recurrence| recurrence.next(toggled.due_at, OffsetDateTime::now_utc()))
+    else {
+        return Ok(None);
+    };
+
+    let next_id = sqlx::query_scalar!(
+        "
+            insert into todos(
+                user_id, content, due_at, parent_id, priority, recurrence, list_id, position
+            )
+            select user_id, content, $3, parent_id, priority, $4, list_id, (
+                select coalesce(max(position), 0) + $5
+                from todos
+                where user_id = $1
+            )
+            from todos
             where user_id = $1 and todo_id = $2
+            returning todo_id
         ",
         user_id,
         todo_id,
+        next.due_at,
+        next.recurrence as Option<Recurrence>,
+        ordering::GAP,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "
+            insert into todo_tags(todo_id, tag_id)
+            select $2, tag_id
+            from todo_tags
+            where todo_id = $1
+        ",
+        todo_id,
+        next_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let created = TodoChange::Created {
+        content: toggled.content,
+    };
+    history::record(&mut *conn, user_id, next_id, &created).await?;
+
+    // The series lives on in the next occurrence, so undoing this one doesn't
+    // schedule another.
+    sqlx::query!(
+        "update todos set recurrence = null where todo_id = $1",
+        todo_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(Some(next_id))
+}
+
+/// Everything nested under the given todos, at any depth.
+#[tracing::instrument(skip(db))]
+pub async fn get_subtasks(
+    db: &PgPool,
+    user_id: Uuid,
+    todo_ids: &[Uuid],
+) -> Result<Vec<Todo>, Error> {
+    sqlx::query_as(
+        "
+            with recursive subtasks as (
+                select todo_id, content, done, user_id, created_at, due_at, priority, recurrence,
+                    position, parent_id, list_id, deleted_at, completed_at, archived_at, version, updated_at
+                from todos
+                where user_id = $1 and parent_id = any($2) and deleted_at is null
+                union all
+                select todos.todo_id, todos.content, todos.done, todos.user_id,
+                    todos.created_at, todos.due_at, todos.priority, todos.recurrence,
+                    todos.position, todos.parent_id, todos.list_id, todos.deleted_at,
+                    todos.completed_at, todos.archived_at, todos.version, todos.updated_at
+                from todos
+                join subtasks on todos.parent_id = subtasks.todo_id
+                where todos.deleted_at is null
+            )
+            select todo_id, content, done, user_id, created_at, due_at, priority, recurrence,
+                position, parent_id, tags_of_todo(todo_id) as tags, list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
+            from subtasks
+            order by position, todo_id
+        ",
+    )
+    .bind(user_id)
+    .bind(todo_ids)
+    .fetch_all(db)
+    .await
+}
+
+/// Marks a todo done along with everything nested under it. Each todo that
+/// wasn't done already has it in its history.
+#[tracing::instrument(skip(db))]
+pub async fn complete_todo_with_subtasks(
+    db: &PgPool,
+    user_id: Uuid,
+    todo_id: Uuid,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "
+            with recursive subtree as (
+                select todo_id
+                from todos
+                where user_id = $1 and todo_id = $2 and deleted_at is null
+                union all
+                select todos.todo_id
+                from todos
+                join subtree on todos.parent_id = subtree.todo_id
+                where todos.deleted_at is null
+            ),
+            completed as (
+                update todos
+                set done = true, completed_at = now(), version = version + 1
+                where todo_id in (select todo_id from subtree) and not done
+                returning todo_id
+            )
+            insert into todo_events(todo_id, actor_id, change)
+            select todo_id, $1, $3 from completed
+        ",
+        user_id,
+        todo_id,
+        Json(TodoChange::Toggled { done: true }) as _,
     )
     .execute(db)
     .await?;
@@ -95,24 +650,281 @@ pub async fn toggle_todo_by_id(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Res
     Ok(())
 }
 
-pub async fn update_todo_by_id(
+/// Moves a todo next to another in the same list with the same parent; see
+/// [`ordering`].
+///
+/// Moves within the same list are serialized by an advisory lock, so that two
+/// clients reordering at once can't both claim the same gap or renumber the
+/// list from under each other.
+#[tracing::instrument(skip(db))]
+pub async fn move_todo(
     db: &PgPool,
     user_id: Uuid,
     todo_id: Uuid,
-    content: String,
+    placement: Placement,
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    // Rolled back explicitly on errors: dropping the transaction would leave
+    // the list locked until the connection happens to be used again.
+    match reposition(&mut tx, user_id, todo_id, placement).await {
+        Ok(()) => tx.commit().await,
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn reposition(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    todo_id: Uuid,
+    placement: Placement,
 ) -> Result<(), Error> {
+    let scope = sqlx::query!(
+        "
+            select list_id, parent_id
+            from todos
+            where user_id = $1 and todo_id = $2 and deleted_at is null
+        ",
+        user_id,
+        todo_id,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // Released when the transaction ends.
+    let key = format!(
+        "todo-order:{}:{}:{}",
+        user_id,
+        scope.list_id.map_or("-".to_owned(), |id| id.to_string()),
+        scope.parent_id.map_or("-".to_owned(), |id| id.to_string()),
+    );
+    sqlx::query("select pg_advisory_xact_lock(hashtextextended($1, 0))")
+        .bind(key)
+        .execute(&mut *conn)
+        .await?;
+
+    // Moved to another list in the meantime, if it isn't here.
+    let mut list: Vec<_> = sqlx::query!(
+        "
+            select todo_id, position
+            from todos
+            where user_id = $1 and deleted_at is null
+                and list_id is not distinct from $2 and parent_id is not distinct from $3
+            order by position, todo_id
+        ",
+        user_id,
+        scope.list_id,
+        scope.parent_id,
+    )
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.todo_id, row.position))
+    .collect();
+
+    if !list.iter().any(|(id, _)| *id == todo_id) {
+        return Err(Error::RowNotFound);
+    }
+
+    let position = match ordering::new_position(&list, todo_id, placement)? {
+        Some(position) => position,
+        None => {
+            list = ordering::renumber(&list);
+            let (ids, positions): (Vec<Uuid>, Vec<i64>) = list.iter().copied().unzip();
+
+            sqlx::query!(
+                "
+                    update todos
+                    set position = renumbered.position
+                    from unnest($1::uuid[], $2::bigint[]) as renumbered(todo_id, position)
+                    where todos.todo_id = renumbered.todo_id
+                ",
+                &ids,
+                &positions,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            ordering::new_position(&list, todo_id, placement)?.ok_or_else(ordering::no_room)?
+        }
+    };
+
     sqlx::query!(
         "
             update todos
-            set content = $3
+            set position = $3
             where user_id = $1 and todo_id = $2
         ",
         user_id,
         todo_id,
-        content,
+        position,
     )
-    .execute(db)
+    .execute(&mut *conn)
     .await?;
 
+    history::record(&mut *conn, user_id, todo_id, &TodoChange::Moved).await?;
+
     Ok(())
 }
+
+/// Edits a todo, returning the id of its next occurrence if marking it done
+/// scheduled one. Errors with `RowNotFound` if `changes.version` is given and
+/// the todo has been changed since, so that two clients editing the same todo
+/// can't silently overwrite each other.
+#[tracing::instrument(skip(db, changes))]
+pub async fn update_todo_by_id(
+    db: &PgPool,
+    user_id: Uuid,
+    todo_id: Uuid,
+    changes: TodoChanges,
+) -> Result<Option<Uuid>, Error> {
+    let mut tx = db.begin().await?;
+
+    match edit_todo(&mut tx, user_id, todo_id, changes).await {
+        Ok(next) => {
+            tx.commit().await?;
+            Ok(next)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn edit_todo(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    todo_id: Uuid,
+    changes: TodoChanges,
+) -> Result<Option<Uuid>, Error> {
+    // `old` is read before the update, so it has the content and list as they
+    // were.
+    let edited = sqlx::query!(
+        r#"
+            update todos
+            set content = $3, due_at = $4, priority = $5, recurrence = $6,
+                version = todos.version + 1
+            from (select todo_id, content, list_id from todos where todo_id = $2) old
+            where todos.user_id = $1 and todos.todo_id = $2 and todos.deleted_at is null
+                and old.todo_id = todos.todo_id
+                and ($7::bigint is null or todos.version = $7)
+            returning old.content as before, list_name(old.list_id) as list, todos.done
+        "#,
+        user_id,
+        todo_id,
+        changes.content,
+        changes.due_at,
+        changes.priority as Priority,
+        changes.recurrence as Option<Recurrence>,
+        changes.version,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    // Only touch the tags and lists of the user's own todos.
+    let Some(edited) = edited else {
+        return match changes.version {
+            Some(_) => Err(Error::RowNotFound),
+            None => Ok(None),
+        };
+    };
+    if edited.before != changes.content {
+        let change = TodoChange::ContentChanged {
+            before: edited.before,
+            after: changes.content,
+        };
+        history::record(&mut *conn, user_id, todo_id, &change).await?;
+    }
+    tags::set_todo_tags(conn, user_id, todo_id, &changes.tags).await?;
+    if edited.list != changes.list {
+        lists::set_todo_list(conn, user_id, todo_id, changes.list.as_deref()).await?;
+        history::record(&mut *conn, user_id, todo_id, &TodoChange::Moved).await?;
+    }
+
+    // The row is locked by the update above, so there's no checking the
+    // version again.
+    match changes.done {
+        Some(done) if done != edited.done => toggle(conn, user_id, todo_id, None).await,
+        _ => Ok(None),
+    }
+}
+
+#[async_trait]
+impl TodoRepository for PgPool {
+    async fn create_todo(&self, user_id: Uuid, todo: NewTodo) -> Result<Todo, Error> {
+        create_todo(self, user_id, todo).await
+    }
+
+    async fn get_todos(&self, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+        get_todos(self, user_id).await
+    }
+
+    async fn list_todos(&self, user_id: Uuid, query: &TodoQuery) -> Result<Page, Error> {
+        list_todos(self, user_id, query).await
+    }
+
+    async fn search_todos(
+        &self,
+        user_id: Uuid,
+        text: &str,
+        filter: &TodoQuery,
+    ) -> Result<Vec<TodoMatch>, Error> {
+        search_todos(self, user_id, text, filter).await
+    }
+
+    async fn get_todo_by_id(&self, user_id: Uuid, todo_id: Uuid) -> Result<Todo, Error> {
+        get_todo_by_id(self, user_id, todo_id).await
+    }
+
+    async fn delete_todo_by_id(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        delete_todo_by_id(self, user_id, todo_id).await
+    }
+
+    async fn toggle_todo_by_id(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        version: Option<i64>,
+    ) -> Result<Option<Uuid>, Error> {
+        toggle_todo_by_id(self, user_id, todo_id, version).await
+    }
+
+    async fn get_subtasks(&self, user_id: Uuid, todo_ids: &[Uuid]) -> Result<Vec<Todo>, Error> {
+        get_subtasks(self, user_id, todo_ids).await
+    }
+
+    async fn complete_todo_with_subtasks(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        complete_todo_with_subtasks(self, user_id, todo_id).await
+    }
+
+    async fn move_todo(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        placement: Placement,
+    ) -> Result<(), Error> {
+        move_todo(self, user_id, todo_id, placement).await
+    }
+
+    async fn update_todo_by_id(
+        &self,
+        user_id: Uuid,
+        todo_id: Uuid,
+        changes: TodoChanges,
+    ) -> Result<Option<Uuid>, Error> {
+        update_todo_by_id(self, user_id, todo_id, changes).await
+    }
+
+    async fn update_todos(
+        &self,
+        user_id: Uuid,
+        todo_ids: &[Uuid],
+        action: &BulkAction,
+    ) -> Result<Vec<BulkResult>, Error> {
+        bulk::update_todos(self, user_id, todo_ids, action).await
+    }
+}
@@ -0,0 +1,212 @@
+//! Deleted todos.
+//!
+//! Deleting a todo only sets its `deleted_at`, hiding it everywhere but the
+//! trash, where it can be restored or deleted for good. Subtasks go to the trash
+//! with their parent and share its `deleted_at`, which is how restoring the
+//! parent knows to bring them back too. Whatever has been in the trash for
+//! longer than the retention period is purged by the server in the background.
+
+use async_trait::async_trait;
+use sqlx::{types::Json, Error, PgConnection, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{
+    changes,
+    history::{self, TodoChange},
+    priority::Priority,
+    recurrence::Recurrence,
+    tags::Tag,
+    todo::Todo,
+    TrashRepository,
+};
+
+/// How many days todos stay in the trash unless `TRASH_RETENTION_DAYS` says
+/// otherwise.
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+#[tracing::instrument(skip(db))]
+pub async fn get_trash(db: &PgPool, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+    sqlx::query_as!(
+        Todo,
+        r#"
+            select todo_id, content, done, user_id, created_at, due_at,
+                priority as "priority: Priority", recurrence as "recurrence: Recurrence",
+                position, parent_id,
+                tags_of_todo(todo_id) as "tags!: Json<Vec<Tag>>", list_id,
+                list_name(list_id) as list, deleted_at, completed_at, archived_at, version, updated_at
+            from todos
+            where user_id = $1 and deleted_at is not null
+                and not exists(
+                    select from todos parent
+                    where parent.todo_id = todos.parent_id and parent.deleted_at = todos.deleted_at
+                )
+            order by deleted_at desc, todo_id
+        "#,
+        user_id,
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Errors with `RowNotFound` if the todo isn't in the user's trash.
+#[tracing::instrument(skip(db))]
+pub async fn restore_todo(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    match restore(&mut tx, user_id, todo_id).await {
+        Ok(()) => tx.commit().await,
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn restore(conn: &mut PgConnection, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+    let restored = sqlx::query!(
+        "
+            with recursive subtree as (
+                select todo_id, deleted_at
+                from todos
+                where user_id = $1 and todo_id = $2 and deleted_at is not null
+                union all
+                select todos.todo_id, todos.deleted_at
+                from todos
+                join subtree on todos.parent_id = subtree.todo_id
+                    and todos.deleted_at = subtree.deleted_at
+            )
+            update todos
+            set deleted_at = null
+            where todo_id in (select todo_id from subtree)
+        ",
+        user_id,
+        todo_id,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    if restored == 0 {
+        return Err(Error::RowNotFound);
+    }
+    history::record(&mut *conn, user_id, todo_id, &TodoChange::Restored).await?;
+
+    // A subtask whose parent has since been deleted too comes back on its own.
+    sqlx::query!(
+        "
+            update todos
+            set parent_id = null
+            where todo_id = $1
+                and exists(
+                    select from todos parent
+                    where parent.todo_id = todos.parent_id and parent.deleted_at is not null
+                )
+        ",
+        todo_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn delete_todo_forever(db: &PgPool, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+    let deleted = sqlx::query!(
+        "
+            delete from todos
+            where user_id = $1 and todo_id = $2 and deleted_at is not null
+        ",
+        user_id,
+        todo_id,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(Error::RowNotFound);
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn empty_trash(db: &PgPool, user_id: Uuid) -> Result<(), Error> {
+    sqlx::query!(
+        "delete from todos where user_id = $1 and deleted_at is not null",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes every user's todos that went in the trash before `before` for good,
+/// returning how many there were. Each user's are deleted in a transaction of
+/// their own; see [`changes::lock_todos`].
+#[tracing::instrument(skip(db))]
+pub async fn purge_trash(db: &PgPool, before: OffsetDateTime) -> Result<u64, Error> {
+    let user_ids = sqlx::query_scalar!(
+        "select distinct user_id from todos where deleted_at < $1",
+        before,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut purged = 0;
+    for user_id in user_ids {
+        let mut tx = db.begin().await?;
+
+        match purge_users_trash(&mut tx, user_id, before).await {
+            Ok(count) => {
+                tx.commit().await?;
+                purged += count;
+            }
+            Err(e) => {
+                tx.rollback().await?;
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(purged)
+}
+
+async fn purge_users_trash(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    before: OffsetDateTime,
+) -> Result<u64, Error> {
+    changes::lock_todos(conn, user_id).await?;
+
+    Ok(sqlx::query!(
+        "delete from todos where user_id = $1 and deleted_at < $2",
+        user_id,
+        before,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected())
+}
+
+#[async_trait]
+impl TrashRepository for PgPool {
+    async fn get_trash(&self, user_id: Uuid) -> Result<Vec<Todo>, Error> {
+        get_trash(self, user_id).await
+    }
+
+    async fn restore_todo(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        restore_todo(self, user_id, todo_id).await
+    }
+
+    async fn delete_todo_forever(&self, user_id: Uuid, todo_id: Uuid) -> Result<(), Error> {
+        delete_todo_forever(self, user_id, todo_id).await
+    }
+
+    async fn empty_trash(&self, user_id: Uuid) -> Result<(), Error> {
+        empty_trash(self, user_id).await
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use axum_login::{AuthUser, AuthnBackend, UserId};
 use password_auth::verify_password;
@@ -6,6 +8,8 @@ use sqlx::{FromRow, PgPool};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use uuid::Uuid;
 
+use super::{audit::AuditAction, UserRepository};
+
 #[serde_with::serde_as]
 #[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -14,6 +18,27 @@ pub struct User {
     password: String,
     #[serde_as(as = "Rfc3339")]
     pub created_at: OffsetDateTime,
+    /// Disabled users can't log in, and any sessions they have stop working.
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub disabled_at: Option<OffsetDateTime>,
+}
+
+impl User {
+    /// Builds a user that has not been persisted yet, hashing the given
+    /// plaintext password.
+    pub fn new(email: &str, password: &str) -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            email: email.to_owned(),
+            password: password_auth::generate_hash(password),
+            created_at: OffsetDateTime::now_utc(),
+            disabled_at: None,
+        }
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
 }
 
 // Here we've implemented `Debug` manually to avoid accidentally logging the
@@ -24,6 +49,7 @@ impl std::fmt::Debug for User {
             .field("user_id", &self.user_id)
             .field("email", &self.email)
             .field("password", &"[redacted]")
+            .field("disabled_at", &self.disabled_at)
             .finish()
     }
 }
@@ -52,14 +78,14 @@ pub struct Credentials {
     pub next: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Backend {
-    db: PgPool,
+    users: Arc<dyn UserRepository>,
 }
 
 impl Backend {
-    pub fn new(db: PgPool) -> Self {
-        Self { db }
+    pub fn new(users: Arc<dyn UserRepository>) -> Self {
+        Self { users }
     }
 }
 
@@ -73,39 +99,23 @@ impl AuthnBackend for Backend {
         &self,
         creds: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        let user: Option<Self::User> = sqlx::query_as!(
-            User,
-            r#"
-                    select * from users where email = $1
-                "#,
-            creds.email
-        )
-        .fetch_optional(&self.db)
-        .await?;
+        let user = self.users.get_user_by_email(&creds.email).await?;
 
         Ok(user.filter(|user| {
-            verify_password(creds.password, &user.password)
-                .ok()
-                .is_some() // We're using password-based authentication--this
-                           // works by comparing our form input with an argon2
-                           // password hash.
+            !user.is_disabled()
+                && verify_password(&creds.password, &user.password)
+                    .ok()
+                    .is_some() // We're using password-based authentication--this
+                               // works by comparing our form input with an argon2
+                               // password hash.
         }))
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        let user = sqlx::query_as!(
-            User,
-            r#"
-                    select user_id, email, password, created_at
-                    from users
-                    where user_id = $1
-                "#,
-            user_id
-        )
-        .fetch_optional(&self.db)
-        .await?;
-
-        Ok(user)
+        let user = self.users.get_user_by_id(*user_id).await?;
+
+        // Filtering here logs out anyone who is disabled mid-session.
+        Ok(user.filter(|user| !user.is_disabled()))
     }
 }
 
@@ -114,6 +124,7 @@ impl AuthnBackend for Backend {
 // Note that we've supplied our concrete backend here.
 pub type AuthSession = axum_login::AuthSession<Backend>;
 
+#[tracing::instrument(skip(db, password))]
 pub async fn create_user(db: &PgPool, email: &str, password: &str) -> Result<User, sqlx::Error> {
     let password = password_auth::generate_hash(password);
 
@@ -123,9 +134,9 @@ pub async fn create_user(db: &PgPool, email: &str, password: &str) -> Result<Use
                 with inserted_user as (
                     insert into users (email, password)
                     values ($1, $2)
-                    returning user_id, email, password, created_at
+                    returning user_id, email, password, created_at, disabled_at
                 )
-                select user_id, email, password, created_at from inserted_user
+                select user_id, email, password, created_at, disabled_at from inserted_user
             "#,
         email,
         password
@@ -135,3 +146,109 @@ pub async fn create_user(db: &PgPool, email: &str, password: &str) -> Result<Use
 
     Ok(user)
 }
+
+#[tracing::instrument(skip(db))]
+pub async fn get_user_by_email(db: &PgPool, email: &str) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        r#"
+                select user_id, email, password, created_at, disabled_at
+                from users
+                where email = $1
+            "#,
+        email
+    )
+    .fetch_optional(db)
+    .await
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn get_user_by_id(db: &PgPool, user_id: Uuid) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        r#"
+                select user_id, email, password, created_at, disabled_at
+                from users
+                where user_id = $1
+            "#,
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn list_users(db: &PgPool) -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        r#"
+                select user_id, email, password, created_at, disabled_at
+                from users
+                order by created_at
+            "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Returns whether a user with that email existed.
+#[tracing::instrument(skip(db))]
+pub async fn disable_user(db: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+                update users
+                set disabled_at = coalesce(disabled_at, now())
+                where email = $1
+            "#,
+        email
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Returns whether a user with that email existed.
+///
+/// Changing the hash also invalidates the user's existing sessions, since it
+/// doubles as the session auth hash. The change goes in the user's audit log;
+/// see [`super::audit`].
+#[tracing::instrument(skip(db, password))]
+pub async fn set_password(db: &PgPool, email: &str, password: &str) -> Result<bool, sqlx::Error> {
+    let password = password_auth::generate_hash(password);
+
+    let result = sqlx::query!(
+        r#"
+                with updated as (
+                    update users
+                    set password = $2
+                    where email = $1
+                    returning user_id, email
+                )
+                insert into audit_log(user_id, action, email)
+                select user_id, $3, email from updated
+            "#,
+        email,
+        password,
+        AuditAction::PasswordChanged as AuditAction,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[async_trait]
+impl UserRepository for PgPool {
+    async fn create_user(&self, email: &str, password: &str) -> Result<User, sqlx::Error> {
+        create_user(self, email, password).await
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+        get_user_by_email(self, email).await
+    }
+
+    async fn get_user_by_id(&self, user_id: Uuid) -> Result<Option<User>, sqlx::Error> {
+        get_user_by_id(self, user_id).await
+    }
+}
@@ -6,6 +6,8 @@ use serde_with::DisplayFromStr;
 use tracing::*;
 use validator::ValidationErrors;
 
+use crate::telemetry;
+
 /// An API-friendly error type.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -14,7 +16,11 @@ pub enum Error {
     /// The exact error contents are not reported to the user in order to avoid leaking
     /// information about databse internals.
     #[error("an internal database error occurred")]
-    Sqlx(#[from] sqlx::Error),
+    Sqlx(#[source] sqlx::Error),
+
+    /// There's no such row, or it isn't the user's.
+    #[error("not found")]
+    NotFound,
 
     /// Similarly, we don't want to report random `anyhow` errors to the user.
     #[error("an internal server error occurred")]
@@ -33,6 +39,17 @@ pub enum Error {
     Unauthorized,
 }
 
+/// Lookups of a row that isn't there, or isn't the user's, come back as
+/// `RowNotFound`, which isn't the server's fault.
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => Error::NotFound,
+            e => Error::Sqlx(e),
+        }
+    }
+}
+
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         warn!("API error: {:?}", self);
@@ -40,12 +57,16 @@ impl IntoResponse for Error {
         #[serde_with::serde_as]
         #[serde_with::skip_serializing_none]
         #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
         struct ErrorResponse<'a> {
             // Serialize the `Display` output as the error message
             #[serde_as(as = "DisplayFromStr")]
             message: &'a Error,
 
             errors: Option<&'a ValidationErrors>,
+
+            // Lets support find the request in our traces from a user's report.
+            trace_id: Option<String>,
         }
 
         let errors = match &self {
@@ -58,6 +79,7 @@ impl IntoResponse for Error {
             Json(ErrorResponse {
                 message: &self,
                 errors,
+                trace_id: telemetry::current_trace_id(),
             }),
         )
             .into_response()
@@ -71,6 +93,7 @@ impl Error {
         match self {
             Sqlx(_) | Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
             InvalidEntity(_) | UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            NotFound => StatusCode::NOT_FOUND,
             Conflict(_) => StatusCode::CONFLICT,
             Unauthorized => StatusCode::UNAUTHORIZED,
         }
@@ -0,0 +1,66 @@
+use anyhow::bail;
+use async_trait::async_trait;
+use fred::{clients::RedisClient, interfaces::ClientLike};
+use sqlx::PgPool;
+
+use crate::data;
+
+/// A dependency that has to be reachable for the app to serve traffic.
+///
+/// Checks are run by `/readyz`; see [`crate::api::health`].
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// The key this check is reported under.
+    fn name(&self) -> &'static str;
+
+    async fn check(&self) -> anyhow::Result<()>;
+}
+
+/// Pings Postgres through the pool.
+pub struct Postgres(pub PgPool);
+
+#[async_trait]
+impl HealthCheck for Postgres {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        sqlx::query("select 1").execute(&self.0).await?;
+
+        Ok(())
+    }
+}
+
+/// Verifies that every migration embedded in the binary has been applied.
+pub struct Migrations(pub PgPool);
+
+#[async_trait]
+impl HealthCheck for Migrations {
+    fn name(&self) -> &'static str {
+        "migrations"
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        let pending = data::pending_migrations(&self.0).await?.len();
+
+        if pending > 0 {
+            bail!("{} pending migration(s)", pending);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HealthCheck for RedisClient {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        self.ping::<()>().await?;
+
+        Ok(())
+    }
+}
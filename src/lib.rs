@@ -1,9 +1,220 @@
 pub mod api;
 pub mod data;
 pub mod error;
+pub mod health;
+pub mod live;
+pub mod prometheus;
+pub mod sessions;
+pub mod shutdown;
+pub mod telemetry;
 pub mod templates;
 pub mod validators;
 
+use std::sync::Arc;
+
+use axum::{
+    error_handling::HandleErrorLayer,
+    http::{HeaderName, StatusCode},
+    middleware,
+    routing::get,
+    BoxError, Router,
+};
+use axum_login::{
+    login_required,
+    tower_sessions::{Expiry, SessionManagerLayer, SessionStore},
+    AuthManagerLayerBuilder,
+};
+use data::{
+    archive, memory::MemoryRepository, trash, user::Backend, ArchiveRepository, AuditRepository,
+    ChangeRepository, HistoryRepository, ListRepository, TagRepository, TodoRepository,
+    TrashRepository, UserRepository,
+};
 use error::Error;
+use health::HealthCheck;
+use live::Live;
+use prometheus::Exporter;
+use sqlx::PgPool;
+use time::Duration;
+use tower::ServiceBuilder;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
 
 pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+/// Shared state handed to every handler.
+#[derive(Clone)]
+pub struct AppState {
+    pub todos: Arc<dyn TodoRepository>,
+    pub tags: Arc<dyn TagRepository>,
+    pub lists: Arc<dyn ListRepository>,
+    pub trash: Arc<dyn TrashRepository>,
+    pub archive: Arc<dyn ArchiveRepository>,
+    pub history: Arc<dyn HistoryRepository>,
+    pub audit: Arc<dyn AuditRepository>,
+    pub changes: Arc<dyn ChangeRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub health_checks: Vec<Arc<dyn HealthCheck>>,
+    pub metrics: Option<Exporter>,
+    /// Where changes to todos are published for the users' open tabs.
+    pub live: Live,
+    /// How many days deleted todos stay in the trash.
+    pub trash_retention_days: u32,
+    /// After how many days done todos are archived, if they are at all.
+    pub auto_archive_days: Option<u32>,
+}
+
+impl AppState {
+    /// State backed by the Postgres pool.
+    pub fn postgres(db: PgPool) -> Self {
+        Self {
+            todos: Arc::new(db.clone()),
+            tags: Arc::new(db.clone()),
+            lists: Arc::new(db.clone()),
+            trash: Arc::new(db.clone()),
+            archive: Arc::new(db.clone()),
+            history: Arc::new(db.clone()),
+            audit: Arc::new(db.clone()),
+            changes: Arc::new(db.clone()),
+            users: Arc::new(db.clone()),
+            health_checks: vec![
+                Arc::new(health::Postgres(db.clone())),
+                Arc::new(health::Migrations(db)),
+            ],
+            metrics: None,
+            live: Live::new(),
+            trash_retention_days: trash::DEFAULT_RETENTION_DAYS,
+            auto_archive_days: Some(archive::DEFAULT_AUTO_ARCHIVE_DAYS),
+        }
+    }
+
+    /// State backed by a fresh in-memory repository, for tests.
+    pub fn memory() -> Self {
+        let repository = MemoryRepository::new();
+
+        Self {
+            todos: Arc::new(repository.clone()),
+            tags: Arc::new(repository.clone()),
+            lists: Arc::new(repository.clone()),
+            trash: Arc::new(repository.clone()),
+            archive: Arc::new(repository.clone()),
+            history: Arc::new(repository.clone()),
+            audit: Arc::new(repository.clone()),
+            changes: Arc::new(repository.clone()),
+            users: Arc::new(repository),
+            health_checks: vec![],
+            metrics: None,
+            live: Live::new(),
+            trash_retention_days: trash::DEFAULT_RETENTION_DAYS,
+            auto_archive_days: Some(archive::DEFAULT_AUTO_ARCHIVE_DAYS),
+        }
+    }
+
+    /// Adds a dependency that `/readyz` should report on.
+    pub fn with_health_check(mut self, check: impl HealthCheck + 'static) -> Self {
+        self.health_checks.push(Arc::new(check));
+        self
+    }
+
+    /// Tells users how long deleted todos stay in the trash. Purging them is
+    /// up to the caller.
+    pub fn with_trash_retention_days(mut self, days: u32) -> Self {
+        self.trash_retention_days = days;
+        self
+    }
+
+    /// Tells users after how many days done todos are archived, or that they
+    /// aren't with `None`. Archiving them is up to the caller.
+    pub fn with_auto_archive_days(mut self, days: Option<u32>) -> Self {
+        self.auto_archive_days = days;
+        self
+    }
+
+    /// Serves `/metrics` from the given exporter.
+    pub fn with_metrics(mut self, exporter: Exporter) -> Self {
+        self.metrics = Some(exporter);
+        self
+    }
+
+    /// Publishes changes to todos through `live`, rather than only to this
+    /// instance's subscribers.
+    pub fn with_live(mut self, live: Live) -> Self {
+        self.live = live;
+        self
+    }
+}
+
+pub fn app<Store: SessionStore>(state: AppState, session_store: Store) -> Router {
+    // Session layer.
+    //
+    // This uses `tower-sessions` to establish a layer that will provide the session
+    // as a request extension.
+    let session_layer = SessionManagerLayer::new(session_store)
+        .with_secure(false)
+        .with_expiry(Expiry::OnInactivity(Duration::days(1)));
+
+    // Auth service.
+    //
+    // This combines the session layer with our backend to establish the auth
+    // service which will provide the auth session as a request extension.
+    let backend = Backend::new(state.users.clone());
+    let auth_service = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(|_: BoxError| async {
+            StatusCode::BAD_REQUEST
+        }))
+        .layer(AuthManagerLayerBuilder::new(backend, session_layer).build());
+
+    Router::new()
+        .route("/", get(api::handle_index))
+        .merge(api::auth::router())
+        .merge(
+            api::todos::router()
+                .merge(api::tags::router())
+                .merge(api::lists::router())
+                .merge(api::trash::router())
+                .merge(api::archive::router())
+                .merge(api::history::router())
+                .merge(api::bulk::router())
+                .merge(api::live::router())
+                .merge(api::changes::router())
+                .merge(api::sync::router())
+                .route_layer(login_required!(Backend, login_url = "/login")),
+        )
+        .nest_service(
+            "/static",
+            ServeDir::new("static")
+                .precompressed_br()
+                .precompressed_gzip(),
+        )
+        .fallback(api::handle_404)
+        .layer(middleware::from_fn(telemetry::record_user))
+        .layer(auth_service)
+        .layer(
+            // The request ID has to be set before the trace span is created so
+            // that it ends up on every log line for the request.
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::new(
+                    HeaderName::from_static(telemetry::REQUEST_ID_HEADER),
+                    MakeRequestUuid,
+                ))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(telemetry::make_span)
+                        .on_request(DefaultOnRequest::new().level(Level::INFO))
+                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
+                )
+                .map_request(telemetry::stash_span)
+                .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+                    telemetry::REQUEST_ID_HEADER,
+                ))),
+        )
+        .layer(middleware::from_fn(prometheus::track_requests))
+        // Probes and scrapes are merged last so that none of the layers above
+        // (sessions, auth, request tracing and metrics) apply to them.
+        .merge(api::health::router())
+        .merge(api::metrics::router())
+        .with_state(state)
+}
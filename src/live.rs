@@ -0,0 +1,231 @@
+//! Live updates to a user's todos, pushed to their open tabs and devices as
+//! server-sent events.
+//!
+//! Handlers [`Live::publish`] what they changed once the change is made. With
+//! Redis, updates go out on a pub/sub channel so that every instance hears
+//! them, wherever the user's browsers happen to be connected, and each
+//! instance [`Live::relay`]s what it hears to its own subscribers. Without
+//! Redis, as in tests, updates stay in-process.
+
+use fred::{
+    clients::RedisClient,
+    interfaces::{ClientLike, PubsubInterface},
+};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::shutdown::Shutdown;
+
+/// The Redis channel updates are published on.
+pub const CHANNEL: &str = "todo-updates";
+
+/// How many updates a slow subscriber can fall behind by before it misses
+/// some and is told to reload everything instead.
+const CAPACITY: usize = 256;
+
+/// What changed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "kind", content = "todoId", rename_all = "snake_case")]
+pub enum Change {
+    Created(Uuid),
+    Updated(Uuid),
+    /// Moved to the trash.
+    Deleted(Uuid),
+    /// Any number of todos at once, as when archiving everything done, or
+    /// updates the subscriber missed.
+    Many,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoUpdate {
+    pub user_id: Uuid,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+impl TodoUpdate {
+    /// The name of the event it's sent as. Updates to one todo are named after
+    /// it, so that only that todo reloads.
+    pub fn event_name(&self) -> String {
+        match self.change {
+            Change::Created(_) => "todo-created".to_owned(),
+            Change::Updated(todo_id) => format!("todo-updated-{}", todo_id),
+            Change::Deleted(_) => "todo-deleted".to_owned(),
+            Change::Many => "todos-changed".to_owned(),
+        }
+    }
+}
+
+/// Where updates are published and subscribed to.
+#[derive(Clone)]
+pub struct Live {
+    local: broadcast::Sender<TodoUpdate>,
+    redis: Option<RedisClient>,
+    shutdown: Shutdown,
+}
+
+impl Default for Live {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Live {
+    /// Updates that stay in-process.
+    pub fn new() -> Self {
+        let (local, _) = broadcast::channel(CAPACITY);
+
+        Self {
+            local,
+            redis: None,
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Publishes updates through Redis, leaving it to [`Live::relay`] to
+    /// bring them back.
+    pub fn with_redis(mut self, redis: RedisClient) -> Self {
+        self.redis = Some(redis);
+        self
+    }
+
+    /// Ends every subscription once the server starts shutting down, so that
+    /// open event streams don't hold up draining.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Tells the user's open tabs about a change. The change has already been
+    /// made, so failing to tell them is only logged.
+    pub async fn publish(&self, user_id: Uuid, change: Change) {
+        let update = TodoUpdate { user_id, change };
+
+        match &self.redis {
+            Some(redis) => {
+                let message = serde_json::to_string(&update).unwrap();
+                if let Err(e) = redis.publish::<(), _, _>(CHANNEL, message).await {
+                    warn!("failed to publish {:?}: {}", update, e);
+                }
+            }
+            // Nobody listening is fine.
+            None => {
+                let _ = self.local.send(update);
+            }
+        }
+    }
+
+    /// The user's updates from now on, until shutdown. If the subscriber
+    /// falls behind, the updates it missed are replaced by one
+    /// [`Change::Many`].
+    pub fn subscribe(&self, user_id: Uuid) -> impl Stream<Item = TodoUpdate> + Send + 'static {
+        let updates = self.local.subscribe();
+
+        futures::stream::unfold(updates, move |mut updates| async move {
+            loop {
+                match updates.recv().await {
+                    Ok(update) if update.user_id == user_id => return Some((update, updates)),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => {
+                        let update = TodoUpdate {
+                            user_id,
+                            change: Change::Many,
+                        };
+                        return Some((update, updates));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .take_until(self.shutdown.clone().wait())
+    }
+
+    /// Hands the updates every instance publishes through Redis to this
+    /// instance's subscribers, until shutdown. A subscribed connection can't
+    /// be used for anything else, so `subscriber` should be a connection of
+    /// its own, which is closed afterwards, also when relaying fails.
+    pub async fn relay(self, subscriber: RedisClient) -> anyhow::Result<()> {
+        let relayed = self.forward(&subscriber).await;
+        let _ = subscriber.quit().await;
+
+        relayed
+    }
+
+    async fn forward(&self, subscriber: &RedisClient) -> anyhow::Result<()> {
+        subscriber.connect();
+        subscriber.wait_for_connect().await?;
+
+        let mut messages = subscriber.on_message();
+        subscriber.subscribe::<(), _>(CHANNEL).await?;
+
+        let stop = self.shutdown.clone().wait();
+        tokio::pin!(stop);
+
+        loop {
+            let message = tokio::select! {
+                _ = &mut stop => break,
+                message = messages.recv() => message,
+            };
+
+            match message {
+                Ok(message) => {
+                    let update = message
+                        .value
+                        .as_string()
+                        .and_then(|value| serde_json::from_str(&value).ok());
+                    match update {
+                        Some(update) => {
+                            let _ = self.local.send(update);
+                        }
+                        None => warn!("ignoring malformed update: {:?}", message.value),
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => warn!("missed {} update(s) from redis", missed),
+                Err(RecvError::Closed) => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_to_one_todo_are_named_after_it() {
+        let todo_id = Uuid::new_v4();
+        let update = TodoUpdate {
+            user_id: Uuid::nil(),
+            change: Change::Updated(todo_id),
+        };
+
+        assert_eq!(update.event_name(), format!("todo-updated-{}", todo_id));
+    }
+
+    #[test]
+    fn updates_round_trip_through_json() {
+        let todo_id = Uuid::new_v4();
+        let update = TodoUpdate {
+            user_id: Uuid::nil(),
+            change: Change::Deleted(todo_id),
+        };
+
+        let json = serde_json::to_value(update).unwrap();
+        assert_eq!(json["kind"], "deleted");
+        assert_eq!(json["todoId"], todo_id.to_string());
+        assert_eq!(serde_json::from_value::<TodoUpdate>(json).unwrap(), update);
+
+        let many = TodoUpdate {
+            user_id: Uuid::nil(),
+            change: Change::Many,
+        };
+        let json = serde_json::to_string(&many).unwrap();
+        assert_eq!(serde_json::from_str::<TodoUpdate>(&json).unwrap(), many);
+    }
+}
@@ -1,58 +1,75 @@
-use anyhow::Context;
-use axum::{
-    error_handling::HandleErrorLayer, http::StatusCode, routing::get, BoxError, Extension, Router,
+use anyhow::{bail, Context};
+use clap::Parser;
+use cli::{Cli, Command};
+use flyio_rust::{
+    api, app,
+    data::{self, archive, trash},
+    live::Live,
+    prometheus::Exporter,
+    sessions::RedisSessionStore,
+    shutdown,
+    shutdown::Shutdown,
+    telemetry::{self, LogFormat, OtlpConfig},
+    AppState,
 };
-use axum_login::{
-    login_required,
-    tower_sessions::{Expiry, RedisStore, SessionManagerLayer},
-    AuthManagerLayerBuilder,
-};
-use flyio_rust::{api, data::user::Backend};
 use fred::prelude::*;
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::{env, net::SocketAddr};
-use time::Duration;
-use tower::ServiceBuilder;
-use tower_http::{
-    services::ServeDir,
-    trace::{DefaultOnRequest, DefaultOnResponse},
+use std::{
+    env, io,
+    net::SocketAddr,
+    time::{Duration, Instant},
 };
-use tracing::{info, Level};
+use time::OffsetDateTime;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 25;
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const RELAY_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const RELAY_MAX_BACKOFF: Duration = Duration::from_secs(60);
 
 mod auth;
+mod cli;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                "flyio_rust=debug,tower_http=debug,axum::rejection=trace,sqlx=info".into()
-            }),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let cli = Cli::parse();
 
-    let port = env::var("PORT")
-        .map(|x| x.parse::<u16>())
-        .unwrap_or(Ok(DEFAULT_PORT))
-        .unwrap();
+    match cli.command.unwrap_or_default() {
+        Command::Serve => serve().await,
+        command => {
+            // Admin commands log to stderr so their output can be piped.
+            tracing_subscriber::registry()
+                .with(env_filter())
+                .with(telemetry::log_layer(LogFormat::Text, io::stderr))
+                .init();
+
+            cli::run(command).await
+        }
+    }
+}
+
+fn env_filter() -> tracing_subscriber::EnvFilter {
+    tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        "flyio_rust=debug,tower_http=debug,axum::rejection=trace,sqlx=info".into()
+    })
+}
 
-    let database_url = env::var("DATABASE_URL").unwrap();
+pub async fn connect_db() -> anyhow::Result<PgPool> {
+    let database_url = env::var("DATABASE_URL").context("DATABASE_URL must be set")?;
 
-    let db = PgPoolOptions::new()
+    PgPoolOptions::new()
         .max_connections(20)
         .connect(&database_url)
         .await
-        .context("failed to connect to DATABASE_URL")?;
-
-    sqlx::migrate!().run(&db).await?;
+        .context("failed to connect to DATABASE_URL")
+}
 
-    let redis_url = env::var("REDIS_URL").unwrap();
+pub async fn connect_redis() -> anyhow::Result<RedisClient> {
+    let redis_url = env::var("REDIS_URL").context("REDIS_URL must be set")?;
 
-    let redis_config = RedisConfig::from_url(redis_url.as_str()).unwrap();
+    let redis_config = RedisConfig::from_url(redis_url.as_str()).context("invalid REDIS_URL")?;
     let redis_client = RedisClient::new(redis_config, None, None, None);
 
     redis_client.connect();
@@ -61,53 +78,245 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("failed to connect to REDIS_URL")?;
 
+    Ok(redis_client)
+}
+
+async fn serve() -> anyhow::Result<()> {
+    let log_format = LogFormat::from_env()?;
+    let otlp_config = OtlpConfig::from_env()?;
+    let tracer_provider = telemetry::tracer_provider(otlp_config.as_ref())?;
+
+    tracing_subscriber::registry()
+        .with(env_filter())
+        .with(telemetry::log_layer(log_format, io::stdout))
+        .with(telemetry::layer(&tracer_provider))
+        .init();
+
+    if let Some(config) = &otlp_config {
+        info!(
+            "exporting traces to {} as {}",
+            config.endpoint, config.service_name
+        );
+    }
+
+    let port = env::var("PORT")
+        .map(|x| x.parse::<u16>())
+        .unwrap_or(Ok(DEFAULT_PORT))
+        .unwrap();
+
+    let drain_timeout = env::var("DRAIN_TIMEOUT_SECS")
+        .map(|x| x.parse::<u64>())
+        .unwrap_or(Ok(DEFAULT_DRAIN_TIMEOUT_SECS))
+        .map(Duration::from_secs)
+        .unwrap();
+
+    let auto_migrate = env::var("AUTO_MIGRATE")
+        .map(|x| x.parse::<bool>())
+        .unwrap_or(Ok(true))
+        .context("invalid AUTO_MIGRATE")?;
+
+    let trash_retention_days = env::var("TRASH_RETENTION_DAYS")
+        .map(|x| x.parse::<u32>())
+        .unwrap_or(Ok(trash::DEFAULT_RETENTION_DAYS))
+        .context("invalid TRASH_RETENTION_DAYS")?;
+
+    // Zero turns auto-archiving off.
+    let auto_archive_days = env::var("AUTO_ARCHIVE_DAYS")
+        .map(|x| x.parse::<u32>())
+        .unwrap_or(Ok(archive::DEFAULT_AUTO_ARCHIVE_DAYS))
+        .context("invalid AUTO_ARCHIVE_DAYS")?;
+    let auto_archive_days = Some(auto_archive_days).filter(|&days| days > 0);
+
+    let db = connect_db().await?;
+
+    if auto_migrate {
+        data::migrate(&db).await?;
+    }
+
+    // Serving against an older schema would fail in confusing ways, so refuse
+    // outright and leave it to `flyio-rust migrate run` (or another instance).
+    let pending = data::pending_migrations(&db).await?;
+    if !pending.is_empty() {
+        bail!(
+            "database schema is behind: {} pending migration(s), run `flyio-rust migrate run`",
+            pending.len()
+        );
+    }
+
+    let redis_client = connect_redis().await?;
+
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
 
+    let shutdown = Shutdown::new();
+
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown::signal().await;
+            info!(
+                "shutdown requested, draining connections for up to {:?}",
+                drain_timeout
+            );
+            shutdown.begin();
+        }
+    });
+
+    // Every instance tries this, but only one does it at a time.
+    tokio::spawn(housekeeping(
+        db.clone(),
+        time::Duration::days(trash_retention_days.into()),
+        auto_archive_days.map(|days| time::Duration::days(days.into())),
+        shutdown.clone(),
+    ));
+
+    // Changes are published through Redis, and what every instance publishes
+    // is relayed to this one's subscribers over a connection of its own.
+    let live = Live::new()
+        .with_redis(redis_client.clone())
+        .with_shutdown(shutdown.clone());
+
+    tokio::spawn(relay_live_updates(
+        live.clone(),
+        redis_client.clone(),
+        shutdown.clone(),
+    ));
+
+    let mut state = AppState::postgres(db.clone())
+        .with_trash_retention_days(trash_retention_days)
+        .with_auto_archive_days(auto_archive_days)
+        .with_live(live)
+        .with_health_check(redis_client.clone())
+        .with_health_check(shutdown.clone());
+
+    // Metrics are only exposed when something protects them: either a token
+    // on the main listener, or a separate (private) listener.
+    let exporter = Exporter::install()?
+        .with_db(db.clone())
+        .with_redis(redis_client.clone());
+
+    if let Ok(token) = env::var("METRICS_TOKEN") {
+        state = state.with_metrics(exporter.clone().with_token(token));
+    }
+
+    if let Ok(metrics_addr) = env::var("METRICS_ADDR") {
+        let metrics_addr = metrics_addr
+            .parse::<SocketAddr>()
+            .context("invalid METRICS_ADDR")?;
+        let metrics_app = api::metrics::router().with_state(state.clone().with_metrics(exporter));
+
+        info!("serving metrics on {}", metrics_addr);
+
+        tokio::spawn(
+            axum::Server::bind(&metrics_addr)
+                .serve(metrics_app.into_make_service())
+                .with_graceful_shutdown(shutdown.clone().wait()),
+        );
+    }
+
     info!("listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app(db, redis_client).into_make_service())
+    let server = axum::Server::bind(&addr)
+        .serve(app(state, RedisSessionStore::new(redis_client.clone())).into_make_service())
+        .with_graceful_shutdown(shutdown.clone().wait());
+
+    // Once draining starts, give in-flight requests `drain_timeout` to finish
+    // before giving up on them.
+    let deadline = async {
+        shutdown.wait().await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+
+    tokio::select! {
+        result = server => result.context("failed to serve")?,
+        _ = deadline => warn!("drain timeout elapsed, dropping remaining connections"),
+    }
+
+    db.close().await;
+    redis_client
+        .quit()
         .await
-        .context("failed to serve")
+        .context("failed to close redis connection")?;
+
+    // Flush any spans still sitting in the batch exporter.
+    tracer_provider.force_flush();
+
+    info!("shutdown complete");
+
+    Ok(())
+}
+
+/// Deletes todos for good once they've been in the trash for `retention`, and
+/// archives todos once they've been done for `auto_archive`, checking every
+/// [`HOUSEKEEPING_INTERVAL`] until shutdown. Instances skip their turn while
+/// another is at it.
+async fn housekeeping(
+    db: PgPool,
+    retention: time::Duration,
+    auto_archive: Option<time::Duration>,
+    shutdown: Shutdown,
+) {
+    let mut interval = tokio::time::interval(HOUSEKEEPING_INTERVAL);
+    let stop = shutdown.wait();
+    tokio::pin!(stop);
+
+    loop {
+        tokio::select! {
+            _ = &mut stop => return,
+            _ = interval.tick() => {}
+        }
+
+        let tidy_up = tidy_up(&db, retention, auto_archive);
+        match data::run_alone(&db, "housekeeping", tidy_up).await {
+            Ok(Some(())) => {}
+            Ok(None) => info!("skipping housekeeping, another instance is at it"),
+            Err(e) => warn!("failed to take turns at housekeeping: {}", e),
+        }
+    }
 }
 
-pub fn app(db: PgPool, redis: RedisClient) -> Router {
-    // Session layer.
-    //
-    // This uses `tower-sessions` to establish a layer that will provide the session
-    // as a request extension.
-    let session_store = RedisStore::new(redis);
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_secure(false)
-        .with_expiry(Expiry::OnInactivity(Duration::days(1)));
-
-    // Auth service.
-    //
-    // This combines the session layer with our backend to establish the auth
-    // service which will provide the auth session as a request extension.
-    let backend = flyio_rust::data::user::Backend::new(db.clone());
-    let auth_service = ServiceBuilder::new()
-        .layer(HandleErrorLayer::new(|_: BoxError| async {
-            StatusCode::BAD_REQUEST
-        }))
-        .layer(AuthManagerLayerBuilder::new(backend, session_layer).build());
-
-    Router::new()
-        .route("/", get(api::handle_index))
-        .merge(api::auth::router())
-        .merge(api::todos::router().route_layer(login_required!(Backend, login_url = "/login")))
-        .nest_service(
-            "/static",
-            ServeDir::new("static")
-                .precompressed_br()
-                .precompressed_gzip(),
-        )
-        .fallback(api::handle_404)
-        .layer(auth_service)
-        .layer(Extension(db))
-        .layer(
-            tower_http::trace::TraceLayer::new_for_http()
-                .on_request(DefaultOnRequest::new().level(Level::INFO))
-                .on_response(DefaultOnResponse::new().level(Level::INFO)),
-        )
+async fn tidy_up(db: &PgPool, retention: time::Duration, auto_archive: Option<time::Duration>) {
+    let now = OffsetDateTime::now_utc();
+
+    match trash::purge_trash(db, now - retention).await {
+        Ok(0) => {}
+        Ok(purged) => info!("purged {} todo(s) from the trash", purged),
+        Err(e) => warn!("failed to purge the trash: {}", e),
+    }
+
+    if let Some(auto_archive) = auto_archive {
+        match archive::auto_archive(db, now - auto_archive).await {
+            Ok(0) => {}
+            Ok(archived) => info!("archived {} done todo(s)", archived),
+            Err(e) => warn!("failed to archive done todos: {}", e),
+        }
+    }
+}
+
+/// Relays live updates from Redis until shutdown, subscribing again whenever
+/// the subscription drops. Retries back off from [`RELAY_MIN_BACKOFF`] up to
+/// [`RELAY_MAX_BACKOFF`], starting over once a subscription has held up.
+async fn relay_live_updates(live: Live, redis: RedisClient, shutdown: Shutdown) {
+    let mut backoff = RELAY_MIN_BACKOFF;
+    let stop = shutdown.clone().wait();
+    tokio::pin!(stop);
+
+    loop {
+        let started = Instant::now();
+        match live.clone().relay(redis.clone_new()).await {
+            Ok(()) if shutdown.is_draining() => return,
+            Ok(()) => warn!("live updates stopped coming from redis"),
+            Err(e) => warn!("failed to relay live updates: {}", e),
+        }
+
+        if started.elapsed() > RELAY_MAX_BACKOFF {
+            backoff = RELAY_MIN_BACKOFF;
+        }
+        info!("subscribing to live updates again in {:?}", backoff);
+
+        tokio::select! {
+            _ = &mut stop => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(RELAY_MAX_BACKOFF);
+    }
 }
@@ -0,0 +1,114 @@
+use std::time::Instant;
+
+use axum::{extract::MatchedPath, http::Request, middleware::Next, response::Response};
+use fred::{clients::RedisClient, interfaces::MetricsInterface};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::PgPool;
+
+/// Buckets for `http_request_duration_seconds`, from 5ms up to 10s.
+const REQUEST_DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Renders everything recorded through the `metrics` macros in the Prometheus
+/// text format, topped up with gauges that are sampled at scrape time.
+#[derive(Clone)]
+pub struct Exporter {
+    handle: PrometheusHandle,
+    token: Option<String>,
+    db: Option<PgPool>,
+    redis: Option<RedisClient>,
+}
+
+impl Exporter {
+    /// Installs the global recorder. This can only happen once per process.
+    pub fn install() -> anyhow::Result<Self> {
+        let handle = PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Full("http_request_duration_seconds".to_owned()),
+                REQUEST_DURATION_BUCKETS,
+            )?
+            .install_recorder()?;
+
+        Ok(Self {
+            handle,
+            token: None,
+            db: None,
+            redis: None,
+        })
+    }
+
+    /// Requires scrapers to send `Authorization: Bearer <token>`.
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Reports connection pool usage.
+    pub fn with_db(mut self, db: PgPool) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// Reports command latency for the client.
+    pub fn with_redis(mut self, redis: RedisClient) -> Self {
+        self.redis = Some(redis);
+        self
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn render(&self) -> String {
+        if let Some(db) = &self.db {
+            let size = db.size() as f64;
+            let idle = db.num_idle() as f64;
+
+            metrics::gauge!("db_pool_connections", idle, "state" => "idle");
+            metrics::gauge!("db_pool_connections", size - idle, "state" => "active");
+        }
+
+        if let Some(redis) = &self.redis {
+            // fred aggregates latency between reads, so fold each window into a
+            // running count and sum (in milliseconds) plus the window's max.
+            let stats = redis.take_latency_metrics();
+
+            metrics::counter!("redis_commands_total", stats.samples);
+            metrics::counter!(
+                "redis_command_latency_milliseconds_sum",
+                stats.sum.max(0) as u64
+            );
+            metrics::gauge!("redis_command_latency_milliseconds_max", stats.max as f64);
+        }
+
+        self.handle.render()
+    }
+}
+
+/// Counts requests and records their latency by method, matched route and
+/// status code.
+pub async fn track_requests<B>(request: Request<B>, next: Next<B>) -> Response {
+    // Label by route template rather than the raw path to keep cardinality down.
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let method = request.method().to_string();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+
+    metrics::increment_counter!("http_requests_total", &labels);
+    metrics::histogram!("http_request_duration_seconds", elapsed, &labels);
+
+    response
+}
@@ -0,0 +1,75 @@
+//! Login sessions in Redis, kept under their own [`KEY_PREFIX`] so that they
+//! can be told apart from everything else in there, as when purging them.
+
+use async_trait::async_trait;
+use fred::{
+    clients::RedisClient,
+    interfaces::KeysInterface,
+    types::{Expiration, RedisKey},
+};
+use tower_sessions::{
+    session::{Id, Session},
+    SessionStore,
+};
+
+/// What every session's key starts with, followed by the session id.
+pub const KEY_PREFIX: &str = "session:";
+
+#[derive(thiserror::Error, Debug)]
+pub enum RedisSessionError {
+    #[error("redis error: {0}")]
+    Redis(#[from] fred::error::RedisError),
+    #[error("failed to encode session: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error("failed to decode session: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+}
+
+/// Like [`tower_sessions::RedisStore`], but with [`KEY_PREFIX`] on the keys.
+#[derive(Debug, Clone)]
+pub struct RedisSessionStore {
+    client: RedisClient,
+}
+
+impl RedisSessionStore {
+    pub fn new(client: RedisClient) -> Self {
+        Self { client }
+    }
+}
+
+fn key(session_id: &Id) -> RedisKey {
+    format!("{}{}", KEY_PREFIX, session_id).into()
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    type Error = RedisSessionError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let expire = Expiration::EXAT(session.expiry_date().unix_timestamp());
+
+        self.client
+            .set::<(), _, _>(
+                key(session.id()),
+                rmp_serde::to_vec(session)?.as_slice(),
+                Some(expire),
+                None,
+                false,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let data: Option<Vec<u8>> = self.client.get(key(session_id)).await?;
+
+        Ok(data.map(|data| rmp_serde::from_slice(&data)).transpose()?)
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.client.del::<(), _>(key(session_id)).await?;
+
+        Ok(())
+    }
+}
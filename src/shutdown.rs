@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use tokio::{signal, sync::watch};
+
+use crate::health::HealthCheck;
+
+/// Shared flag flipped once the server starts shutting down.
+///
+/// Registered as a [`HealthCheck`] so that `/readyz` reports the instance as
+/// unavailable while in-flight requests are drained.
+#[derive(Clone)]
+pub struct Shutdown {
+    draining: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (draining, _) = watch::channel(false);
+        Self {
+            draining: Arc::new(draining),
+        }
+    }
+
+    pub fn begin(&self) {
+        self.draining.send_replace(true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// Resolves once [`Shutdown::begin`] has been called.
+    pub async fn wait(self) {
+        let mut draining = self.draining.subscribe();
+        // The sender lives in `self`, so this can't fail.
+        let _ = draining.wait_for(|draining| *draining).await;
+    }
+}
+
+#[async_trait]
+impl HealthCheck for Shutdown {
+    fn name(&self) -> &'static str {
+        "shutdown"
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        if self.is_draining() {
+            bail!("draining connections");
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves on SIGINT or, on Unix, SIGTERM (which is what Fly sends when
+/// stopping a machine).
+pub async fn signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install SIGINT handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
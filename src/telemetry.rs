@@ -0,0 +1,229 @@
+use std::env;
+
+use anyhow::{bail, Context};
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::{TraceContextExt, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{self, Sampler, Tracer, TracerProvider},
+    Resource,
+};
+use tracing::{field, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::{fmt::MakeWriter, registry::LookupSpan, Layer};
+
+use crate::data::user::AuthSession;
+
+/// The header request IDs are read from and echoed back in.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+pub const DEFAULT_SERVICE_NAME: &str = "flyio-rust";
+
+/// How log lines are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines, for local development.
+    Text,
+    /// One JSON object per line, carrying the fields of every enclosing span.
+    Json,
+}
+
+impl LogFormat {
+    /// Reads `LOG_FORMAT` (`text` or `json`), defaulting to text.
+    pub fn from_env() -> anyhow::Result<Self> {
+        match env::var("LOG_FORMAT").as_deref() {
+            Err(_) | Ok("text") => Ok(Self::Text),
+            Ok("json") => Ok(Self::Json),
+            Ok(other) => bail!("invalid LOG_FORMAT: {}", other),
+        }
+    }
+}
+
+/// A `tracing` layer that writes log lines in the given format.
+pub fn log_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+
+    match format {
+        LogFormat::Text => layer.boxed(),
+        // The whole span list rather than just the current span, so that lines
+        // logged from inside e.g. a query span still carry the request's fields.
+        LogFormat::Json => layer
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
+
+/// Where and how to export spans over OTLP/HTTP.
+#[derive(Clone, Debug)]
+pub struct OtlpConfig {
+    /// Base URL of the collector; `/v1/traces` is appended.
+    pub endpoint: String,
+    pub service_name: String,
+    /// Fraction of new traces to sample, between 0 and 1. Traces started by a
+    /// caller follow the caller's sampling decision.
+    pub sampling_ratio: f64,
+}
+
+impl OtlpConfig {
+    /// Reads the standard `OTEL_*` variables. Export is disabled unless
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") else {
+            return Ok(None);
+        };
+
+        let service_name =
+            env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_owned());
+
+        let sampling_ratio = env::var("OTEL_TRACES_SAMPLER_ARG")
+            .map(|x| x.parse::<f64>())
+            .unwrap_or(Ok(1.0))
+            .context("invalid OTEL_TRACES_SAMPLER_ARG")?;
+
+        Ok(Some(Self {
+            endpoint,
+            service_name,
+            sampling_ratio,
+        }))
+    }
+}
+
+/// Builds the tracer provider behind [`layer`].
+///
+/// Without a config, spans still get trace IDs (so that propagation and the
+/// `traceId` in error responses work) but are never exported.
+pub fn tracer_provider(config: Option<&OtlpConfig>) -> anyhow::Result<TracerProvider> {
+    let Some(config) = config else {
+        return Ok(TracerProvider::builder().build());
+    };
+
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(&config.endpoint)
+        .build_span_exporter()
+        .context("failed to build OTLP exporter")?;
+
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sampling_ratio)));
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_config(
+            trace::config()
+                .with_sampler(sampler)
+                .with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    config.service_name.clone(),
+                )])),
+        )
+        .build())
+}
+
+/// A `tracing` layer that turns spans into OpenTelemetry spans.
+pub fn layer<S>(provider: &TracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(DEFAULT_SERVICE_NAME))
+}
+
+/// Creates the span for an incoming request, continuing the caller's trace if
+/// they sent a W3C `traceparent` header.
+///
+/// `user_id` is filled in by [`record_user`] once the session has been loaded.
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or("unmatched");
+
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id,
+        route,
+        user_id = field::Empty,
+    );
+
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(request.headers()));
+    span.set_parent(parent);
+
+    span
+}
+
+/// The span created by [`make_span`], stashed in the request's extensions by
+/// [`stash_span`].
+///
+/// Middleware further in (like the session layer) enters spans of its own, so
+/// by the time a handler runs `Span::current()` is no longer the request span.
+#[derive(Clone)]
+pub struct RequestSpan(pub Span);
+
+pub fn stash_span<B>(mut request: Request<B>) -> Request<B> {
+    request
+        .extensions_mut()
+        .insert(RequestSpan(Span::current()));
+    request
+}
+
+/// Records the logged in user on the request span.
+pub async fn record_user<B>(
+    auth_session: AuthSession,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let span = request.extensions().get::<RequestSpan>();
+
+    if let (Some(user), Some(RequestSpan(span))) = (&auth_session.user, span) {
+        span.record("user_id", field::display(user.user_id));
+    }
+
+    next.run(request).await
+}
+
+/// The trace ID of the current span, if it has one.
+pub fn current_trace_id() -> Option<String> {
+    let context = Span::current().context();
+    let span_context = context.span().span_context().clone();
+
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
@@ -1,4 +1,9 @@
-use crate::data::{todo::Todo, user::User};
+use crate::api::listing::ListParams;
+use crate::data::{
+    archive::ArchivedDay, audit::AuditEvent, history::TodoEvent, lists::ListSummary,
+    priority::Priority, search::TodoMatch, shorthand::Shorthand, subtasks::Subtasks,
+    tags::TagSummary, todo::Todo, user::User,
+};
 use askama::Template;
 
 #[derive(Template)]
@@ -12,6 +17,70 @@ pub struct NotFoundTemplate<'a> {
 pub struct TodosTemplate<'a> {
     pub user: &'a Option<User>,
     pub todos: &'a Vec<Todo>,
+    pub subtasks: &'a Subtasks,
+    pub next_page: Option<String>,
+    pub params: &'a ListParams,
+    /// To filter by.
+    pub tags: &'a Vec<TagSummary>,
+    pub lists: &'a Vec<ListSummary>,
+}
+
+#[derive(Template)]
+#[template(path = "tags.html")]
+pub struct TagsTemplate<'a> {
+    pub user: &'a Option<User>,
+    pub tags: &'a Vec<TagSummary>,
+}
+
+#[derive(Template)]
+#[template(path = "partial/tags.html")]
+pub struct PartialTagsTemplate<'a> {
+    pub tags: &'a Vec<TagSummary>,
+}
+
+#[derive(Template)]
+#[template(path = "trash.html")]
+pub struct TrashTemplate<'a> {
+    pub user: &'a Option<User>,
+    pub todos: &'a Vec<Todo>,
+    /// How many days deleted todos are kept for.
+    pub retention_days: u32,
+}
+
+#[derive(Template)]
+#[template(path = "partial/trash.html")]
+pub struct PartialTrashTemplate<'a> {
+    pub todos: &'a Vec<Todo>,
+}
+
+/// Shown after deleting a todo, with a button to restore it.
+#[derive(Template)]
+#[template(path = "partial/trash_toast.html")]
+pub struct TrashToastTemplate<'a> {
+    pub todo: &'a Todo,
+}
+
+#[derive(Template)]
+#[template(path = "archive.html")]
+pub struct ArchiveTemplate<'a> {
+    pub user: &'a Option<User>,
+    pub days: &'a Vec<ArchivedDay>,
+    /// After how many days done todos are archived, if they are.
+    pub auto_archive_days: Option<u32>,
+}
+
+/// Shown under a todo.
+#[derive(Template)]
+#[template(path = "partial/todo_history.html")]
+pub struct TodoHistoryTemplate<'a> {
+    pub events: &'a Vec<TodoEvent>,
+}
+
+#[derive(Template)]
+#[template(path = "security.html")]
+pub struct SecurityTemplate<'a> {
+    pub user: &'a Option<User>,
+    pub events: &'a Vec<AuditEvent>,
 }
 
 #[derive(Template)]
@@ -30,16 +99,48 @@ pub struct SignupTemplate<'a> {
 #[template(path = "partial/todos.html")]
 pub struct PartialTodosTemplate<'a> {
     pub todos: &'a Vec<Todo>,
+    pub subtasks: &'a Subtasks,
+    /// Loaded once the end of the list scrolls into view.
+    pub next_page: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "partial/search_results.html")]
+pub struct SearchResultsTemplate<'a> {
+    pub results: &'a Vec<TodoMatch>,
 }
 
 #[derive(Template)]
 #[template(path = "partial/todo.html")]
 pub struct SingleTodoTemplate<'a> {
     pub todo: &'a Todo,
+    /// Everything nested under `todo`, rendered along with it.
+    pub subtasks: &'a Subtasks,
+}
+
+#[derive(Template)]
+#[template(path = "partial/quick_add_preview.html")]
+pub struct QuickAddPreviewTemplate<'a> {
+    pub parsed: &'a Shorthand,
 }
 
 #[derive(Template)]
 #[template(path = "partial/todo_edit.html")]
 pub struct EditTodoTemplate<'a> {
     pub todo: &'a Todo,
+    /// Why the form is shown again, when an edit was refused.
+    pub conflict: Option<&'a str>,
+}
+
+mod filters {
+    use askama::Template;
+
+    use super::SingleTodoTemplate;
+    use crate::data::{subtasks::Subtasks, todo::Todo};
+
+    /// Renders a subtask in full. `partial/todo.html` can't include itself, as
+    /// includes are expanded when the templates are compiled.
+    pub fn subtask(todo: &Todo, subtasks: &Subtasks) -> askama::Result<String> {
+        SingleTodoTemplate { todo, subtasks }.render()
+    }
 }
@@ -3,6 +3,8 @@ use validator::*;
 
 lazy_static::lazy_static! {
     pub static ref RE_SPECIAL_CHAR: Regex = Regex::new(r"^.*?[@$!%*?&].*$").unwrap();
+    /// What a `<input type="color">` submits.
+    pub static ref RE_COLOR: Regex = Regex::new(r"^#[0-9a-f]{6}$").unwrap();
 }
 
 pub fn validate_password(password: &str) -> Result<(), ValidationError> {
@@ -15,12 +17,12 @@ pub fn validate_password(password: &str) -> Result<(), ValidationError> {
         has_whitespace |= c.is_whitespace();
         has_lower |= c.is_lowercase();
         has_upper |= c.is_uppercase();
-        has_digit |= c.is_digit(10);
+        has_digit |= c.is_ascii_digit();
     }
 
     if !has_whitespace && has_upper && has_lower && has_digit && password.len() >= 8 {
         Ok(())
     } else {
-        return Err(ValidationError::new("Password does not meet requirements"));
+        Err(ValidationError::new("Password does not meet requirements"))
     }
 }
@@ -0,0 +1,263 @@
+use axum::http::StatusCode;
+use uuid::Uuid;
+
+mod common;
+
+use common::{todo_ids, TestApp, PASSWORD};
+
+#[tokio::test]
+async fn index_redirects_to_login_when_logged_out() {
+    let app = TestApp::new().await;
+
+    let response = app.client().get("/").await;
+
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.location(), Some("/login"));
+}
+
+#[tokio::test]
+async fn index_redirects_to_todos_when_logged_in() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let response = client.get("/").await;
+
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.location(), Some("/todos"));
+}
+
+#[tokio::test]
+async fn signup_rejects_invalid_email() {
+    let app = TestApp::new().await;
+
+    let response = app.client().signup("not-an-email", PASSWORD).await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn signup_rejects_duplicate_email() {
+    let app = TestApp::new().await;
+    let mut client = app.client();
+
+    let response = client.signup("dupe@example.com", PASSWORD).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.location(), Some("/login"));
+
+    let response = client.signup("dupe@example.com", PASSWORD).await;
+    assert!(!response.status.is_success() && !response.status.is_redirection());
+}
+
+#[tokio::test]
+async fn login_with_wrong_password_does_not_authenticate() {
+    let app = TestApp::new().await;
+    let mut client = app.client();
+    client.signup("wrong@example.com", PASSWORD).await;
+
+    let response = client.login("wrong@example.com", "Wr0ngPassword!").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Login"));
+
+    let response = client.get("/todos").await;
+    assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+}
+
+#[tokio::test]
+async fn logout_ends_the_session() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    assert!(client.is_logged_in());
+
+    let response = client.get("/logout").await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.location(), Some("/login"));
+    assert!(!client.is_logged_in());
+
+    let response = client.get("/todos").await;
+    assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+}
+
+#[tokio::test]
+async fn todos_require_login() {
+    let app = TestApp::new().await;
+
+    let response = app.client().get("/todos").await;
+
+    assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+    assert!(response.location().unwrap().starts_with("/login"));
+}
+
+#[tokio::test]
+async fn todo_crud() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let response = client.post("/todos", &[("content", "Buy milk")]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Buy milk"));
+
+    let response = client.post("/todos", &[("content", "Walk <dog>")]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Walk &lt;dog&gt;"));
+
+    let ids = todo_ids(&response.body);
+    assert_eq!(ids.len(), 2);
+
+    let response = client.get(&format!("/todos/{}/edit", ids[0])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("value=\"Buy milk\""));
+
+    let response = client
+        .put(
+            &format!("/todos/{}", ids[0]),
+            &[("content", "Buy oat milk")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Buy oat milk"));
+
+    let response = client.delete(&format!("/todos/{}", ids[1])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    // Nothing takes its place but the toast to undo it.
+    assert!(todo_ids(&response.body).is_empty());
+    assert!(response.body.contains("Undo"));
+
+    let response = client.get("/todos").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(todo_ids(&response.body), vec![ids[0]]);
+    assert!(response.body.contains("Buy oat milk"));
+    assert!(!response.body.contains("Walk"));
+}
+
+#[tokio::test]
+async fn todo_content_is_validated() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let response = client.post("/todos", &[("content", "")]).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let too_long = "x".repeat(1001);
+    let response = client.post("/todos", &[("content", &too_long)]).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn toggling_flips_done() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let response = client.post("/todos", &[("content", "Water plants")]).await;
+    let id = todo_ids(&response.body)[0];
+    assert!(!response.body.contains("checked"));
+
+    let response = client.post(&format!("/todos/{}/toggle", id), &[]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("checked"));
+
+    let response = client.post(&format!("/todos/{}/toggle", id), &[]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.body.contains("checked"));
+}
+
+#[tokio::test]
+async fn users_cannot_touch_each_others_todos() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut mallory = app.logged_in_client().await;
+
+    let response = alice.post("/todos", &[("content", "Alice secret")]).await;
+    let id = todo_ids(&response.body)[0];
+
+    let response = mallory.get("/todos").await;
+    assert!(todo_ids(&response.body).is_empty());
+    assert!(!response.body.contains("Alice secret"));
+
+    let before = alice.get(&format!("/api/v1/todos/{}", id)).await.body;
+
+    let response = mallory.get(&format!("/todos/{}/edit", id)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = mallory
+        .put(&format!("/todos/{}", id), &[("content", "pwned")])
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = mallory.post(&format!("/todos/{}/toggle", id), &[]).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // Deleting a todo that's already gone isn't an error, and leaves nothing
+    // to swap in.
+    let response = mallory.delete(&format!("/todos/{}", id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.is_empty());
+
+    // Not even its version has moved.
+    let after = alice.get(&format!("/api/v1/todos/{}", id)).await.body;
+    assert_eq!(after, before);
+
+    let response = alice.get("/todos").await;
+    assert_eq!(todo_ids(&response.body), vec![id]);
+    assert!(response.body.contains("Alice"));
+    assert!(!response.body.contains("pwned"));
+
+    // The page's scripts deal with checkboxes too, so look at the list alone.
+    let response = alice.get("/todos/page").await;
+    assert!(!response.body.contains("checked"));
+}
+
+#[tokio::test]
+async fn missing_and_other_users_todos_are_not_found() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut mallory = app.logged_in_client().await;
+
+    let response = alice.post("/todos", &[("content", "Alice secret")]).await;
+    let id = todo_ids(&response.body)[0];
+
+    for (client, id) in [(&mut mallory, id), (&mut alice, Uuid::new_v4())] {
+        let response = client.get(&format!("/api/v1/todos/{}", id)).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert!(response.body.contains("not found"));
+    }
+}
+
+#[tokio::test]
+async fn unknown_routes_render_not_found() {
+    let app = TestApp::new().await;
+
+    let response = app.client().get("/does-not-exist").await;
+
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert!(response.body.contains("Not found!"));
+}
+
+#[tokio::test]
+async fn probes_do_not_touch_the_session() {
+    let app = TestApp::new().await;
+    let mut client = app.client();
+
+    let response = client.get("/healthz").await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = client.get("/readyz").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.starts_with(r#"{"status":"ok""#));
+
+    assert!(!client.is_logged_in());
+}
+
+#[tokio::test]
+async fn request_ids_are_echoed_or_generated() {
+    let app = TestApp::new().await;
+
+    let response = app.client().get("/login").await;
+    let generated = response.headers["x-request-id"].to_str().unwrap();
+    assert!(generated.parse::<uuid::Uuid>().is_ok());
+
+    let mut client = app
+        .client()
+        .with_header("x-request-id", "support-ticket-42");
+    let response = client.get("/login").await;
+    assert_eq!(response.headers["x-request-id"], "support-ticket-42");
+}
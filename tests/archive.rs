@@ -0,0 +1,110 @@
+use axum::http::StatusCode;
+use flyio_rust::data::archive;
+use serde_json::Value;
+use time::{Duration, OffsetDateTime};
+
+mod common;
+
+use common::{add_todo, contents, json, todo_ids, TestApp};
+
+#[tokio::test]
+async fn completing_a_todo_records_when() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let todo_id = add_todo(&mut client, "Water plants", None).await;
+    assert_eq!(
+        json(&mut client, "/api/v1/todos").await[0]["completedAt"],
+        Value::Null
+    );
+
+    client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    let completed_at = json(&mut client, "/api/v1/todos").await[0]["completedAt"].clone();
+    assert!(completed_at.is_string());
+
+    client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    assert_eq!(
+        json(&mut client, "/api/v1/todos").await[0]["completedAt"],
+        Value::Null
+    );
+}
+
+#[tokio::test]
+async fn completed_todos_can_be_archived_and_brought_back() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let parent = add_todo(&mut client, "Plan trip", None).await;
+    add_todo(&mut client, "Book flights", Some(&parent)).await;
+    let open = add_todo(&mut client, "Water plants", None).await;
+    client
+        .post(&format!("/todos/{}/complete", parent), &[])
+        .await;
+
+    let response = client.post("/archive", &[("status", "all")]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(todo_ids(&response.body), vec![open.parse().unwrap()]);
+
+    // The subtask went with its parent, and is only listed through it.
+    assert_eq!(
+        contents(&json(&mut client, "/api/v1/todos").await),
+        ["Water plants"]
+    );
+    let archived = json(&mut client, "/api/v1/archive").await;
+    assert_eq!(contents(&archived), ["Plan trip"]);
+    assert!(archived[0]["archivedAt"].is_string());
+
+    let response = client.get("/archive").await;
+    assert!(response.body.contains("Plan trip"));
+    let today = OffsetDateTime::now_utc().date();
+    let heading = format!("{} {}, {}", today.month(), today.day(), today.year());
+    assert!(response.body.contains(&heading));
+
+    let response = client
+        .post(&format!("/archive/{}/restore", parent), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["HX-Trigger"], "todos-changed");
+    assert_eq!(
+        contents(&json(&mut client, "/api/v1/todos").await),
+        ["Plan trip", "Book flights", "Water plants"]
+    );
+
+    let response = client
+        .post(&format!("/archive/{}/restore", parent), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn todos_done_long_enough_ago_are_archived() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else {
+        return;
+    };
+    let mut client = app.logged_in_client().await;
+
+    let todo_id = add_todo(&mut client, "Call mum", None).await;
+    add_todo(&mut client, "Call the bank", None).await;
+    client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+
+    let archived = archive::auto_archive(db, OffsetDateTime::now_utc() - Duration::days(1))
+        .await
+        .unwrap();
+    assert_eq!(archived, 0);
+
+    let archived = archive::auto_archive(db, OffsetDateTime::now_utc() + Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(archived, 1);
+    assert_eq!(
+        contents(&json(&mut client, "/api/v1/archive").await),
+        ["Call mum"]
+    );
+}
@@ -0,0 +1,281 @@
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+mod common;
+
+use common::{todo_ids, TestApp, TestClient};
+
+/// Adds todos and returns their ids, in order.
+async fn add_todos(client: &mut TestClient, contents: &[&str]) -> Vec<String> {
+    for content in contents {
+        let response = client.post("/todos", &[("content", content)]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    let mut ids = vec![];
+    for content in contents {
+        ids.push(id_of(client, content).await);
+    }
+    ids
+}
+
+async fn id_of(client: &mut TestClient, content: &str) -> String {
+    todos(client)
+        .await
+        .iter()
+        .find(|todo| todo["content"] == content)
+        .unwrap()["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+async fn todos(client: &mut TestClient) -> Vec<Value> {
+    let response = client.get("/api/v1/todos?status=all").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+    body["todos"].as_array().unwrap().clone()
+}
+
+async fn bulk(client: &mut TestClient, request: Value) -> Vec<(String, String)> {
+    let response = client.post_json("/api/v1/todos/bulk", &request).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+    body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| {
+            (
+                result["todoId"].as_str().unwrap().to_owned(),
+                result["status"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+fn statuses(results: &[(String, String)]) -> Vec<&str> {
+    results.iter().map(|(_, status)| status.as_str()).collect()
+}
+
+#[tokio::test]
+async fn many_todos_can_be_done_at_once() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, &["Call mum", "Call the bank"]).await;
+    let mut other = app.logged_in_client().await;
+    let theirs = add_todos(&mut other, &["Someone else's"]).await;
+    let missing = Uuid::new_v4().to_string();
+
+    let results = bulk(
+        &mut client,
+        json!({ "todoIds": [ids[0], ids[1], theirs[0], missing], "action": "done" }),
+    )
+    .await;
+    assert_eq!(results[3].0, missing);
+    assert_eq!(
+        statuses(&results),
+        ["updated", "updated", "not_found", "not_found"]
+    );
+    assert!(todos(&mut client)
+        .await
+        .iter()
+        .all(|todo| todo["done"] == true));
+    assert_eq!(todos(&mut other).await[0]["done"], false);
+
+    let results = bulk(
+        &mut client,
+        json!({ "todoIds": [ids[0]], "action": "done" }),
+    )
+    .await;
+    assert_eq!(statuses(&results), ["unchanged"]);
+
+    let results = bulk(
+        &mut client,
+        json!({ "todoIds": [ids[0]], "action": "undone" }),
+    )
+    .await;
+    assert_eq!(statuses(&results), ["updated"]);
+    assert_eq!(todos(&mut client).await[0]["done"], false);
+}
+
+#[tokio::test]
+async fn tags_lists_and_due_dates_can_be_changed_at_once() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, &["Call mum", "Call the bank"]).await;
+    bulk(
+        &mut client,
+        json!({ "todoIds": [ids[0]], "action": "add_tag", "tagName": "family" }),
+    )
+    .await;
+
+    let results = bulk(
+        &mut client,
+        json!({ "todoIds": ids, "action": "add_tag", "tagName": "#Phone" }),
+    )
+    .await;
+    assert_eq!(statuses(&results), ["updated", "updated"]);
+    let tags: Vec<_> = todos(&mut client)
+        .await
+        .iter()
+        .map(|todo| todo["tags"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(tags, [2, 1]);
+
+    let results = bulk(
+        &mut client,
+        json!({ "todoIds": ids, "action": "remove_tag", "tagName": "family" }),
+    )
+    .await;
+    assert_eq!(statuses(&results), ["updated", "unchanged"]);
+
+    bulk(
+        &mut client,
+        json!({ "todoIds": ids, "action": "move_to_list", "listName": "@calls" }),
+    )
+    .await;
+    bulk(
+        &mut client,
+        json!({ "todoIds": ids, "action": "set_due", "due": "2023-12-24T09:00" }),
+    )
+    .await;
+    for todo in todos(&mut client).await {
+        assert_eq!(todo["tags"][0]["name"], "phone");
+        assert_eq!(todo["list"], "calls");
+        assert_eq!(todo["dueAt"], "2023-12-24T09:00:00Z");
+        assert_eq!(
+            todo["version"],
+            if todo["todoId"] == ids[0] { 6 } else { 4 }
+        );
+    }
+
+    bulk(
+        &mut client,
+        json!({ "todoIds": ids, "action": "move_to_list", "listName": "" }),
+    )
+    .await;
+    bulk(
+        &mut client,
+        json!({ "todoIds": ids, "action": "set_due", "due": "" }),
+    )
+    .await;
+    for todo in todos(&mut client).await {
+        assert_eq!(todo["list"], Value::Null);
+        assert_eq!(todo["dueAt"], Value::Null);
+    }
+}
+
+#[tokio::test]
+async fn bulk_changes_are_kept_in_each_todos_history() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, &["Call mum", "Call the bank"]).await;
+
+    for request in [
+        json!({ "todoIds": ids, "action": "move_to_list", "listName": "@calls" }),
+        json!({ "todoIds": ids, "action": "add_tag", "tagName": "phone" }),
+        json!({ "todoIds": ids, "action": "remove_tag", "tagName": "phone" }),
+        json!({ "todoIds": ids, "action": "set_due", "due": "2023-12-24T09:00" }),
+        json!({ "todoIds": ids, "action": "done" }),
+    ] {
+        bulk(&mut client, request).await;
+    }
+
+    for id in &ids {
+        let response = client.get(&format!("/api/v1/todos/{}/history", id)).await;
+        let history: Vec<Value> = serde_json::from_str(&response.body).unwrap();
+        let kinds: Vec<_> = history.iter().map(|event| &event["kind"]).collect();
+        assert_eq!(
+            kinds,
+            [
+                "created",
+                "moved",
+                "retagged",
+                "retagged",
+                "rescheduled",
+                "toggled"
+            ]
+        );
+    }
+}
+
+#[tokio::test]
+async fn deleted_todos_go_to_the_trash_with_their_subtasks() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, &["Plan trip", "Water plants"]).await;
+    client
+        .post(
+            "/todos",
+            &[("content", "Book flights"), ("parent", &ids[0])],
+        )
+        .await;
+    let child = id_of(&mut client, "Book flights").await;
+
+    let results = bulk(
+        &mut client,
+        json!({ "todoIds": [ids[0], child], "action": "delete" }),
+    )
+    .await;
+    assert_eq!(statuses(&results), ["updated", "updated"]);
+
+    let left: Vec<_> = todos(&mut client)
+        .await
+        .iter()
+        .map(|todo| todo["content"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(left, ["Water plants"]);
+
+    let response = client.get("/api/v1/trash").await;
+    assert!(response.body.contains("Plan trip"));
+}
+
+#[tokio::test]
+async fn the_list_is_rerendered_after_a_bulk_change() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, &["Call mum", "Call the bank", "Water plants"]).await;
+
+    let response = client
+        .post(
+            "/todos/bulk",
+            &[
+                ("todo_id", &ids[0]),
+                ("todo_id", &ids[2]),
+                ("action", "done"),
+                ("status", "open"),
+            ],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(todo_ids(&response.body), vec![ids[1].parse().unwrap()]);
+}
+
+#[tokio::test]
+async fn bulk_changes_are_checked() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, &["Call mum"]).await;
+
+    for request in [
+        json!({ "todoIds": [], "action": "done" }),
+        json!({ "todoIds": ids, "action": "explode" }),
+        json!({ "todoIds": ids, "action": "add_tag", "tagName": "not a tag" }),
+        json!({ "todoIds": ids, "action": "set_due", "due": "tomorrow" }),
+    ] {
+        let response = client.post_json("/api/v1/todos/bulk", &request).await;
+        assert_eq!(
+            response.status,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{}",
+            request
+        );
+    }
+
+    let response = client
+        .post("/todos/bulk", &[("todo_id", "nope"), ("action", "done")])
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,233 @@
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+mod common;
+
+use common::{TestApp, TestClient, TestSocket};
+
+/// Opens the change feed and pulls everything since `since`, returning the
+/// first reply.
+async fn pull(client: &mut TestClient, since: i64) -> (TestSocket, Value) {
+    let mut socket = client.websocket("/api/v1/changes").await;
+    socket
+        .send(&json!({ "type": "pull", "since": since }))
+        .await;
+    let reply = socket.next().await.expect("no changes came");
+    assert_eq!(reply["type"], "changes", "{}", reply);
+
+    (socket, reply)
+}
+
+/// Pushes the mutations and returns the result of each.
+async fn push(socket: &mut TestSocket, mutations: Value) -> Vec<Value> {
+    socket
+        .send(&json!({ "type": "push", "mutations": mutations }))
+        .await;
+
+    // Changes announced along the way may come first.
+    loop {
+        let reply = socket.next().await.expect("no reply came");
+        match reply["type"].as_str() {
+            Some("pushed") => return reply["results"].as_array().unwrap().clone(),
+            Some("changes") => continue,
+            _ => panic!("unexpected reply: {}", reply),
+        }
+    }
+}
+
+fn create(todo_id: Uuid, content: &str) -> Value {
+    json!({
+        "mutationId": Uuid::new_v4(),
+        "op": "create",
+        "todoId": todo_id,
+        "content": content,
+    })
+}
+
+#[tokio::test]
+async fn pulling_from_scratch_gets_every_todo() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    client.post("/todos", &[("content", "Call mum")]).await;
+    client.post("/todos", &[("content", "Call the bank")]).await;
+
+    let (_, reply) = pull(&mut client, 0).await;
+
+    let changes = reply["changes"].as_array().unwrap();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0]["todo"]["content"], "Call mum");
+    assert_eq!(changes[1]["todo"]["content"], "Call the bank");
+    assert_eq!(reply["seq"], changes[1]["seq"]);
+
+    // Nothing's new since then.
+    let (_, reply) = pull(&mut client, reply["seq"].as_i64().unwrap()).await;
+    assert_eq!(reply["changes"], json!([]));
+}
+
+#[tokio::test]
+async fn pushed_todos_keep_their_client_ids() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let (mut socket, _) = pull(&mut client, 0).await;
+    let todo_id = Uuid::new_v4();
+
+    let results = push(&mut socket, json!([create(todo_id, "Call mum")])).await;
+
+    assert_eq!(results[0]["status"], "applied", "{:?}", results);
+    assert_eq!(results[0]["todo"]["todoId"], todo_id.to_string());
+    let response = client.get("/api/v1/todos").await;
+    assert!(response.body.contains(&todo_id.to_string()));
+
+    // Say the reply was lost and the client tries again.
+    let results = push(&mut socket, json!([create(todo_id, "Call mum")])).await;
+    assert_eq!(results[0]["status"], "conflict");
+    assert_eq!(results[0]["todo"]["content"], "Call mum");
+}
+
+#[tokio::test]
+async fn edits_to_stale_versions_conflict() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let (mut socket, _) = pull(&mut client, 0).await;
+    let todo_id = Uuid::new_v4();
+    let results = push(&mut socket, json!([create(todo_id, "Call mum")])).await;
+    let version = results[0]["todo"]["version"].as_i64().unwrap();
+
+    let edit = |content: &str| {
+        json!({
+            "mutationId": Uuid::new_v4(),
+            "op": "update",
+            "todoId": todo_id,
+            "baseVersion": version,
+            "content": content,
+            "done": true,
+        })
+    };
+    let results = push(&mut socket, json!([edit("Call dad"), edit("Call gran")])).await;
+
+    assert_eq!(results[0]["status"], "applied");
+    assert_eq!(results[0]["todo"]["content"], "Call dad");
+    assert_eq!(results[0]["todo"]["done"], true);
+    assert_eq!(results[1]["status"], "conflict");
+    assert_eq!(results[1]["todo"]["content"], "Call dad");
+
+    let results = push(
+        &mut socket,
+        json!([{
+            "mutationId": Uuid::new_v4(),
+            "op": "delete",
+            "todoId": todo_id,
+            "baseVersion": version,
+        }]),
+    )
+    .await;
+    assert_eq!(results[0]["status"], "conflict");
+}
+
+#[tokio::test]
+async fn stale_edits_that_tick_a_todo_off_change_nothing() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let (mut socket, _) = pull(&mut client, 0).await;
+    let todo_id = Uuid::new_v4();
+    push(&mut socket, json!([create(todo_id, "Call mum")])).await;
+
+    // Edited in a browser in the meantime.
+    let response = client
+        .put(&format!("/todos/{}", todo_id), &[("content", "Call dad")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let results = push(
+        &mut socket,
+        json!([{
+            "mutationId": Uuid::new_v4(),
+            "op": "update",
+            "todoId": todo_id,
+            "baseVersion": 1,
+            "content": "Call gran",
+            "done": true,
+        }]),
+    )
+    .await;
+
+    assert_eq!(results[0]["status"], "conflict");
+    assert_eq!(results[0]["todo"]["content"], "Call dad");
+    assert_eq!(results[0]["todo"]["done"], false);
+    assert_eq!(results[0]["todo"]["version"], 2);
+}
+
+#[tokio::test]
+async fn invalid_mutations_are_rejected() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let (mut socket, _) = pull(&mut client, 0).await;
+
+    let results = push(
+        &mut socket,
+        json!([
+            create(Uuid::new_v4(), ""),
+            {
+                "mutationId": Uuid::new_v4(),
+                "op": "update",
+                "todoId": Uuid::new_v4(),
+                "baseVersion": 1,
+                "content": "Call dad",
+            },
+            create(Uuid::new_v4(), "Call mum"),
+        ]),
+    )
+    .await;
+
+    assert_eq!(results[0]["status"], "rejected");
+    assert!(results[0]["message"].is_string());
+    assert_eq!(results[1]["status"], "not_found");
+    // The rest still go through.
+    assert_eq!(results[2]["status"], "applied");
+}
+
+#[tokio::test]
+async fn changes_are_streamed_after_a_pull() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let (mut socket, reply) = pull(&mut client, 0).await;
+    let seq = reply["seq"].as_i64().unwrap();
+
+    client.post("/todos", &[("content", "Call mum")]).await;
+    let reply = socket.next().await.expect("no changes came");
+    assert_eq!(reply["type"], "changes");
+    assert_eq!(reply["changes"][0]["todo"]["content"], "Call mum");
+    assert!(reply["seq"].as_i64().unwrap() > seq);
+    let todo_id = reply["changes"][0]["todoId"].as_str().unwrap().to_owned();
+
+    let response = client.delete(&format!("/todos/{}", todo_id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let reply = socket.next().await.expect("no changes came");
+    assert_eq!(reply["changes"][0]["todoId"], todo_id.as_str());
+    assert_eq!(reply["changes"][0]["todo"], Value::Null);
+}
+
+#[tokio::test]
+async fn other_users_changes_are_not_synced() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut bob = app.logged_in_client().await;
+    bob.post("/todos", &[("content", "Bob's todo")]).await;
+
+    let (mut socket, reply) = pull(&mut alice, 0).await;
+    assert_eq!(reply["changes"], json!([]));
+
+    bob.post("/todos", &[("content", "Another of Bob's")]).await;
+    assert!(socket.next().await.is_none());
+}
+
+#[tokio::test]
+async fn the_change_feed_needs_a_login() {
+    let app = TestApp::new().await;
+    let mut client = app.client();
+
+    let response = client.get("/api/v1/changes").await;
+
+    assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+}
@@ -0,0 +1,458 @@
+//! Shared harness for the integration tests.
+//!
+//! Each [`TestApp`] builds the full router from [`flyio_rust::app`] with an
+//! in-memory session store. When `DATABASE_URL` is set the app runs against a
+//! throwaway Postgres schema that is migrated on creation and dropped when the
+//! `TestApp` goes away; otherwise it falls back to the in-memory repository so
+//! the suite can still run without a database.
+
+#![allow(dead_code)]
+
+use std::{env, time::Duration};
+
+use axum::{
+    body::{Body, BoxBody},
+    http::{header, request, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use axum_login::tower_sessions::MemoryStore;
+use flyio_rust::{app, data, AppState};
+use futures::{SinkExt, StreamExt};
+use hyper::body::HttpBody;
+use serde_json::Value;
+use sqlx::{postgres::PgPoolOptions, Executor, PgPool};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+pub const PASSWORD: &str = "Sup3rSecret!";
+
+pub struct TestApp {
+    pub router: Router,
+    schema: Option<TestSchema>,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        let (state, schema) = match env::var("DATABASE_URL") {
+            Ok(database_url) => {
+                let schema = TestSchema::create(&database_url).await;
+                (AppState::postgres(schema.db.clone()), Some(schema))
+            }
+            Err(_) => (AppState::memory(), None),
+        };
+
+        Self {
+            router: app(state, MemoryStore::default()),
+            schema,
+        }
+    }
+
+    /// The pool behind the app, when running against Postgres.
+    pub fn db(&self) -> Option<&PgPool> {
+        self.schema.as_ref().map(|schema| &schema.db)
+    }
+
+    /// A client with its own cookie jar, i.e. a separate browser.
+    pub fn client(&self) -> TestClient {
+        TestClient {
+            router: self.router.clone(),
+            cookie: None,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Signs up a fresh user with a unique email and returns a client that is
+    /// logged in as them.
+    pub async fn logged_in_client(&self) -> TestClient {
+        let email = format!("{}@example.com", Uuid::new_v4().simple());
+
+        let mut client = self.client();
+        assert_eq!(
+            client.signup(&email, PASSWORD).await.status,
+            StatusCode::SEE_OTHER
+        );
+        assert_eq!(
+            client.login(&email, PASSWORD).await.status,
+            StatusCode::SEE_OTHER
+        );
+
+        client
+    }
+}
+
+/// A Postgres schema private to a single test.
+struct TestSchema {
+    database_url: String,
+    name: String,
+    db: PgPool,
+}
+
+impl TestSchema {
+    async fn create(database_url: &str) -> Self {
+        let name = format!("test_{}", Uuid::new_v4().simple());
+
+        let admin = PgPool::connect(database_url).await.unwrap();
+        admin
+            .execute(format!("create schema {}", name).as_str())
+            .await
+            .unwrap();
+        admin.close().await;
+
+        let search_path = format!("set search_path to {}", name);
+        let db = PgPoolOptions::new()
+            .max_connections(5)
+            .after_connect(move |conn, _| {
+                let search_path = search_path.clone();
+                Box::pin(async move {
+                    conn.execute(search_path.as_str()).await?;
+                    Ok(())
+                })
+            })
+            .connect(database_url)
+            .await
+            .unwrap();
+
+        data::migrate(&db).await.unwrap();
+
+        Self {
+            database_url: database_url.to_owned(),
+            name,
+            db,
+        }
+    }
+}
+
+impl Drop for TestSchema {
+    fn drop(&mut self) {
+        // `Drop` can't be async and we're usually inside the test's runtime, so
+        // clean up from a separate thread with its own runtime.
+        let database_url = self.database_url.clone();
+        let statement = format!("drop schema if exists {} cascade", self.name);
+
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async move {
+                    let admin = PgPool::connect(&database_url).await.unwrap();
+                    admin.execute(statement.as_str()).await.unwrap();
+                    admin.close().await;
+                });
+        })
+        .join()
+        .unwrap();
+    }
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn location(&self) -> Option<&str> {
+        self.headers
+            .get(header::LOCATION)
+            .map(|value| value.to_str().unwrap())
+    }
+}
+
+/// Drives the router one request at a time, carrying the session cookie
+/// between requests like a browser would.
+pub struct TestClient {
+    router: Router,
+    cookie: Option<String>,
+    headers: HeaderMap,
+}
+
+impl TestClient {
+    /// Sends `name: value` with every subsequent request.
+    pub fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.insert(name, value.parse().unwrap());
+        self
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.cookie.is_some()
+    }
+
+    pub async fn signup(&mut self, email: &str, password: &str) -> TestResponse {
+        self.post("/signup", &[("email", email), ("password", password)])
+            .await
+    }
+
+    pub async fn login(&mut self, email: &str, password: &str) -> TestResponse {
+        self.post("/login", &[("email", email), ("password", password)])
+            .await
+    }
+
+    pub async fn get(&mut self, uri: &str) -> TestResponse {
+        self.send(Method::GET, uri, &[]).await
+    }
+
+    pub async fn post(&mut self, uri: &str, form: &[(&str, &str)]) -> TestResponse {
+        self.send(Method::POST, uri, form).await
+    }
+
+    pub async fn put(&mut self, uri: &str, form: &[(&str, &str)]) -> TestResponse {
+        self.send(Method::PUT, uri, form).await
+    }
+
+    pub async fn delete(&mut self, uri: &str) -> TestResponse {
+        self.send(Method::DELETE, uri, &[]).await
+    }
+
+    pub async fn post_json(&mut self, uri: &str, json: &serde_json::Value) -> TestResponse {
+        let body = ("application/json", json.to_string());
+        self.send_body(Method::POST, uri, Some(body)).await
+    }
+
+    /// Opens a stream of server-sent events, leaving it open for the test to
+    /// read from.
+    pub async fn events(&mut self, uri: &str) -> TestEvents {
+        let request = self.request(Method::GET, uri).body(Body::empty()).unwrap();
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        TestEvents {
+            body: response.into_body(),
+            buffered: String::new(),
+        }
+    }
+
+    /// Serves the app on a local port and opens a WebSocket to it, since
+    /// upgrades need a real connection.
+    pub async fn websocket(&mut self, uri: &str) -> TestSocket {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(self.router.clone().into_make_service());
+        tokio::spawn(server);
+
+        let mut request = format!("ws://{}{}", addr, uri)
+            .into_client_request()
+            .unwrap();
+        if let Some(cookie) = &self.cookie {
+            request
+                .headers_mut()
+                .insert(header::COOKIE, cookie.parse().unwrap());
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+        TestSocket { socket }
+    }
+
+    async fn send(&mut self, method: Method, uri: &str, form: &[(&str, &str)]) -> TestResponse {
+        let body =
+            (!form.is_empty()).then(|| ("application/x-www-form-urlencoded", encode_form(form)));
+        self.send_body(method, uri, body).await
+    }
+
+    /// Sends a request with a body of the given content type, if any.
+    async fn send_body(
+        &mut self,
+        method: Method,
+        uri: &str,
+        body: Option<(&str, String)>,
+    ) -> TestResponse {
+        let mut request = self.request(method, uri);
+
+        let body = match body {
+            Some((content_type, body)) => {
+                request = request.header(header::CONTENT_TYPE, content_type);
+                Body::from(body)
+            }
+            None => Body::empty(),
+        };
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+
+        if let Some(set_cookie) = response.headers().get(header::SET_COOKIE) {
+            let set_cookie = set_cookie.to_str().unwrap();
+            let cookie = set_cookie.split(';').next().unwrap();
+
+            // An expired cookie is how the session layer tells us to forget it.
+            self.cookie = if set_cookie.contains("Max-Age=0") {
+                None
+            } else {
+                Some(cookie.to_owned())
+            };
+        }
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        TestResponse {
+            status,
+            headers,
+            body: String::from_utf8(body.to_vec()).unwrap(),
+        }
+    }
+
+    /// A request carrying the client's headers and session cookie.
+    fn request(&self, method: Method, uri: &str) -> request::Builder {
+        let mut request = Request::builder().method(method).uri(uri);
+
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        if let Some(cookie) = &self.cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+
+        request
+    }
+}
+
+/// An open stream of server-sent events.
+pub struct TestEvents {
+    body: BoxBody,
+    buffered: String,
+}
+
+/// One server-sent event.
+#[derive(Debug)]
+pub struct TestEvent {
+    pub event: String,
+    pub data: String,
+}
+
+impl TestEvents {
+    /// The next event, or `None` if none comes within a few seconds.
+    pub async fn next(&mut self) -> Option<TestEvent> {
+        loop {
+            if let Some(end) = self.buffered.find("\n\n") {
+                let raw: String = self.buffered.drain(..end + 2).collect();
+                let field = |name: &str| {
+                    raw.lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .unwrap_or_default()
+                        .trim()
+                        .to_owned()
+                };
+
+                // Comments, such as keep-alives, aren't events.
+                if raw.starts_with(':') {
+                    continue;
+                }
+
+                return Some(TestEvent {
+                    event: field("event:"),
+                    data: field("data:"),
+                });
+            }
+
+            let chunk = tokio::time::timeout(Duration::from_secs(2), self.body.data())
+                .await
+                .ok()??
+                .unwrap();
+            self.buffered.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+/// An open WebSocket, speaking JSON.
+pub struct TestSocket {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl TestSocket {
+    pub async fn send(&mut self, json: &serde_json::Value) {
+        self.socket
+            .send(Message::Text(json.to_string()))
+            .await
+            .unwrap();
+    }
+
+    /// The next message, or `None` if none comes within a few seconds or the
+    /// socket is closed.
+    pub async fn next(&mut self) -> Option<serde_json::Value> {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(2), self.socket.next())
+                .await
+                .ok()??
+                .unwrap();
+
+            match message {
+                Message::Text(text) => return Some(serde_json::from_str(&text).unwrap()),
+                Message::Close(_) => return None,
+                _ => continue,
+            }
+        }
+    }
+}
+
+fn encode_form(form: &[(&str, &str)]) -> String {
+    form.iter()
+        .map(|(key, value)| format!("{}={}", key, urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Pulls the id of every todo out of rendered todo partials.
+pub fn todo_ids(body: &str) -> Vec<Uuid> {
+    let mut ids: Vec<Uuid> = body
+        .split("hx-post=\"/todos/")
+        .skip(1)
+        .filter_map(|rest| rest.split('/').next())
+        .filter_map(|id| id.parse().ok())
+        .collect();
+    // A todo can have more than one button.
+    ids.dedup();
+
+    ids
+}
+
+/// Adds a todo, optionally under `parent`, and returns its id.
+pub async fn add_todo(client: &mut TestClient, content: &str, parent: Option<&str>) -> String {
+    let mut form = vec![("content", content)];
+    form.extend(parent.map(|parent| ("parent", parent)));
+    let response = client.post("/todos", &form).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = client.get("/api/v1/todos").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+    body["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|todo| todo["content"] == content)
+        .unwrap()["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+/// The todos a JSON endpoint returns, whether bare or as a page.
+pub async fn json(client: &mut TestClient, uri: &str) -> Vec<Value> {
+    let response = client.get(uri).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    match body {
+        Value::Array(todos) => todos,
+        body => body["todos"].as_array().unwrap().clone(),
+    }
+}
+
+pub fn contents(todos: &[Value]) -> Vec<&str> {
+    todos
+        .iter()
+        .map(|todo| todo["content"].as_str().unwrap())
+        .collect()
+}
@@ -0,0 +1,240 @@
+use axum::http::StatusCode;
+use flyio_rust::data::{
+    self,
+    todo::{self, TodoChanges},
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+mod common;
+
+use common::{add_todo, json, TestApp, TestClient};
+
+async fn content(client: &mut TestClient, todo_id: &str) -> String {
+    let response = client.get(&format!("/api/v1/todos/{}", todo_id)).await;
+    let todo: Value = serde_json::from_str(&response.body).unwrap();
+    todo["content"].as_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn todos_have_an_etag_that_changes_with_them() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let response = client.post("/todos", &[("content", "Buy milk")]).await;
+    assert_eq!(response.headers["ETag"], "\"1\"");
+    let todo_id = json(&mut client, "/api/v1/todos").await[0]["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let response = client.get(&format!("/api/v1/todos/{}", todo_id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["ETag"], "\"1\"");
+
+    let response = client.get(&format!("/todos/{}/edit", todo_id)).await;
+    assert_eq!(response.headers["ETag"], "\"1\"");
+    assert!(response
+        .body
+        .contains(r#"<input type="hidden" name="version" value="1" />"#));
+
+    let response = client
+        .put(
+            &format!("/todos/{}", todo_id),
+            &[("content", "Buy oat milk")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["ETag"], "\"2\"");
+
+    let response = client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    assert_eq!(response.headers["ETag"], "\"3\"");
+    let response = client.get(&format!("/api/v1/todos/{}", todo_id)).await;
+    assert_eq!(response.headers["ETag"], "\"3\"");
+
+    // Where it sits in the list isn't part of the todo's version.
+    let other = add_todo(&mut client, "Walk dog", None).await;
+    let response = client
+        .post(&format!("/todos/{}/move", todo_id), &[("after", &other)])
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    assert_eq!(response.headers["ETag"], "\"3\"");
+}
+
+#[tokio::test]
+async fn stale_edits_are_refused() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let todo_id = add_todo(&mut client, "Buy milk", None).await;
+    let uri = format!("/todos/{}", todo_id);
+
+    // Two tabs open the same todo for editing; the first saves.
+    let response = client
+        .put(&uri, &[("content", "Buy oat milk"), ("version", "1")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // The second is told what it now says instead of overwriting it.
+    let response = client
+        .put(&uri, &[("content", "Buy soy milk"), ("version", "1")])
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.headers["content-type"], "text/html; charset=utf-8");
+    assert_eq!(response.headers["ETag"], "\"2\"");
+    assert!(response.body.contains("changed somewhere else"));
+    assert!(
+        response.body.contains(r#"value="Buy oat milk""#),
+        "{}",
+        response.body
+    );
+    assert!(response
+        .body
+        .contains(r#"<input type="hidden" name="version" value="2" />"#));
+    assert_eq!(content(&mut client, &todo_id).await, "Buy oat milk");
+
+    // Ticking it off counts as a change too.
+    client.post(&format!("{}/toggle", uri), &[]).await;
+    let response = client
+        .put(&uri, &[("content", "Buy soy milk"), ("version", "2")])
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    // Without a version, the edit is made regardless.
+    let response = client.put(&uri, &[("content", "Buy soy milk")]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(content(&mut client, &todo_id).await, "Buy soy milk");
+
+    // Nor can a todo deleted in the meantime be edited.
+    client.delete(&uri).await;
+    let response = client
+        .put(&uri, &[("content", "Buy rice milk"), ("version", "4")])
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert!(response.body.contains("deleted somewhere else"));
+}
+
+#[tokio::test]
+async fn if_match_is_checked() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let todo_id = add_todo(&mut client, "Buy milk", None).await;
+    let uri = format!("/todos/{}", todo_id);
+
+    let mut client = client.with_header("if-match", "\"1\"");
+    // The header wins over the form.
+    let response = client
+        .put(&uri, &[("content", "Buy oat milk"), ("version", "7")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = client.put(&uri, &[("content", "Buy soy milk")]).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(content(&mut client, &todo_id).await, "Buy oat milk");
+
+    let mut client = client.with_header("if-match", "*");
+    let response = client.put(&uri, &[("content", "Buy soy milk")]).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let mut client = client.with_header("if-match", "nonsense");
+    let response = client.put(&uri, &[("content", "Buy rice milk")]).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn stale_toggles_are_refused() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+    let mut client = app.logged_in_client().await;
+    let todo_id: Uuid = add_todo(&mut client, "Buy milk", None)
+        .await
+        .parse()
+        .unwrap();
+    let user_id: Uuid = sqlx::query_scalar("select user_id from todos where todo_id = $1")
+        .bind(todo_id)
+        .fetch_one(db)
+        .await
+        .unwrap();
+
+    client
+        .put(
+            &format!("/todos/{}", todo_id),
+            &[("content", "Buy oat milk")],
+        )
+        .await;
+    let toggled = todo::toggle_todo_by_id(db, user_id, todo_id, Some(1)).await;
+    assert!(matches!(toggled, Err(sqlx::Error::RowNotFound)));
+
+    todo::toggle_todo_by_id(db, user_id, todo_id, Some(2))
+        .await
+        .unwrap();
+    let response = client.get(&format!("/api/v1/todos/{}", todo_id)).await;
+    let todo: Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(todo["done"], true);
+}
+
+#[tokio::test]
+async fn edits_that_tick_a_todo_off_are_made_together_or_not_at_all() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+    let mut client = app.logged_in_client().await;
+    let todo_id: Uuid = add_todo(&mut client, "Buy milk", None)
+        .await
+        .parse()
+        .unwrap();
+    let user_id: Uuid = sqlx::query_scalar("select user_id from todos where todo_id = $1")
+        .bind(todo_id)
+        .fetch_one(db)
+        .await
+        .unwrap();
+    let changes = |version| TodoChanges {
+        content: "Buy oat milk".to_owned(),
+        done: Some(true),
+        version: Some(version),
+        ..Default::default()
+    };
+
+    let updated = todo::update_todo_by_id(db, user_id, todo_id, changes(2)).await;
+    assert!(matches!(updated, Err(sqlx::Error::RowNotFound)));
+    assert_eq!(content(&mut client, &todo_id.to_string()).await, "Buy milk");
+
+    todo::update_todo_by_id(db, user_id, todo_id, changes(1))
+        .await
+        .unwrap();
+    let response = client.get(&format!("/api/v1/todos/{}", todo_id)).await;
+    let todo: Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(todo["content"], "Buy oat milk");
+    assert_eq!(todo["done"], true);
+    assert_eq!(todo["version"], 3);
+}
+
+#[tokio::test]
+async fn only_one_instance_runs_a_task_at_a_time() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+    let name = format!("test-{}", Uuid::new_v4());
+
+    let (started, mut start) = tokio::sync::mpsc::channel(1);
+    let (finish, finished) = tokio::sync::oneshot::channel::<()>();
+    let first = tokio::spawn({
+        let db = db.clone();
+        let name = name.clone();
+        async move {
+            data::run_alone(&db, &name, async {
+                started.send(()).await.unwrap();
+                finished.await.unwrap();
+            })
+            .await
+        }
+    });
+    start.recv().await;
+
+    let second = data::run_alone(db, &name, async {}).await.unwrap();
+    assert_eq!(second, None);
+
+    finish.send(()).unwrap();
+    assert_eq!(first.await.unwrap().unwrap(), Some(()));
+
+    let third = data::run_alone(db, &name, async {}).await.unwrap();
+    assert_eq!(third, Some(()));
+}
@@ -0,0 +1,175 @@
+use flyio_rust::data::user;
+use serde_json::Value;
+use uuid::Uuid;
+
+mod common;
+
+use common::{add_todo, json, TestApp, PASSWORD};
+
+fn field<'a>(events: &'a [Value], name: &str) -> Vec<&'a str> {
+    events
+        .iter()
+        .map(|event| event[name].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn changes_to_a_todo_are_kept_in_its_history() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let todo_id = add_todo(&mut client, "Buy milk", None).await;
+    let other = add_todo(&mut client, "Walk dog", None).await;
+
+    client
+        .put(
+            &format!("/todos/{}", todo_id),
+            &[("content", "Buy oat milk")],
+        )
+        .await;
+    // Saving without changing the content isn't a change.
+    client
+        .put(
+            &format!("/todos/{}", todo_id),
+            &[("content", "Buy oat milk")],
+        )
+        .await;
+    client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    client
+        .post(&format!("/todos/{}/move", todo_id), &[("after", &other)])
+        .await;
+    client.delete(&format!("/todos/{}", todo_id)).await;
+    client
+        .post(&format!("/trash/{}/restore", todo_id), &[])
+        .await;
+
+    let history = json(&mut client, &format!("/api/v1/todos/{}/history", todo_id)).await;
+    assert_eq!(
+        field(&history, "kind"),
+        [
+            "created",
+            "content_changed",
+            "toggled",
+            "moved",
+            "deleted",
+            "restored"
+        ]
+    );
+    assert_eq!(history[0]["content"], "Buy milk");
+    assert_eq!(history[1]["before"], "Buy milk");
+    assert_eq!(history[1]["after"], "Buy oat milk");
+    assert_eq!(history[2]["done"], true);
+    assert!(history[0]["actor"]
+        .as_str()
+        .unwrap()
+        .ends_with("@example.com"));
+    assert!(history[0]["createdAt"].is_string());
+
+    let response = client.get(&format!("/todos/{}/history", todo_id)).await;
+    assert!(response
+        .body
+        .contains("changed &quot;Buy milk&quot; to &quot;Buy oat milk&quot;"));
+    assert!(response.body.contains("marked it done"));
+}
+
+#[tokio::test]
+async fn edits_that_change_the_list_are_kept_in_the_history() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let todo_id = add_todo(&mut client, "Buy milk", None).await;
+
+    // Onto a list, again onto the same one, and off it.
+    for content in ["Buy milk @groceries", "Buy milk @groceries", "Buy milk"] {
+        client
+            .put(&format!("/todos/{}", todo_id), &[("content", content)])
+            .await;
+    }
+
+    let history = json(&mut client, &format!("/api/v1/todos/{}/history", todo_id)).await;
+    assert_eq!(field(&history, "kind"), ["created", "moved", "moved"]);
+}
+
+#[tokio::test]
+async fn completing_subtasks_is_kept_in_their_history() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let parent = add_todo(&mut client, "Plan trip", None).await;
+    client
+        .post(
+            "/todos",
+            &[("content", "Book flights"), ("parent", &parent)],
+        )
+        .await;
+    let response = client.get("/api/v1/todos").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+    let child = body["todos"][1]["todoId"].as_str().unwrap().to_owned();
+
+    client
+        .post(&format!("/todos/{}/complete", parent), &[])
+        .await;
+
+    for todo_id in [&parent, &child] {
+        let history = json(&mut client, &format!("/api/v1/todos/{}/history", todo_id)).await;
+        assert_eq!(field(&history, "kind"), ["created", "toggled"]);
+    }
+}
+
+#[tokio::test]
+async fn histories_are_private() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut mallory = app.logged_in_client().await;
+
+    let todo_id = add_todo(&mut alice, "Alice secret", None).await;
+
+    let history = json(&mut mallory, &format!("/api/v1/todos/{}/history", todo_id)).await;
+    assert!(history.is_empty());
+}
+
+#[tokio::test]
+async fn logins_are_audited() {
+    let app = TestApp::new().await;
+    let email = format!("{}@example.com", Uuid::new_v4().simple());
+    let mut client = app.client().with_header("fly-client-ip", "203.0.113.7");
+    client.signup(&email, PASSWORD).await;
+
+    let response = client.login(&email, "Wr0ngPassword!").await;
+    assert!(!client.is_logged_in(), "{}", response.body);
+    client.login(&email, PASSWORD).await;
+
+    let log = json(&mut client, "/api/v1/account/audit-log").await;
+    assert_eq!(field(&log, "action"), ["login", "login_failed"]);
+    assert_eq!(log[1]["email"], email.as_str());
+    assert_eq!(log[1]["ip"], "203.0.113.7");
+
+    let response = client.get("/account/security").await;
+    assert!(response.body.contains("Failed login"));
+    assert!(response.body.contains("203.0.113.7"));
+
+    // Nobody else sees them.
+    let mut other = app.logged_in_client().await;
+    let log = json(&mut other, "/api/v1/account/audit-log").await;
+    assert_eq!(field(&log, "action"), ["login"]);
+}
+
+#[tokio::test]
+async fn password_changes_are_audited() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else {
+        return;
+    };
+    let email = format!("{}@example.com", Uuid::new_v4().simple());
+    let mut client = app.client();
+    client.signup(&email, PASSWORD).await;
+
+    assert!(user::set_password(db, &email, "N3wPassword!")
+        .await
+        .unwrap());
+
+    client.login(&email, "N3wPassword!").await;
+    let log = json(&mut client, "/api/v1/account/audit-log").await;
+    assert_eq!(field(&log, "action"), ["login", "password_changed"]);
+}
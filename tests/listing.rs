@@ -0,0 +1,237 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+mod common;
+
+use common::{todo_ids, TestApp, TestClient};
+
+async fn add_todo(client: &mut TestClient, content: &str, due: &str) {
+    let response = client
+        .post("/todos", &[("content", content), ("due", due)])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+async fn list(client: &mut TestClient, query: &str) -> Value {
+    let response = client.get(&format!("/api/v1/todos?{}", query)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    serde_json::from_str(&response.body).unwrap()
+}
+
+fn contents(body: &Value) -> Vec<&str> {
+    body["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["content"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn cursors_walk_every_page_once() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    for n in 0..7 {
+        add_todo(&mut client, &format!("todo {}", n), "").await;
+    }
+
+    let mut seen = vec![];
+    let mut query = "limit=3&sort=-created".to_owned();
+    loop {
+        let body = list(&mut client, &query).await;
+        seen.extend(contents(&body).iter().map(|c| c.to_string()));
+
+        match body["nextCursor"].as_str() {
+            Some(cursor) => query = format!("limit=3&sort=-created&cursor={}", cursor),
+            None => break,
+        }
+    }
+
+    let expected: Vec<_> = (0..7).rev().map(|n| format!("todo {}", n)).collect();
+    assert_eq!(seen, expected);
+}
+
+#[tokio::test]
+async fn todos_filter_by_status() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "open", "").await;
+    add_todo(&mut client, "done", "").await;
+
+    let body = list(&mut client, "status=done").await;
+    assert!(contents(&body).is_empty());
+
+    let id = body_id(&list(&mut client, "").await, "done");
+    client.post(&format!("/todos/{}/toggle", id), &[]).await;
+
+    assert_eq!(contents(&list(&mut client, "status=done").await), ["done"]);
+    assert_eq!(contents(&list(&mut client, "status=open").await), ["open"]);
+}
+
+#[tokio::test]
+async fn todos_sort_and_filter_by_due_date() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "whenever", "").await;
+    add_todo(&mut client, "later", "2023-12-24T18:00").await;
+    add_todo(&mut client, "sooner", "2023-12-01T09:30").await;
+
+    let body = list(&mut client, "sort=due").await;
+    assert_eq!(contents(&body), ["sooner", "later", "whenever"]);
+    assert_eq!(body["todos"][0]["dueAt"], "2023-12-01T09:30:00Z");
+
+    // Undated todos stay last either way.
+    let body = list(&mut client, "sort=-due").await;
+    assert_eq!(contents(&body), ["later", "sooner", "whenever"]);
+
+    let body = list(&mut client, "due_from=2023-12-01&due_to=2023-12-01").await;
+    assert_eq!(contents(&body), ["sooner"]);
+}
+
+#[tokio::test]
+async fn undated_todos_page_by_id() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "dated", "2023-12-01T09:30").await;
+    for n in 0..3 {
+        add_todo(&mut client, &format!("undated {}", n), "").await;
+    }
+
+    let first = list(&mut client, "sort=due&limit=2").await;
+    let cursor = first["nextCursor"].as_str().unwrap();
+    let second = list(&mut client, &format!("sort=due&limit=2&cursor={}", cursor)).await;
+
+    assert_eq!(contents(&first)[0], "dated");
+    assert_eq!(contents(&first).len() + contents(&second).len(), 4);
+    assert!(second["nextCursor"].is_null());
+}
+
+#[tokio::test]
+async fn created_range_is_inclusive() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "today", "").await;
+
+    let today = list(&mut client, "").await["todos"][0]["createdAt"]
+        .as_str()
+        .unwrap()[..10]
+        .to_owned();
+
+    let body = list(
+        &mut client,
+        &format!("created_from={0}&created_to={0}", today),
+    )
+    .await;
+    assert_eq!(contents(&body), ["today"]);
+
+    let body = list(&mut client, "created_to=2000-01-01").await;
+    assert!(contents(&body).is_empty());
+}
+
+#[tokio::test]
+async fn cursors_only_work_with_the_sort_they_came_from() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    for n in 0..3 {
+        add_todo(&mut client, &format!("todo {}", n), "").await;
+    }
+    let created = list(&mut client, "limit=1&sort=created").await["nextCursor"].clone();
+    let manual = list(&mut client, "limit=1&sort=manual").await["nextCursor"].clone();
+
+    for (sort, cursor) in [
+        ("priority", &created),
+        ("manual", &created),
+        ("-created", &manual),
+        ("due", &manual),
+    ] {
+        let query = format!("sort={}&cursor={}", sort, cursor.as_str().unwrap());
+        let response = client.get(&format!("/api/v1/todos?{}", query)).await;
+
+        assert_eq!(
+            response.status,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{}",
+            query
+        );
+        assert!(response.body.contains("cursor doesn't match sort"));
+    }
+
+    // The direction can change, though.
+    let query = format!("sort=-created&cursor={}", created.as_str().unwrap());
+    list(&mut client, &query).await;
+}
+
+#[tokio::test]
+async fn invalid_parameters_are_rejected() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    for query in [
+        "sort=size",
+        "status=maybe",
+        "cursor=abc",
+        "limit=0",
+        "due_from=tomorrow",
+    ] {
+        let response = client.get(&format!("/api/v1/todos?{}", query)).await;
+        assert_eq!(
+            response.status,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{}",
+            query
+        );
+    }
+}
+
+#[tokio::test]
+async fn html_list_ends_in_a_load_more_trigger() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    for n in 0..3 {
+        add_todo(&mut client, &format!("todo {}", n), "").await;
+    }
+
+    let response = client.get("/todos/page?limit=2&status=open").await;
+    assert_eq!(todo_ids(&response.body).len(), 2);
+
+    let next = response
+        .body
+        .split("hx-get=\"")
+        .filter_map(|rest| rest.split('"').next())
+        .find(|url| url.starts_with("/todos/page?"))
+        .expect("a load more trigger")
+        .replace("&amp;", "&");
+    assert!(next.contains("status=open"));
+
+    let response = client.get(&next).await;
+    assert_eq!(todo_ids(&response.body).len(), 1);
+    assert!(!response.body.contains("Load more"));
+}
+
+#[tokio::test]
+async fn creating_a_todo_rerenders_the_filtered_list() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "first", "").await;
+    let id = body_id(&list(&mut client, "").await, "first");
+    client.post(&format!("/todos/{}/toggle", id), &[]).await;
+
+    let response = client
+        .post("/todos", &[("content", "second"), ("status", "open")])
+        .await;
+
+    assert!(response.body.contains("second"));
+    assert!(!response.body.contains("first"));
+}
+
+fn body_id(body: &Value, content: &str) -> String {
+    body["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|todo| todo["content"] == content)
+        .unwrap()["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
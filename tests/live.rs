@@ -0,0 +1,144 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+mod common;
+
+use common::{add_todo, TestApp, TestEvent, TestEvents};
+
+async fn next_event(events: &mut TestEvents) -> (TestEvent, Value) {
+    let event = events.next().await.expect("no event came");
+    let data = serde_json::from_str(&event.data).unwrap();
+
+    (event, data)
+}
+
+#[tokio::test]
+async fn changes_are_pushed_to_the_users_open_tabs() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let mut events = client.events("/todos/events").await;
+
+    let todo_id = add_todo(&mut client, "Call mum", None).await;
+    let (event, data) = next_event(&mut events).await;
+    assert_eq!(event.event, "todo-created");
+    assert_eq!(data["kind"], "created");
+    assert_eq!(data["todoId"], todo_id.as_str());
+
+    let response = client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let (event, data) = next_event(&mut events).await;
+    assert_eq!(event.event, format!("todo-updated-{}", todo_id));
+    assert_eq!(data["kind"], "updated");
+
+    let response = client
+        .put(&format!("/todos/{}", todo_id), &[("content", "Call dad")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let (event, _) = next_event(&mut events).await;
+    assert_eq!(event.event, format!("todo-updated-{}", todo_id));
+
+    let response = client.delete(&format!("/todos/{}", todo_id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let (event, data) = next_event(&mut events).await;
+    assert_eq!(event.event, "todo-deleted");
+    assert_eq!(data["todoId"], todo_id.as_str());
+
+    let response = client
+        .post(&format!("/trash/{}/restore", todo_id), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let (event, _) = next_event(&mut events).await;
+    assert_eq!(event.event, "todo-created");
+}
+
+#[tokio::test]
+async fn changes_to_many_todos_reload_the_list() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let first = add_todo(&mut client, "Call mum", None).await;
+    let second = add_todo(&mut client, "Call the bank", None).await;
+    let mut events = client.events("/todos/events").await;
+
+    let response = client
+        .post_json(
+            "/api/v1/todos/bulk",
+            &serde_json::json!({ "todoIds": [first, second], "action": "done" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let (event, data) = next_event(&mut events).await;
+    assert_eq!(event.event, "todos-changed");
+    assert_eq!(data["kind"], "many");
+
+    let response = client.post("/archive", &[("q", "")]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let (event, _) = next_event(&mut events).await;
+    assert_eq!(event.event, "todos-changed");
+}
+
+#[tokio::test]
+async fn other_users_changes_are_not_pushed() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut bob = app.logged_in_client().await;
+    let mut events = alice.events("/todos/events").await;
+
+    add_todo(&mut bob, "Bob's todo", None).await;
+    let todo_id = add_todo(&mut alice, "Alice's todo", None).await;
+
+    // Bob's came first, so it would be first if it came at all.
+    let (event, data) = next_event(&mut events).await;
+    assert_eq!(event.event, "todo-created");
+    assert_eq!(data["todoId"], todo_id.as_str());
+}
+
+#[tokio::test]
+async fn event_streams_need_a_login() {
+    let app = TestApp::new().await;
+    let mut client = app.client();
+
+    let response = client.get("/todos/events").await;
+
+    assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+}
+
+#[tokio::test]
+async fn updated_todos_are_rendered_as_their_whole_tree() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let parent = add_todo(&mut client, "Plan trip", None).await;
+    let response = client
+        .post(
+            "/todos",
+            &[("content", "Book flights"), ("parent", &parent)],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = client.get("/api/v1/todos").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+    let subtask = body["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|todo| todo["content"] == "Book flights")
+        .unwrap()["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let response = client.get(&format!("/todos/{}", subtask)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers["HX-Retarget"],
+        format!("#todo-{}", parent).as_str()
+    );
+    assert!(response.body.contains("Plan trip"));
+    assert!(response.body.contains("Book flights"));
+    assert!(response
+        .body
+        .contains(&format!(r#"hx-trigger="sse:todo-updated-{}""#, subtask)));
+}
@@ -0,0 +1,66 @@
+//! These need Postgres and are skipped when `DATABASE_URL` isn't set.
+
+use axum::http::StatusCode;
+use flyio_rust::data;
+use sqlx::{Executor, PgPool};
+
+mod common;
+
+use common::TestApp;
+
+/// `add_disabled_at_to_users`, undone by hand by [`roll_back`].
+const ROLLED_BACK: i64 = 20231121000000;
+
+/// Reverts one migration, as if the binary were newer than the schema.
+async fn roll_back(db: &PgPool) {
+    db.execute("alter table users drop column disabled_at")
+        .await
+        .unwrap();
+    sqlx::query("delete from _sqlx_migrations where version = $1")
+        .bind(ROLLED_BACK)
+        .execute(db)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn pending_migrations_are_reported() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+
+    assert!(data::pending_migrations(db).await.unwrap().is_empty());
+
+    roll_back(db).await;
+
+    let pending = data::pending_migrations(db).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].version, ROLLED_BACK);
+}
+
+#[tokio::test]
+async fn readyz_is_unavailable_while_migrations_are_pending() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+
+    assert_eq!(app.client().get("/readyz").await.status, StatusCode::OK);
+
+    roll_back(db).await;
+
+    let response = app.client().get("/readyz").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(response.body.contains("1 pending migration(s)"));
+}
+
+#[tokio::test]
+async fn concurrent_migrations_apply_once() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+
+    roll_back(db).await;
+
+    let (a, b) = tokio::join!(data::migrate(db), data::migrate(db));
+    a.unwrap();
+    b.unwrap();
+
+    assert!(data::pending_migrations(db).await.unwrap().is_empty());
+}
@@ -0,0 +1,249 @@
+use axum::http::StatusCode;
+use flyio_rust::data::{self, ordering::Placement};
+use serde_json::Value;
+use uuid::Uuid;
+
+mod common;
+
+use common::{TestApp, TestClient};
+
+/// Adds todos named after their index and returns their ids in order.
+async fn add_todos(client: &mut TestClient, count: usize) -> Vec<String> {
+    for n in 0..count {
+        let content = n.to_string();
+        client.post("/todos", &[("content", &content)]).await;
+    }
+
+    manual_order(client)
+        .await
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect()
+}
+
+async fn manual_order(client: &mut TestClient) -> Vec<(String, String)> {
+    let response = client.get("/api/v1/todos?sort=manual&limit=200").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| {
+            (
+                todo["todoId"].as_str().unwrap().to_owned(),
+                todo["content"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+async fn contents(client: &mut TestClient) -> Vec<String> {
+    manual_order(client)
+        .await
+        .into_iter()
+        .map(|(_, content)| content)
+        .collect()
+}
+
+async fn move_todo(client: &mut TestClient, id: &str, placement: &str, anchor: &str) -> StatusCode {
+    client
+        .post(&format!("/todos/{}/move", id), &[(placement, anchor)])
+        .await
+        .status
+}
+
+#[tokio::test]
+async fn todos_move_before_and_after_each_other() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, 4).await;
+
+    assert_eq!(contents(&mut client).await, ["0", "1", "2", "3"]);
+
+    let status = move_todo(&mut client, &ids[3], "before", &ids[1]).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(contents(&mut client).await, ["0", "3", "1", "2"]);
+
+    move_todo(&mut client, &ids[0], "after", &ids[2]).await;
+    assert_eq!(contents(&mut client).await, ["3", "1", "2", "0"]);
+
+    move_todo(&mut client, &ids[2], "before", &ids[3]).await;
+    assert_eq!(contents(&mut client).await, ["2", "3", "1", "0"]);
+
+    move_todo(&mut client, &ids[1], "after", &ids[1]).await;
+    assert_eq!(contents(&mut client).await, ["2", "3", "1", "0"]);
+}
+
+#[tokio::test]
+async fn repeated_moves_into_one_spot_keep_the_order() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, 30).await;
+
+    // Each move halves the gap after "0", so this runs out of room and has to
+    // renumber along the way.
+    for id in ids[1..].iter().rev() {
+        move_todo(&mut client, id, "after", &ids[0]).await;
+    }
+
+    let expected: Vec<_> = (0..30).map(|n| n.to_string()).collect();
+    assert_eq!(contents(&mut client).await, expected);
+
+    for id in ids[1..].iter() {
+        move_todo(&mut client, id, "after", &ids[0]).await;
+    }
+
+    let mut expected: Vec<_> = (1..30).rev().map(|n| n.to_string()).collect();
+    expected.insert(0, "0".to_owned());
+    assert_eq!(contents(&mut client).await, expected);
+}
+
+#[tokio::test]
+async fn moves_next_to_missing_todos_conflict() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let mut other = app.logged_in_client().await;
+    let ids = add_todos(&mut client, 2).await;
+    let others = add_todos(&mut other, 1).await;
+
+    let missing = Uuid::new_v4().to_string();
+    let status = move_todo(&mut client, &ids[0], "after", &missing).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let status = move_todo(&mut client, &ids[0], "after", &others[0]).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    client.delete(&format!("/todos/{}", ids[1])).await;
+    let status = move_todo(&mut client, &ids[0], "after", &ids[1]).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+/// The `(id, position)` of each todo on the list, in order.
+async fn positions_on(client: &mut TestClient, list: &str) -> Vec<(String, i64)> {
+    let response = client
+        .get(&format!(
+            "/api/v1/todos?sort=manual&limit=200&list={}",
+            list
+        ))
+        .await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| {
+            (
+                todo["todoId"].as_str().unwrap().to_owned(),
+                todo["position"].as_i64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn todos_are_ordered_within_their_list() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    for n in 0..20 {
+        let list = if n % 4 == 0 { "home" } else { "work" };
+        let content = format!("{} @{}", n, list);
+        client.post("/todos", &[("content", &content)]).await;
+    }
+    let home = positions_on(&mut client, "home").await;
+    let work: Vec<_> = positions_on(&mut client, "work")
+        .await
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+
+    let status = move_todo(&mut client, &work[0], "after", &home[0].0).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Enough moves into one spot to renumber the work list.
+    for id in work[1..].iter().rev() {
+        assert_eq!(
+            move_todo(&mut client, id, "after", &work[0]).await,
+            StatusCode::NO_CONTENT
+        );
+    }
+    for id in work[1..].iter() {
+        move_todo(&mut client, id, "after", &work[0]).await;
+    }
+
+    let moved: Vec<_> = positions_on(&mut client, "work")
+        .await
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    let mut expected = vec![work[0].clone()];
+    expected.extend(work[1..].iter().rev().cloned());
+    assert_eq!(moved, expected);
+    assert_eq!(positions_on(&mut client, "home").await, home);
+}
+
+#[tokio::test]
+async fn moves_need_exactly_one_anchor() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let ids = add_todos(&mut client, 2).await;
+
+    let response = client
+        .post(&format!("/todos/{}/move", ids[0]), &[("position", "1")])
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = client
+        .post(
+            &format!("/todos/{}/move", ids[0]),
+            &[("before", &ids[1]), ("after", &ids[1])],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn concurrent_moves_are_serialized() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+    let mut client = app.logged_in_client().await;
+    let ids: Vec<Uuid> = add_todos(&mut client, 10)
+        .await
+        .iter()
+        .map(|id| id.parse().unwrap())
+        .collect();
+
+    let user_id: Uuid = sqlx::query_scalar("select user_id from todos where todo_id = $1")
+        .bind(ids[0])
+        .fetch_one(db)
+        .await
+        .unwrap();
+
+    // Everyone piles in right after the first todo at once, enough times to
+    // need renumbering part way through.
+    let moves = (0..40).map(|n| {
+        let db = db.clone();
+        let todo_id = ids[1 + n % 9];
+        let anchor = ids[0];
+        tokio::spawn(async move {
+            data::todo::move_todo(&db, user_id, todo_id, Placement::After(anchor)).await
+        })
+    });
+
+    for result in futures::future::join_all(moves).await {
+        result.unwrap().unwrap();
+    }
+
+    let positions: Vec<i64> = sqlx::query_scalar("select position from todos where user_id = $1")
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+        .unwrap();
+    let mut distinct = positions.clone();
+    distinct.sort();
+    distinct.dedup();
+
+    assert_eq!(distinct.len(), positions.len());
+    assert_eq!(contents(&mut client).await[0], "0");
+}
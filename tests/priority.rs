@@ -0,0 +1,155 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+mod common;
+
+use common::{TestApp, TestClient};
+
+async fn todos(client: &mut TestClient, query: &str) -> Vec<Value> {
+    let response = client.get(&format!("/api/v1/todos?{}", query)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body["todos"].as_array().unwrap().clone()
+}
+
+/// Each todo's content and priority.
+async fn prioritized(client: &mut TestClient, query: &str) -> Vec<(String, String)> {
+    todos(client, query)
+        .await
+        .into_iter()
+        .map(|todo| {
+            (
+                todo["content"].as_str().unwrap().to_owned(),
+                todo["priority"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+fn owned(todos: &[(&str, &str)]) -> Vec<(String, String)> {
+    todos
+        .iter()
+        .map(|(content, priority)| (content.to_string(), priority.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn priority_comes_from_the_form_or_shorthand() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    for form in [
+        vec![("content", "Water plants")],
+        vec![("content", "Pay rent !4")],
+        vec![("content", "Call mum"), ("priority", "medium")],
+        // Shorthand beats the select.
+        vec![("content", "File taxes !3"), ("priority", "low")],
+    ] {
+        let response = client.post("/todos", &form).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    assert_eq!(
+        prioritized(&mut client, "").await,
+        owned(&[
+            ("Water plants", "none"),
+            ("Pay rent", "urgent"),
+            ("Call mum", "medium"),
+            ("File taxes", "high"),
+        ])
+    );
+
+    let response = client.get("/todos").await;
+    assert!(response.body.contains(r#"title="Urgent priority""#));
+    assert!(response.body.contains("!!!!"));
+
+    let response = client
+        .post("/todos", &[("content", "Nope"), ("priority", "critical")])
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let response = client.post("/todos", &[("content", "!2")]).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn editing_changes_the_priority() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    client
+        .post(
+            "/todos",
+            &[("content", "Book dentist"), ("priority", "low")],
+        )
+        .await;
+    let todo_id = todos(&mut client, "").await[0]["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let response = client.get(&format!("/todos/{}/edit", todo_id)).await;
+    let option = response.body.split(r#"value="low""#).nth(1).unwrap();
+    assert!(option.split('>').next().unwrap().contains("selected"));
+
+    let response = client
+        .put(
+            &format!("/todos/{}", todo_id),
+            &[("content", "Book dentist"), ("priority", "high")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains(r#"title="High priority""#));
+    assert_eq!(
+        prioritized(&mut client, "").await,
+        owned(&[("Book dentist", "high")])
+    );
+}
+
+#[tokio::test]
+async fn todos_sort_and_filter_by_priority() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    for content in ["Low !1", "None", "Urgent !4", "Medium !2", "Also low !1"] {
+        client.post("/todos", &[("content", content)]).await;
+    }
+
+    let contents = |todos: Vec<(String, String)>| -> Vec<String> {
+        todos.into_iter().map(|(content, _)| content).collect()
+    };
+
+    let by_priority = contents(prioritized(&mut client, "sort=-priority").await);
+    assert_eq!(by_priority[..2], ["Urgent", "Medium"]);
+    assert_eq!(by_priority[4], "None");
+
+    // Paging through a sort with ties still lists every todo once.
+    let mut paged = vec![];
+    let mut cursor = String::new();
+    loop {
+        let response = client
+            .get(&format!(
+                "/api/v1/todos?sort=priority&limit=2&cursor={}",
+                cursor
+            ))
+            .await;
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        for todo in body["todos"].as_array().unwrap() {
+            paged.push(todo["content"].as_str().unwrap().to_owned());
+        }
+        match body["nextCursor"].as_str() {
+            Some(next) => cursor = next.to_owned(),
+            None => break,
+        }
+    }
+    assert_eq!(paged.len(), 5);
+    assert_eq!(paged[0], "None");
+    assert_eq!(paged[4], "Urgent");
+
+    assert_eq!(
+        contents(prioritized(&mut client, "min_priority=medium").await),
+        ["Urgent", "Medium"]
+    );
+    let response = client.get("/api/v1/todos?min_priority=asap").await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
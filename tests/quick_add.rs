@@ -0,0 +1,145 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+use time::{macros::format_description, Duration, OffsetDateTime};
+
+mod common;
+
+use common::{TestApp, TestClient};
+
+async fn todos(client: &mut TestClient, query: &str) -> Vec<Value> {
+    let response = client.get(&format!("/api/v1/todos?{}", query)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body["todos"].as_array().unwrap().clone()
+}
+
+async fn add_todo(client: &mut TestClient, content: &str) {
+    let response = client.post("/todos", &[("content", content)]).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+}
+
+/// Tomorrow's date as `YYYY-MM-DD`.
+fn tomorrow() -> String {
+    (OffsetDateTime::now_utc() + Duration::days(1))
+        .format(format_description!("[year]-[month]-[day]"))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn phrases_are_taken_out_of_new_todos() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    add_todo(
+        &mut client,
+        "Buy milk tomorrow 5pm #errands !high @Groceries",
+    )
+    .await;
+    add_todo(&mut client, "Stretch every day at 7am").await;
+
+    let listed = todos(&mut client, "").await;
+    let milk = &listed[0];
+    assert_eq!(milk["content"], "Buy milk");
+    assert_eq!(milk["priority"], "high");
+    assert_eq!(milk["tags"][0]["name"], "errands");
+    assert_eq!(milk["list"], "groceries");
+    assert!(milk["dueAt"]
+        .as_str()
+        .unwrap()
+        .starts_with(&format!("{}T17:00:00", tomorrow())));
+
+    let stretch = &listed[1];
+    assert_eq!(stretch["content"], "Stretch");
+    assert_eq!(stretch["recurrence"], "FREQ=DAILY");
+    assert!(stretch["dueAt"].as_str().unwrap().contains("T07:00:00"));
+
+    // Phrases win over the form's fields.
+    let response = client
+        .post(
+            "/todos",
+            &[("content", "Pay rent today"), ("due", "2030-01-01T12:00")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let rent = todos(&mut client, "").await.pop().unwrap();
+    assert!(!rent["dueAt"].as_str().unwrap().starts_with("2030"));
+
+    // There has to be something left to do.
+    add_todo(&mut client, "Tomorrow #errands").await;
+    let tomorrow = todos(&mut client, "").await.pop().unwrap();
+    assert_eq!(tomorrow["content"], "Tomorrow");
+    assert_eq!(tomorrow["dueAt"], Value::Null);
+}
+
+#[tokio::test]
+async fn edits_keep_dates_in_words() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    add_todo(&mut client, "Plan the party @home").await;
+    let todo_id = todos(&mut client, "").await[0]["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let response = client.get(&format!("/todos/{}/edit", todo_id)).await;
+    assert!(response.body.contains(r#"value="Plan the party @home""#));
+
+    let response = client
+        .put(
+            &format!("/todos/{}", todo_id),
+            &[("content", "Plan the party tomorrow @work")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Plan the party tomorrow"));
+    assert!(response.body.contains(r#"data-list="work""#));
+}
+
+#[tokio::test]
+async fn todos_can_be_filtered_by_list() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    add_todo(&mut client, "Buy milk @groceries").await;
+    add_todo(&mut client, "Buy bread @groceries").await;
+    add_todo(&mut client, "Write report @work").await;
+    add_todo(&mut client, "Call mum").await;
+
+    let contents: Vec<_> = todos(&mut client, "list=@groceries")
+        .await
+        .iter()
+        .map(|todo| todo["content"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(contents, ["Buy milk", "Buy bread"]);
+
+    let response = client.get("/api/v1/lists").await;
+    let lists: Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(lists[0]["name"], "groceries");
+    assert_eq!(lists[0]["todos"], 2);
+    assert_eq!(lists[1]["name"], "work");
+
+    let response = client.get("/todos").await;
+    assert!(response.body.contains(r#"value="work""#));
+
+    let response = client.get("/api/v1/todos?list=no.dots").await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn quick_add_is_previewed() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let response = client
+        .get("/todos/preview?content=Water+plants+every+monday+%23garden")
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Water plants"));
+    assert!(response.body.contains("↻ Weekly on Mon"));
+    assert!(response.body.contains("#garden"));
+
+    let response = client.get("/todos/preview?content=Water+plants").await;
+    assert_eq!(response.body, "");
+}
@@ -0,0 +1,154 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+use time::{macros::format_description, Duration, OffsetDateTime};
+
+mod common;
+
+use common::{TestApp, TestClient};
+
+async fn todos(client: &mut TestClient) -> Vec<Value> {
+    let response = client.get("/api/v1/todos").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body["todos"].as_array().unwrap().clone()
+}
+
+/// Adds a todo due at `due` and returns its id.
+async fn add_todo(client: &mut TestClient, content: &str, due: &str, repeat: &str) -> String {
+    let response = client
+        .post(
+            "/todos",
+            &[("content", content), ("due", due), ("repeat", repeat)],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    todos(client).await.last().unwrap()["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+/// A `datetime-local` value `days` from now.
+fn days_from_now(days: i64) -> String {
+    (OffsetDateTime::now_utc() + Duration::days(days))
+        .format(format_description!("[year]-[month]-[day]T[hour]:[minute]"))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn completing_a_recurring_todo_schedules_the_next_one() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let due = days_from_now(1);
+    let todo_id = add_todo(&mut client, "Water plants #home !2", &due, "FREQ=DAILY").await;
+
+    let response = client.get("/todos").await;
+    assert!(response.body.contains("↻ Daily"));
+
+    let response = client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["HX-Trigger"], "todos-changed");
+
+    let listed = todos(&mut client).await;
+    assert_eq!(listed.len(), 2);
+    let (done, next) = (&listed[0], &listed[1]);
+    assert_eq!(done["done"], true);
+    assert_eq!(done["recurrence"], Value::Null);
+
+    assert_eq!(next["done"], false);
+    assert_eq!(next["content"], "Water plants");
+    assert_eq!(next["priority"], "medium");
+    assert_eq!(next["tags"][0]["name"], "home");
+    assert_eq!(next["recurrence"], "FREQ=DAILY");
+    assert!(next["dueAt"]
+        .as_str()
+        .unwrap()
+        .starts_with(&days_from_now(2)[..10]));
+
+    // Undoing and redoing the first one doesn't schedule another.
+    client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    let response = client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    assert!(!response.headers.contains_key("HX-Trigger"));
+    assert_eq!(todos(&mut client).await.len(), 2);
+}
+
+#[tokio::test]
+async fn series_can_be_edited_and_stopped() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let due = days_from_now(1);
+    let todo_id = add_todo(&mut client, "Pay rent", &due, "").await;
+    let uri = format!("/todos/{}", todo_id);
+
+    let response = client
+        .put(
+            &uri,
+            &[
+                ("content", "Pay rent"),
+                ("due", &due),
+                ("repeat", "rrule:freq=monthly;count=3"),
+            ],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("↻ Monthly, 3 left"));
+
+    let response = client.get(&format!("{}/edit", uri)).await;
+    assert!(response.body.contains(r#"value="FREQ=MONTHLY;COUNT=3""#));
+    assert!(response.body.contains("Stop repeating"));
+
+    let response = client
+        .put(
+            &uri,
+            &[
+                ("content", "Pay rent"),
+                ("due", &due),
+                ("repeat", "FREQ=HOURLY"),
+            ],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = client
+        .put(
+            &uri,
+            &[("content", "Pay rent"), ("due", &due), ("repeat", "")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.body.contains('↻'));
+
+    client.post(&format!("{}/toggle", uri), &[]).await;
+    assert_eq!(todos(&mut client).await.len(), 1);
+}
+
+#[tokio::test]
+async fn counted_series_end() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let due = days_from_now(1);
+    let first = add_todo(&mut client, "Take pills", &due, "FREQ=DAILY;COUNT=2").await;
+
+    client.post(&format!("/todos/{}/toggle", first), &[]).await;
+    let second = todos(&mut client).await[1].clone();
+    assert_eq!(second["recurrence"], "FREQ=DAILY;COUNT=1");
+
+    let response = client
+        .post(
+            &format!("/todos/{}/toggle", second["todoId"].as_str().unwrap()),
+            &[],
+        )
+        .await;
+    assert!(!response.headers.contains_key("HX-Trigger"));
+    assert_eq!(todos(&mut client).await.len(), 2);
+}
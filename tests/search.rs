@@ -0,0 +1,113 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+mod common;
+
+use common::{TestApp, TestClient};
+
+async fn add_todos(client: &mut TestClient, contents: &[&str]) {
+    for content in contents {
+        let response = client.post("/todos", &[("content", content)]).await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn active_search_highlights_prefix_matches() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todos(&mut client, &["Buy groceries", "Walk the dog"]).await;
+
+    let response = client.get("/todos/page?q=gro").await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("<mark>groceries</mark>"));
+    assert!(!response.body.contains("dog"));
+}
+
+#[tokio::test]
+async fn clearing_the_search_lists_everything() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todos(&mut client, &["Buy groceries", "Walk the dog"]).await;
+
+    let response = client.get("/todos/page?q=").await;
+
+    assert!(response.body.contains("Buy groceries"));
+    assert!(response.body.contains("Walk the dog"));
+    assert!(!response.body.contains("<mark>"));
+}
+
+#[tokio::test]
+async fn snippets_escape_content() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todos(&mut client, &["<script>alert(1)</script> homework"]).await;
+
+    let response = client.get("/todos/page?q=homework").await;
+
+    assert!(!response.body.contains("<script>"));
+    assert!(response.body.contains("<mark>homework</mark>"));
+}
+
+#[tokio::test]
+async fn search_only_covers_own_todos() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut bob = app.logged_in_client().await;
+    add_todos(&mut alice, &["Secret plans"]).await;
+
+    let response = bob.get("/todos/page?q=secret").await;
+
+    assert!(!response.body.contains("plans"));
+    assert!(response.body.contains("No matching tasks."));
+}
+
+#[tokio::test]
+async fn json_listing_accepts_a_query() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todos(
+        &mut client,
+        &[
+            "Paint fence",
+            "Paint the fence red, fence post too",
+            "Mow lawn",
+        ],
+    )
+    .await;
+
+    let response = client.get("/api/v1/todos").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(body["todos"].as_array().unwrap().len(), 3);
+
+    let response = client.get("/api/v1/todos?q=fence").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+    let matches = body["todos"].as_array().unwrap();
+
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0]["content"], "Paint the fence red, fence post too");
+    assert!(matches[0]["rank"].as_f64() > matches[1]["rank"].as_f64());
+    assert!(matches[0]["snippet"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|fragment| fragment["highlighted"] == true && fragment["text"] == "fence"));
+}
+
+#[tokio::test]
+async fn search_respects_filters() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todos(&mut client, &["Call mum", "Call the bank"]).await;
+
+    let response = client.get("/todos/page?q=mum").await;
+    let mum = common::todo_ids(&response.body)[0];
+    client.post(&format!("/todos/{}/toggle", mum), &[]).await;
+
+    let response = client.get("/todos/page?q=call&status=open").await;
+
+    assert!(!response.body.contains("mum"));
+    assert!(response.body.contains("bank"));
+}
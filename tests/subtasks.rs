@@ -0,0 +1,137 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+use uuid::Uuid;
+
+mod common;
+
+use common::{todo_ids, TestApp, TestClient};
+
+/// Adds a todo, optionally as a subtask, and returns its id.
+async fn add_todo(client: &mut TestClient, content: &str, parent: Option<Uuid>) -> Uuid {
+    let parent = parent.map(|id| id.to_string()).unwrap_or_default();
+    let mut form = vec![("content", content)];
+    if !parent.is_empty() {
+        form.push(("parent", &parent));
+    }
+
+    let response = client.post("/todos", &form).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    todos(client)
+        .await
+        .into_iter()
+        .find(|todo| todo["content"] == content)
+        .and_then(|todo| todo["todoId"].as_str().unwrap().parse().ok())
+        .unwrap()
+}
+
+async fn todos(client: &mut TestClient) -> Vec<Value> {
+    let response = client.get("/api/v1/todos?limit=200").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body["todos"].as_array().unwrap().clone()
+}
+
+async fn done(client: &mut TestClient) -> Vec<String> {
+    todos(client)
+        .await
+        .into_iter()
+        .filter(|todo| todo["done"] == true)
+        .map(|todo| todo["content"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn subtasks_render_under_their_parent() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let parent = add_todo(&mut client, "Pack", None).await;
+    let socks = add_todo(&mut client, "Socks", Some(parent)).await;
+    let shirts = add_todo(&mut client, "Shirts", Some(parent)).await;
+    let other = add_todo(&mut client, "Book flights", None).await;
+
+    // The API lists every todo, subtasks included.
+    let listed = todos(&mut client).await;
+    assert_eq!(listed.len(), 4);
+    assert_eq!(listed[1]["parentId"], parent.to_string());
+
+    // Pages only count top-level todos, each followed by its subtasks.
+    let page = client.get("/todos/page?limit=1").await;
+    assert_eq!(todo_ids(&page.body), [parent, socks, shirts]);
+    assert!(page.body.contains("0/2 done"));
+    assert!(page.body.contains("Load more"));
+
+    let response = client.get("/todos").await;
+    assert_eq!(todo_ids(&response.body), [parent, socks, shirts, other]);
+
+    // Toggling a subtask redraws the whole tree, for the parent's progress.
+    let response = client.post(&format!("/todos/{}/toggle", socks), &[]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers["HX-Retarget"],
+        format!("#todo-{}", parent).as_str()
+    );
+    assert!(response.body.contains("1/2 done"));
+}
+
+#[tokio::test]
+async fn completing_a_todo_can_complete_its_subtasks() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let trip = add_todo(&mut client, "Trip", None).await;
+    let pack = add_todo(&mut client, "Pack", Some(trip)).await;
+    add_todo(&mut client, "Socks", Some(pack)).await;
+    add_todo(&mut client, "Unrelated", None).await;
+
+    // Toggling only touches the todo itself.
+    client.post(&format!("/todos/{}/toggle", trip), &[]).await;
+    assert_eq!(done(&mut client).await, ["Trip"]);
+    client.post(&format!("/todos/{}/toggle", trip), &[]).await;
+
+    let response = client.post(&format!("/todos/{}/complete", trip), &[]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("1/1 done"));
+    assert_eq!(done(&mut client).await, ["Trip", "Pack", "Socks"]);
+}
+
+#[tokio::test]
+async fn deleting_a_todo_deletes_its_subtasks() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let trip = add_todo(&mut client, "Trip", None).await;
+    let pack = add_todo(&mut client, "Pack", Some(trip)).await;
+    let socks = add_todo(&mut client, "Socks", Some(pack)).await;
+    add_todo(&mut client, "Shirts", Some(pack)).await;
+
+    let response = client.delete(&format!("/todos/{}", socks)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("0/1 done"));
+
+    let response = client.delete(&format!("/todos/{}", trip)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(todos(&mut client).await.is_empty());
+}
+
+#[tokio::test]
+async fn subtasks_need_a_parent_of_your_own() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut bob = app.logged_in_client().await;
+
+    let theirs = add_todo(&mut alice, "Alice's", None).await;
+
+    for parent in [theirs, Uuid::new_v4()] {
+        let response = bob
+            .post(
+                "/todos",
+                &[("content", "Sneaky"), ("parent", &parent.to_string())],
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    assert!(todos(&mut bob).await.is_empty());
+}
@@ -0,0 +1,127 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+mod common;
+
+use common::{add_todo, TestApp, TestClient};
+
+async fn sync(client: &mut TestClient, since: Option<&Value>) -> Value {
+    let uri = match since {
+        Some(cursor) => format!("/api/v1/sync?since={}", cursor.as_str().unwrap()),
+        None => "/api/v1/sync".to_owned(),
+    };
+    let response = client.get(&uri).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    serde_json::from_str(&response.body).unwrap()
+}
+
+#[tokio::test]
+async fn syncing_from_scratch_gets_every_todo() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "Call mum", None).await;
+    add_todo(&mut client, "Call the bank", None).await;
+
+    let body = sync(&mut client, None).await;
+
+    let changed = body["changed"].as_array().unwrap();
+    assert_eq!(changed.len(), 2);
+    assert_eq!(changed[0]["content"], "Call mum");
+    assert!(changed[0]["updatedAt"].is_string());
+    assert_eq!(body["deleted"], serde_json::json!([]));
+    assert_eq!(body["hasMore"], false);
+}
+
+#[tokio::test]
+async fn only_what_changed_since_the_cursor_is_returned() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let todo_id = add_todo(&mut client, "Call mum", None).await;
+    add_todo(&mut client, "Call the bank", None).await;
+    let body = sync(&mut client, None).await;
+    let cursor = body["cursor"].clone();
+    let updated_at = body["changed"][0]["updatedAt"].clone();
+
+    let response = client
+        .post(&format!("/todos/{}/toggle", todo_id), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = sync(&mut client, Some(&cursor)).await;
+
+    let changed = body["changed"].as_array().unwrap();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0]["todoId"], todo_id.as_str());
+    assert_eq!(changed[0]["done"], true);
+    assert_ne!(changed[0]["updatedAt"], updated_at);
+    assert_ne!(body["cursor"], cursor);
+
+    // Nothing new since.
+    let cursor = body["cursor"].clone();
+    let body = sync(&mut client, Some(&cursor)).await;
+    assert_eq!(body["changed"], serde_json::json!([]));
+    assert_eq!(body["cursor"], cursor);
+}
+
+#[tokio::test]
+async fn deleted_todos_come_back_as_tombstones() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    let todo_id = add_todo(&mut client, "Call mum", None).await;
+    let cursor = sync(&mut client, None).await["cursor"].clone();
+
+    let response = client.delete(&format!("/todos/{}", todo_id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = sync(&mut client, Some(&cursor)).await;
+    assert_eq!(body["changed"], serde_json::json!([]));
+    assert_eq!(body["deleted"][0]["todoId"], todo_id.as_str());
+    assert!(body["deleted"][0]["deletedAt"].is_string());
+
+    let response = client.delete(&format!("/trash/{}", todo_id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = sync(&mut client, Some(&cursor)).await;
+    assert_eq!(body["deleted"][0]["todoId"], todo_id.as_str());
+}
+
+#[tokio::test]
+async fn big_syncs_come_a_page_at_a_time() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    for n in 0..201 {
+        let content = format!("Todo {}", n);
+        client.post("/todos", &[("content", &content)]).await;
+    }
+
+    let body = sync(&mut client, None).await;
+    assert_eq!(body["changed"].as_array().unwrap().len(), 200);
+    assert_eq!(body["hasMore"], true);
+
+    let body = sync(&mut client, Some(&body["cursor"])).await;
+    assert_eq!(body["changed"].as_array().unwrap().len(), 1);
+    assert_eq!(body["changed"][0]["content"], "Todo 200");
+    assert_eq!(body["hasMore"], false);
+}
+
+#[tokio::test]
+async fn other_users_todos_are_not_synced() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut bob = app.logged_in_client().await;
+    add_todo(&mut bob, "Bob's todo", None).await;
+
+    let body = sync(&mut alice, None).await;
+
+    assert_eq!(body["changed"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn garbage_cursors_are_rejected() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    for since in ["nope", "42", "1.nope"] {
+        let response = client.get(&format!("/api/v1/sync?since={}", since)).await;
+
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
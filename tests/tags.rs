@@ -0,0 +1,275 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+mod common;
+
+use common::{TestApp, TestClient};
+
+async fn add_todo(client: &mut TestClient, content: &str) -> StatusCode {
+    client.post("/todos", &[("content", content)]).await.status
+}
+
+async fn todos(client: &mut TestClient, query: &str) -> Vec<Value> {
+    let response = client.get(&format!("/api/v1/todos?{}", query)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body["todos"].as_array().unwrap().clone()
+}
+
+/// Each todo's content and tag names.
+async fn tagged(client: &mut TestClient, query: &str) -> Vec<(String, Vec<String>)> {
+    todos(client, query)
+        .await
+        .into_iter()
+        .map(|todo| {
+            let tags = todo["tags"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|tag| tag["name"].as_str().unwrap().to_owned())
+                .collect();
+            (todo["content"].as_str().unwrap().to_owned(), tags)
+        })
+        .collect()
+}
+
+/// The user's tags by name, as `(name, id, todo count)`.
+async fn tags(client: &mut TestClient) -> Vec<(String, String, i64)> {
+    let response = client.get("/api/v1/tags").await;
+    let body: Value = serde_json::from_str(&response.body).unwrap();
+
+    body.as_array()
+        .unwrap()
+        .iter()
+        .map(|tag| {
+            (
+                tag["name"].as_str().unwrap().to_owned(),
+                tag["tagId"].as_str().unwrap().to_owned(),
+                tag["todos"].as_i64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn owned(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+}
+
+#[tokio::test]
+async fn tags_are_parsed_out_of_content() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    assert_eq!(
+        add_todo(&mut client, "Buy milk #Errands #home").await,
+        StatusCode::OK
+    );
+    assert_eq!(add_todo(&mut client, "Learn C#").await, StatusCode::OK);
+    assert_eq!(
+        add_todo(&mut client, "#just #tags").await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+
+    assert_eq!(
+        tagged(&mut client, "").await,
+        [
+            ("Buy milk".to_owned(), owned(&["errands", "home"])),
+            ("Learn C#".to_owned(), vec![]),
+        ]
+    );
+
+    let counts: Vec<_> = tags(&mut client)
+        .await
+        .into_iter()
+        .map(|(name, _, todos)| (name, todos))
+        .collect();
+    assert_eq!(counts, [("errands".to_owned(), 1), ("home".to_owned(), 1)]);
+
+    let page = client.get("/todos/page").await;
+    assert!(page.body.contains("data-tag=\"errands\""));
+}
+
+#[tokio::test]
+async fn lists_filter_by_tag() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "Buy milk #errands").await;
+    add_todo(&mut client, "Post letter #errands #urgent").await;
+    add_todo(&mut client, "Write report #work").await;
+
+    let contents = |todos: Vec<(String, Vec<String>)>| -> Vec<String> {
+        todos.into_iter().map(|(content, _)| content).collect()
+    };
+    assert_eq!(
+        contents(tagged(&mut client, "tag=errands").await),
+        ["Buy milk", "Post letter"]
+    );
+    assert_eq!(
+        contents(tagged(&mut client, "tag=%23URGENT").await),
+        ["Post letter"]
+    );
+    assert!(tagged(&mut client, "tag=nope").await.is_empty());
+
+    let response = client.get("/api/v1/todos?tag=two+words").await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let page = client.get("/todos/page?tag=work").await;
+    assert!(page.body.contains("Write report"));
+    assert!(!page.body.contains("Buy milk"));
+}
+
+#[tokio::test]
+async fn editing_a_todo_replaces_its_tags() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "Buy milk #errands #home").await;
+    let todo_id = todos(&mut client, "").await[0]["todoId"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let edit = client.get(&format!("/todos/{}/edit", todo_id)).await;
+    assert!(edit.body.contains("value=\"Buy milk #errands #home\""));
+
+    let response = client
+        .put(
+            &format!("/todos/{}", todo_id),
+            &[("content", "Buy oat milk #home #shop")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_eq!(
+        tagged(&mut client, "").await,
+        [("Buy oat milk".to_owned(), owned(&["home", "shop"]))]
+    );
+    // The tag stays around for reuse, on no todos.
+    let counts: Vec<_> = tags(&mut client)
+        .await
+        .into_iter()
+        .map(|(name, _, todos)| (name, todos))
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            ("errands".to_owned(), 0),
+            ("home".to_owned(), 1),
+            ("shop".to_owned(), 1)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tags_can_be_renamed_merged_and_deleted() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+    add_todo(&mut client, "Buy milk #errands").await;
+    add_todo(&mut client, "Post letter #chores #errands").await;
+    add_todo(&mut client, "Hoover #housework").await;
+
+    let id = |tags: &[(String, String, i64)], name: &str| {
+        tags.iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, id, _)| id.clone())
+            .unwrap()
+    };
+    let all = tags(&mut client).await;
+
+    // Renaming onto another tag's name is what merging is for.
+    let response = client
+        .put(
+            &format!("/tags/{}", id(&all, "errands")),
+            &[("name", "chores"), ("color", "#000000")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    let response = client
+        .put(
+            &format!("/tags/{}", id(&all, "errands")),
+            &[("name", "#Shopping"), ("color", "#123abc")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("value=\"shopping\""));
+    assert!(response.body.contains("value=\"#123abc\""));
+
+    let response = client
+        .put(
+            &format!("/tags/{}", id(&all, "chores")),
+            &[("name", "chores"), ("color", "red")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = client
+        .post(
+            &format!("/tags/{}/merge", id(&all, "housework")),
+            &[("into", &id(&all, "chores"))],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_eq!(
+        tagged(&mut client, "").await,
+        [
+            ("Buy milk".to_owned(), owned(&["shopping"])),
+            ("Post letter".to_owned(), owned(&["chores", "shopping"])),
+            ("Hoover".to_owned(), owned(&["chores"])),
+        ]
+    );
+
+    let response = client
+        .delete(&format!("/tags/{}", id(&all, "errands")))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_eq!(
+        tagged(&mut client, "").await,
+        [
+            ("Buy milk".to_owned(), vec![]),
+            ("Post letter".to_owned(), owned(&["chores"])),
+            ("Hoover".to_owned(), owned(&["chores"])),
+        ]
+    );
+    let names: Vec<_> = tags(&mut client)
+        .await
+        .into_iter()
+        .map(|(name, _, todos)| (name, todos))
+        .collect();
+    assert_eq!(names, [("chores".to_owned(), 2)]);
+}
+
+#[tokio::test]
+async fn tags_belong_to_their_user() {
+    let app = TestApp::new().await;
+    let mut alice = app.logged_in_client().await;
+    let mut bob = app.logged_in_client().await;
+    add_todo(&mut alice, "Buy milk #errands").await;
+    add_todo(&mut bob, "Buy bread #errands #food").await;
+
+    let alices = tags(&mut alice).await;
+    let bobs = tags(&mut bob).await;
+    assert_eq!(alices.len(), 1);
+    assert_ne!(alices[0].1, bobs[0].1);
+
+    let response = bob
+        .put(
+            &format!("/tags/{}", alices[0].1),
+            &[("name", "mine"), ("color", "#000000")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = bob
+        .post(
+            &format!("/tags/{}/merge", bobs[1].1),
+            &[("into", &alices[0].1)],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    bob.delete(&format!("/tags/{}", alices[0].1)).await;
+    assert_eq!(tags(&mut alice).await, alices);
+}
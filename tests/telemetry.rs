@@ -0,0 +1,148 @@
+use std::{
+    io,
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use axum::{extract::State, http::StatusCode, routing::post, Router};
+use flyio_rust::telemetry::{self, LogFormat, OtlpConfig};
+use serde_json::Value;
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, Registry};
+
+mod common;
+
+use common::{TestApp, PASSWORD};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+/// Stands in for an OTLP/HTTP collector, counting the export requests it gets.
+fn spawn_collector() -> (String, Arc<AtomicUsize>) {
+    let exports = Arc::new(AtomicUsize::new(0));
+
+    let collector = Router::new()
+        .route(
+            "/v1/traces",
+            post(|State(exports): State<Arc<AtomicUsize>>| async move {
+                exports.fetch_add(1, Ordering::SeqCst);
+                StatusCode::OK
+            }),
+        )
+        .with_state(exports.clone());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(collector.into_make_service()),
+    );
+
+    (endpoint, exports)
+}
+
+// The batch exporter runs on the runtime and `force_flush` blocks until it's
+// done, so this needs more than one worker.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn spans_are_exported_and_errors_carry_the_callers_trace_id() {
+    let (endpoint, exports) = spawn_collector();
+
+    let config = OtlpConfig {
+        endpoint,
+        service_name: "flyio-rust-test".to_owned(),
+        sampling_ratio: 1.0,
+    };
+    let provider = telemetry::tracer_provider(Some(&config)).unwrap();
+    let subscriber = Registry::default().with(telemetry::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = TestApp::new().await;
+    let mut client = app.client().with_header(
+        "traceparent",
+        &format!("00-{}-00f067aa0ba902b7-01", TRACE_ID),
+    );
+
+    let response = client.signup("not-an-email", PASSWORD).await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(response
+        .body
+        .contains(&format!(r#""traceId":"{}""#, TRACE_ID)));
+
+    provider.force_flush();
+
+    assert!(exports.load(Ordering::SeqCst) > 0);
+}
+
+#[tokio::test]
+async fn errors_carry_a_fresh_trace_id_without_a_caller() {
+    let provider = telemetry::tracer_provider(None).unwrap();
+    let subscriber = Registry::default().with(telemetry::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = TestApp::new().await;
+    let response = app.client().signup("not-an-email", PASSWORD).await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(response.body.contains(r#""traceId":""#));
+    assert!(!response.body.contains(TRACE_ID));
+}
+
+/// Collects everything written by the log layer.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn json_logs_carry_request_context() {
+    let logs = Captured::default();
+    let subscriber = Registry::default().with(telemetry::log_layer(LogFormat::Json, logs.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = TestApp::new().await;
+    let mut client = app
+        .logged_in_client()
+        .await
+        .with_header("x-request-id", "req-1234");
+
+    let response = client.post("/todos", &[("content", "")]).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let error_line = logs
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .find(|line| {
+            line["fields"]["message"]
+                .as_str()
+                .is_some_and(|message| message.starts_with("API error"))
+        })
+        .expect("the API error should have been logged");
+
+    let request_span = &error_line["spans"][0];
+    assert_eq!(request_span["name"], "request");
+    assert_eq!(request_span["request_id"], "req-1234");
+    assert_eq!(request_span["route"], "/todos");
+    assert!(request_span["user_id"].as_str().is_some());
+}
@@ -0,0 +1,134 @@
+use axum::http::StatusCode;
+use flyio_rust::data::trash;
+use serde_json::Value;
+use time::{Duration, OffsetDateTime};
+
+mod common;
+
+use common::{add_todo, contents, json, todo_ids, TestApp};
+
+#[tokio::test]
+async fn deleted_todos_can_be_restored() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let parent = add_todo(&mut client, "Plan trip", None).await;
+    add_todo(&mut client, "Book flights", Some(&parent)).await;
+    add_todo(&mut client, "Water plants", None).await;
+
+    let response = client.delete(&format!("/todos/{}", parent)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains(r#"hx-swap-oob="true""#));
+    assert!(response
+        .body
+        .contains(&format!(r#"hx-post="/trash/{}/restore""#, parent)));
+
+    assert_eq!(
+        contents(&json(&mut client, "/api/v1/todos").await),
+        ["Water plants"]
+    );
+    // The subtask went with its parent, and is only listed through it.
+    let trash = json(&mut client, "/api/v1/trash").await;
+    assert_eq!(contents(&trash), ["Plan trip"]);
+    assert!(trash[0]["deletedAt"].is_string());
+
+    let response = client.get("/trash").await;
+    assert!(response.body.contains("Plan trip"));
+
+    let response = client
+        .post(&format!("/trash/{}/restore", parent), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["HX-Trigger"], "todos-changed");
+
+    assert_eq!(
+        contents(&json(&mut client, "/api/v1/todos").await),
+        ["Plan trip", "Book flights", "Water plants"]
+    );
+    assert!(json(&mut client, "/api/v1/trash").await.is_empty());
+
+    let response = client
+        .post(&format!("/trash/{}/restore", parent), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn subtasks_restored_after_their_parent_was_deleted_come_back_on_their_own() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let parent = add_todo(&mut client, "Plan trip", None).await;
+    let child = add_todo(&mut client, "Book flights", Some(&parent)).await;
+
+    let response = client.delete(&format!("/todos/{}", child)).await;
+    // The parent is re-rendered without it, along with the toast.
+    assert_eq!(todo_ids(&response.body).len(), 1);
+    assert!(response.body.contains("Undo"));
+    client.delete(&format!("/todos/{}", parent)).await;
+
+    assert_eq!(
+        contents(&json(&mut client, "/api/v1/trash").await),
+        ["Plan trip", "Book flights"]
+    );
+
+    client.post(&format!("/trash/{}/restore", child), &[]).await;
+    let todos = json(&mut client, "/api/v1/todos").await;
+    assert_eq!(contents(&todos), ["Book flights"]);
+    assert_eq!(todos[0]["parentId"], Value::Null);
+}
+
+#[tokio::test]
+async fn todos_can_be_deleted_for_good() {
+    let app = TestApp::new().await;
+    let mut client = app.logged_in_client().await;
+
+    let first = add_todo(&mut client, "Call mum", None).await;
+    let second = add_todo(&mut client, "Call the bank", None).await;
+    let third = add_todo(&mut client, "Call the plumber", None).await;
+
+    // Only what's in the trash can be deleted for good.
+    let response = client.delete(&format!("/trash/{}", first)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(json(&mut client, "/api/v1/todos").await.len(), 3);
+
+    for todo_id in [&first, &second, &third] {
+        client.delete(&format!("/todos/{}", todo_id)).await;
+    }
+    let response = client.delete(&format!("/trash/{}", first)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = client.delete(&format!("/trash/{}", first)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(
+        contents(&json(&mut client, "/api/v1/trash").await),
+        ["Call the plumber", "Call the bank"]
+    );
+
+    let response = client.delete("/trash").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("The trash is empty."));
+    assert!(json(&mut client, "/api/v1/trash").await.is_empty());
+}
+
+#[tokio::test]
+async fn the_trash_is_purged_after_the_retention_period() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else {
+        return;
+    };
+    let mut client = app.logged_in_client().await;
+
+    let todo_id = add_todo(&mut client, "Call mum", None).await;
+    client.delete(&format!("/todos/{}", todo_id)).await;
+
+    let purged = trash::purge_trash(db, OffsetDateTime::now_utc() - Duration::days(1))
+        .await
+        .unwrap();
+    assert_eq!(purged, 0);
+
+    let purged = trash::purge_trash(db, OffsetDateTime::now_utc() + Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+    assert!(json(&mut client, "/api/v1/trash").await.is_empty());
+}
@@ -0,0 +1,81 @@
+//! Account administration through `data::user`, as used by the admin CLI.
+//!
+//! These need Postgres and are skipped when `DATABASE_URL` isn't set.
+
+use axum::http::StatusCode;
+use flyio_rust::data::user;
+
+mod common;
+
+use common::{TestApp, PASSWORD};
+
+#[tokio::test]
+async fn disabled_users_are_logged_out_and_cannot_log_in() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+
+    let mut client = app.client();
+    client.signup("disabled@example.com", PASSWORD).await;
+    client.login("disabled@example.com", PASSWORD).await;
+    assert_eq!(client.get("/todos").await.status, StatusCode::OK);
+
+    assert!(user::disable_user(db, "disabled@example.com")
+        .await
+        .unwrap());
+
+    let response = client.get("/todos").await;
+    assert!(response.status.is_redirection());
+
+    let mut client = app.client();
+    client.login("disabled@example.com", PASSWORD).await;
+    assert!(client.get("/todos").await.status.is_redirection());
+}
+
+#[tokio::test]
+async fn resetting_a_password_ends_existing_sessions() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+
+    let mut client = app.client();
+    client.signup("reset@example.com", PASSWORD).await;
+    client.login("reset@example.com", PASSWORD).await;
+
+    assert!(user::set_password(db, "reset@example.com", "N3wSecret!")
+        .await
+        .unwrap());
+
+    assert!(client.get("/todos").await.status.is_redirection());
+
+    let mut client = app.client();
+    client.login("reset@example.com", "N3wSecret!").await;
+    assert_eq!(client.get("/todos").await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn unknown_emails_are_reported() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+
+    assert!(!user::disable_user(db, "nobody@example.com").await.unwrap());
+    assert!(!user::set_password(db, "nobody@example.com", PASSWORD)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn users_are_listed_oldest_first() {
+    let app = TestApp::new().await;
+    let Some(db) = app.db() else { return };
+
+    let mut client = app.client();
+    client.signup("first@example.com", PASSWORD).await;
+    client.signup("second@example.com", PASSWORD).await;
+    user::disable_user(db, "second@example.com").await.unwrap();
+
+    let users = user::list_users(db).await.unwrap();
+    let emails: Vec<_> = users.iter().map(|user| user.email.as_str()).collect();
+
+    assert_eq!(emails, ["first@example.com", "second@example.com"]);
+    assert!(!users[0].is_disabled());
+    assert!(users[1].is_disabled());
+}